@@ -0,0 +1,832 @@
+//! The compilation of Speak syntax trees to bytecode, the instructions run by the
+//! [`vm`](crate::vm). A program compiles to a chunk of instructions, and each function
+//! literal to a chunk of its own, so that the body of a function is compiled once and
+//! not walked on every call. The parameters and local names of a function are resolved
+//! to slots of its calls, and only globals and captured names are looked up by name.
+
+use super::{
+    error::{Err, ErrorReason, Span},
+    eval::value::Value,
+    lexer::Kind,
+    parser::{MatchArm, Node, Pattern},
+};
+use std::{collections::HashSet, rc::Rc};
+
+/// An instruction of the VM. Operands index the tables of the chunk; `node` operands are
+/// the syntax tree nodes the instruction was compiled from, which describe its errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Pushes a constant.
+    Const(u32),
+    /// Pushes the value bound to the identifier node.
+    Load(u32),
+    /// Pushes the value of the slot of a local of the function, or if it is not bound in
+    /// the function, the value bound to the identifier node.
+    LoadLocal {
+        slot: u16,
+        node: u32,
+    },
+    /// Pushes the value of the module member node.
+    Member(u32),
+    /// Raises an error of the chunk.
+    Fail(u32),
+    /// Raises the error of negating the operand of the unary expression node in place.
+    NegateIdent(u32),
+    /// Pops the items of the array literal node, and pushes the array.
+    Array(u32),
+    /// Pops the fields of the object literal node, and pushes the object.
+    Object(u32),
+    /// Pops the operands of the binary expression node, and pushes its value.
+    Binary(u32),
+    /// Pops an object, and pushes the field read by the accessor node.
+    Field(u32),
+    /// Pops a value and binds it to the identifier node, pushing the assignment.
+    Assign(u32),
+    /// Pops a value and binds it to a local of the function, pushing the assignment. A
+    /// local not yet bound is bound in its slot if the assignment is in the function's
+    /// own frame, `top`, and no outer frame binds the identifier node, as `eval::assign`.
+    AssignLocal {
+        slot: u16,
+        node: u32,
+        top: bool,
+    },
+    /// Asserts that the value is an object with the field assigned by the accessor node.
+    FieldTarget(u32),
+    /// Pops a value and an object, and sets the field assigned by the accessor node.
+    SetField(u32),
+    /// Pops the value of the operand node, which cannot be assigned to, and raises it.
+    AssignError(u32),
+    /// Asserts that the value of the operand node is an array.
+    ArrayTarget(u32),
+    /// Asserts that the value of the operand node is an array or a string.
+    IndexTarget(u32),
+    /// Converts the value of the index node to an index.
+    ToIndex(u32),
+    /// Pops an index and an array or a string, and pushes the item at the index.
+    Index,
+    /// Pops the start and the end index, if present, and an array or a string, and pushes
+    /// the slice.
+    Slice {
+        start: bool,
+        end: bool,
+    },
+    /// Pops a value, an index and an array, and sets the item of the operand node's array.
+    SetIndex(u32),
+    /// Binds the function compiled to a proto of the chunk to its name.
+    Define(u32),
+    /// Pushes the anonymous function compiled to a proto of the chunk.
+    Closure(u32),
+    /// Pops a function and its arguments, and calls it at the call node.
+    Call {
+        argc: u32,
+        node: u32,
+    },
+    /// Calls a function in the tail position of a function body. The callee takes over
+    /// the caller's frame if it returns the same type, as the tree walker's thunks do.
+    TailCall {
+        argc: u32,
+        node: u32,
+    },
+    /// Runs the following instructions outside of any loop, as arguments and indices are.
+    OutOfLoop,
+    /// Restores the loop the instructions ran in before `OutOfLoop`.
+    RestoreLoop,
+    Jump(u32),
+    /// Pops the condition of the if expression node, and jumps if it does not hold.
+    JumpUnless {
+        target: u32,
+        node: u32,
+    },
+    /// Pops the value of a statement of a clause of an if expression, or if it continues or
+    /// breaks a loop, leaves the clause with it as the clause's value.
+    BlockCheck {
+        end: u32,
+    },
+    /// Pops the iterable of the for expression node, and enters the loop.
+    ForBegin(u32),
+    /// Enters a while loop, whose condition is checked by `JumpUnless`.
+    WhileBegin,
+    /// Binds the next item to the loop variable, or jumps out of the loop when done.
+    ForNext {
+        exit: u32,
+    },
+    /// Pops the value of a statement of the loop body, and continues or leaves the loop.
+    LoopCheck {
+        next: u32,
+        exit: u32,
+    },
+    /// Leaves the loop, and pushes its value.
+    ForEnd,
+    /// Matches the value being matched against the pattern of an arm of the match
+    /// expression node, binding its names in a frame of the arm, or jumps to the next arm.
+    MatchArm {
+        node: u32,
+        arm: u32,
+        next: u32,
+    },
+    /// Pops the value of the guard of an arm of the match expression node, and leaves the
+    /// arm for the next if it does not hold.
+    Guard {
+        node: u32,
+        arm: u32,
+        next: u32,
+    },
+    /// Leaves the arm taken, and replaces the value matched with the value of the arm.
+    EndArm,
+    /// Pushes the value of the continue literal node, in a loop.
+    Continue(u32),
+    /// Pushes the value of the break literal node, in a loop.
+    Break(u32),
+    /// Pops the value of a statement of a function body, and returns it if it is of the
+    /// function's return type.
+    Return {
+        last: bool,
+    },
+    /// Raises the error of a function body without a value of its return type.
+    NoReturn,
+    Pop,
+    /// Pops the value of the program, and stops.
+    Halt,
+}
+
+/// Chunk holds the instructions of a program or a function body, and the tables their
+/// operands index.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub ops: Vec<Op>,
+    pub consts: Vec<Value>,
+    pub nodes: Vec<Node>,
+    pub errs: Vec<Err>,
+    pub protos: Vec<Rc<Proto>>,
+    /// The names of the parameters and locals of a function, by slot.
+    pub locals: Vec<String>,
+    /// The loops and match arms the instructions being compiled run in, which bind
+    /// names in frames of their own.
+    depth: usize,
+}
+
+/// Proto is a function literal compiled to bytecode.
+#[derive(Debug)]
+pub struct Proto {
+    // defn must be of variant `FunctionLiteral`.
+    pub defn: Rc<Node>,
+    pub name: String,
+    /// The return type of the function's signature.
+    pub ret: String,
+    pub chunk: Rc<Chunk>,
+}
+
+impl Proto {
+    /// Compiles the body of a function literal. The statements of the body are checked
+    /// in order for the function's return value.
+    pub fn compile(defn: Rc<Node>) -> Proto {
+        let mut chunk = Chunk::default();
+        let (mut name, mut ret) = (String::new(), String::new());
+        if let Node::FunctionLiteral { sign, body, .. } = defn.as_ref() {
+            name = sign.0.string();
+            ret = sign.2.string();
+            chunk.locals = locals(&sign.1, body);
+            for (i, stmt) in body.iter().enumerate() {
+                let last = i + 1 == body.len();
+                chunk.expr(stmt, last);
+                chunk.emit(Op::Return { last });
+            }
+        }
+        chunk.emit(Op::NoReturn);
+
+        Proto {
+            defn,
+            name,
+            ret,
+            chunk: Rc::new(chunk),
+        }
+    }
+}
+
+impl Chunk {
+    /// Compiles the statements of a program, whose value is the value of the last.
+    pub fn compile(nodes: &[Node]) -> Chunk {
+        let mut chunk = Chunk::default();
+        for (i, node) in nodes.iter().enumerate() {
+            chunk.expr(node, false);
+            if i + 1 < nodes.len() {
+                chunk.emit(Op::Pop);
+            }
+        }
+        if nodes.is_empty() {
+            chunk.constant(Value::Empty);
+        }
+        chunk.emit(Op::Halt);
+        chunk
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+
+    /// The index the next instruction is emitted at.
+    fn here(&self) -> u32 {
+        self.ops.len() as u32
+    }
+
+    fn constant(&mut self, val: Value) {
+        self.consts.push(val);
+        self.emit(Op::Const(self.consts.len() as u32 - 1));
+    }
+
+    fn fail(&mut self, err: Err) {
+        self.errs.push(err);
+        self.emit(Op::Fail(self.errs.len() as u32 - 1));
+    }
+
+    /// Adds the node to the chunk. Only the parts of the node its errors describe are
+    /// kept, the bodies of functions, loops and clauses are compiled on their own.
+    fn node(&mut self, node: &Node) -> u32 {
+        let node = match node {
+            Node::FunctionCall {
+                function, position, ..
+            } => Node::FunctionCall {
+                function: function.clone(),
+                arguments: Vec::new(),
+                position: position.clone(),
+            },
+            Node::IfExpr {
+                condition,
+                position,
+                ..
+            } => Node::IfExpr {
+                condition: condition.clone(),
+                on_true: None,
+                on_false: None,
+                position: position.clone(),
+            },
+            Node::ForExpr {
+                variable,
+                iterable,
+                position,
+                ..
+            } => Node::ForExpr {
+                variable: variable.clone(),
+                iterable: iterable.clone(),
+                body: None,
+                position: position.clone(),
+            },
+            Node::WhileExpr {
+                condition,
+                position,
+                ..
+            } => Node::WhileExpr {
+                condition: condition.clone(),
+                body: None,
+                position: position.clone(),
+            },
+            Node::MatchExpr {
+                value,
+                arms,
+                position,
+            } => Node::MatchExpr {
+                value: value.clone(),
+                arms: arms
+                    .iter()
+                    .map(|arm| MatchArm {
+                        body: Node::EmptyLiteral {
+                            position: arm.body.position().clone(),
+                        },
+                        ..arm.clone()
+                    })
+                    .collect(),
+                position: position.clone(),
+            },
+            node => node.clone(),
+        };
+        self.nodes.push(node);
+        self.nodes.len() as u32 - 1
+    }
+
+    /// The slot of the local of the function with the name, if it is resolved to one.
+    fn slot(&self, name: &str) -> Option<u16> {
+        let slot = self.locals.iter().position(|local| local == name)?;
+        u16::try_from(slot).ok()
+    }
+
+    /// Points the jump instruction at `idx` to the next instruction.
+    fn patch(&mut self, idx: usize) {
+        let here = self.here();
+        match &mut self.ops[idx] {
+            Op::Jump(target)
+            | Op::JumpUnless { target, .. }
+            | Op::ForNext { exit: target }
+            | Op::LoopCheck { exit: target, .. }
+            | Op::BlockCheck { end: target }
+            | Op::MatchArm { next: target, .. }
+            | Op::Guard { next: target, .. } => *target = here,
+            _ => {}
+        }
+    }
+
+    /// Compiles the node to instructions pushing its value. Calls in the tail position of
+    /// a function body are compiled to tail calls.
+    fn expr(&mut self, node: &Node, tail: bool) {
+        match node {
+            Node::NumberLiteral { value, .. } => self.constant(Value::Number(*value)),
+            Node::StringLiteral { value, .. } => self.constant(Value::String(value.clone())),
+            Node::BoolLiteral { value, .. } => self.constant(Value::Bool(*value)),
+            Node::ArrayLiteral { value, .. } => {
+                for item in value {
+                    self.expr(item, false);
+                }
+                let node = self.node(node);
+                self.emit(Op::Array(node));
+            }
+            Node::ObjectLiteral { value, .. } => {
+                for (_, field) in value {
+                    self.expr(field, false);
+                }
+                let node = self.node(node);
+                self.emit(Op::Object(node));
+            }
+            Node::EmptyLiteral { .. } | Node::EmptyIdentifier { .. } => self.constant(Value::Empty),
+            Node::Identifier { value, .. } => {
+                let slot = self.slot(value);
+                let node = self.node(node);
+                self.emit(match slot {
+                    Some(slot) => Op::LoadLocal { slot, node },
+                    None => Op::Load(node),
+                });
+            }
+            Node::ModuleAccess { .. } => {
+                let node = self.node(node);
+                self.emit(Op::Member(node));
+            }
+            Node::UnaryExpression {
+                operator,
+                operand,
+                position,
+            } => match (operator, operand.as_ref()) {
+                (Kind::NegationOp, Node::NumberLiteral { value, .. }) => {
+                    self.constant(Value::Number(-*value))
+                }
+                (Kind::NegationOp, Node::BoolLiteral { value, .. }) => {
+                    self.constant(Value::Bool(!*value))
+                }
+                (Kind::NegationOp, Node::Identifier { .. }) => {
+                    let node = self.node(node);
+                    self.emit(Op::NegateIdent(node));
+                }
+                (Kind::NegationOp, _) => self.fail(Err {
+                    message: t!(
+                        "errors.eval_e3",
+                        a = operand.string(),
+                        b = position.string()
+                    ),
+                    reason: ErrorReason::Syntax,
+                    span: Some(Span::at(position)),
+                    trace: None,
+                }),
+                _ => self.fail(Err {
+                    message: t!(
+                        "errors.eval_e4",
+                        a = operator.string(),
+                        b = position.string()
+                    ),
+                    reason: ErrorReason::Syntax,
+                    span: Some(Span::at(position)),
+                    trace: None,
+                }),
+            },
+            Node::BinaryExpression {
+                operator: Kind::AssignOp,
+                left_operand,
+                right_operand,
+                ..
+            } => self.assignment(left_operand, right_operand),
+            Node::BinaryExpression {
+                operator: Kind::AccessorOp,
+                left_operand,
+                ..
+            } => {
+                self.expr(left_operand, false);
+                let node = self.node(node);
+                self.emit(Op::Field(node));
+            }
+            Node::BinaryExpression {
+                left_operand,
+                right_operand,
+                ..
+            } => {
+                self.expr(left_operand, false);
+                self.expr(right_operand, false);
+                let node = self.node(node);
+                self.emit(Op::Binary(node));
+            }
+            Node::IndexingOp { operand, index, .. } => {
+                self.expr(operand, false);
+                let operand = self.node(operand);
+                self.emit(Op::IndexTarget(operand));
+                self.index(index);
+                self.emit(Op::Index);
+            }
+            Node::SlicingOp {
+                operand,
+                start_inclusive,
+                end_exclusive,
+                ..
+            } => {
+                self.expr(operand, false);
+                let operand = self.node(operand);
+                self.emit(Op::IndexTarget(operand));
+                // the end is evaluated before the start
+                for index in [end_exclusive, start_inclusive].into_iter().flatten() {
+                    self.index(index);
+                }
+                self.emit(Op::Slice {
+                    start: start_inclusive.is_some(),
+                    end: end_exclusive.is_some(),
+                });
+            }
+            Node::FunctionCall {
+                function,
+                arguments,
+                ..
+            } => {
+                self.emit(Op::OutOfLoop);
+                for arg in arguments {
+                    self.expr(arg, false);
+                }
+                self.emit(Op::RestoreLoop);
+                self.expr(function, false);
+
+                let (argc, node) = (arguments.len() as u32, self.node(node));
+                self.emit(match tail {
+                    true => Op::TailCall { argc, node },
+                    false => Op::Call { argc, node },
+                });
+            }
+            Node::FunctionLiteral { sign, .. } => match sign.0.as_ref() {
+                Node::Identifier { .. } | Node::EmptyIdentifier { .. } => {
+                    let proto = Proto::compile(Rc::new(node.clone()));
+                    self.protos.push(Rc::new(proto));
+                    let idx = self.protos.len() as u32 - 1;
+                    self.emit(match sign.0.as_ref() {
+                        Node::Identifier { .. } => Op::Define(idx),
+                        _ => Op::Closure(idx),
+                    });
+                }
+                _ => self.fail(Err {
+                    message: t!(
+                        "errors.eval_e7",
+                        a = sign.0.string(),
+                        b = sign.0.position().string()
+                    ),
+                    reason: ErrorReason::Assert,
+                    span: Some(Span::at(sign.0.position())),
+                    trace: None,
+                }),
+            },
+            Node::IfExpr {
+                condition,
+                on_true,
+                on_false,
+                ..
+            } => {
+                self.expr(condition, false);
+                let node = self.node(node);
+                let on_false_jump = self.emit(Op::JumpUnless { target: 0, node });
+                self.clause(on_true.as_deref(), tail);
+                let end_jump = self.emit(Op::Jump(0));
+                self.patch(on_false_jump);
+                self.clause(on_false.as_deref(), tail);
+                self.patch(end_jump);
+            }
+            Node::ForExpr { iterable, body, .. } => {
+                self.expr(iterable, false);
+                let node = self.node(node);
+                self.emit(Op::ForBegin(node));
+
+                // we only loop if there's a body
+                if let Some(body) = body {
+                    self.depth += 1;
+                    let next = self.here();
+                    let mut exits = vec![self.emit(Op::ForNext { exit: 0 })];
+                    for stmt in body {
+                        self.expr(stmt, false);
+                        exits.push(self.emit(Op::LoopCheck { next, exit: 0 }));
+                    }
+                    self.emit(Op::Jump(next));
+                    for exit in exits {
+                        self.patch(exit);
+                    }
+                    self.depth -= 1;
+                }
+                self.emit(Op::ForEnd);
+            }
+            Node::WhileExpr {
+                condition, body, ..
+            } => {
+                self.emit(Op::WhileBegin);
+                self.depth += 1;
+                let next = self.here();
+                self.expr(condition, false);
+                let node = self.node(node);
+                let mut exits = vec![self.emit(Op::JumpUnless { target: 0, node })];
+                for stmt in body.iter().flatten() {
+                    self.expr(stmt, false);
+                    exits.push(self.emit(Op::LoopCheck { next, exit: 0 }));
+                }
+                self.emit(Op::Jump(next));
+                for exit in exits {
+                    self.patch(exit);
+                }
+                self.depth -= 1;
+                self.emit(Op::ForEnd);
+            }
+            Node::MatchExpr { value, arms, .. } => {
+                self.expr(value, false);
+                let node = self.node(node);
+
+                let mut ends = Vec::new();
+                for (arm, MatchArm { guard, body, .. }) in arms.iter().enumerate() {
+                    let arm = arm as u32;
+                    let mut nexts = vec![self.emit(Op::MatchArm { node, arm, next: 0 })];
+                    self.depth += 1;
+                    if let Some(guard) = guard {
+                        self.expr(guard, false);
+                        nexts.push(self.emit(Op::Guard { node, arm, next: 0 }));
+                    }
+                    self.expr(body, tail);
+                    self.depth -= 1;
+                    self.emit(Op::EndArm);
+                    ends.push(self.emit(Op::Jump(0)));
+                    for next in nexts {
+                        self.patch(next);
+                    }
+                }
+
+                // like an if expression without an else clause, no arm taken is empty
+                self.emit(Op::Pop);
+                self.constant(Value::Empty);
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+            Node::Continueliteral { .. } => {
+                let node = self.node(node);
+                self.emit(Op::Continue(node));
+            }
+            Node::BreakLiteral { .. } => {
+                let node = self.node(node);
+                self.emit(Op::Break(node));
+            }
+        }
+    }
+
+    /// Compiles a clause of an if expression, whose value is that of its last statement.
+    fn clause(&mut self, stmts: Option<&[Node]>, tail: bool) {
+        let stmts = stmts.unwrap_or_default();
+        if stmts.is_empty() {
+            self.constant(Value::Empty);
+        }
+
+        let mut ends = Vec::new();
+        for (i, stmt) in stmts.iter().enumerate() {
+            let last = i + 1 == stmts.len();
+            self.expr(stmt, tail && last);
+            if !last {
+                ends.push(self.emit(Op::BlockCheck { end: 0 }));
+            }
+        }
+        for end in ends {
+            self.patch(end);
+        }
+    }
+
+    /// Compiles an index of an indexing or slicing operation, converted to an index.
+    fn index(&mut self, index: &Node) {
+        self.emit(Op::OutOfLoop);
+        self.expr(index, false);
+        self.emit(Op::RestoreLoop);
+        let index = self.node(index);
+        self.emit(Op::ToIndex(index));
+    }
+
+    fn assignment(&mut self, left_operand: &Node, right_operand: &Node) {
+        match left_operand {
+            Node::Identifier { value, .. } => {
+                self.expr(right_operand, false);
+                let slot = self.slot(value);
+                let node = self.node(left_operand);
+                self.emit(match slot {
+                    Some(slot) => Op::AssignLocal {
+                        slot,
+                        node,
+                        top: self.depth == 0,
+                    },
+                    None => Op::Assign(node),
+                });
+            }
+            Node::EmptyIdentifier { .. } => {
+                self.expr(right_operand, false);
+                self.emit(Op::Pop);
+                self.constant(Value::Empty);
+            }
+            Node::IndexingOp { operand, index, .. } => {
+                self.expr(operand, false);
+                let operand = self.node(operand);
+                self.emit(Op::ArrayTarget(operand));
+                self.index(index);
+                self.expr(right_operand, false);
+                self.emit(Op::SetIndex(operand));
+            }
+            Node::BinaryExpression {
+                operator: Kind::AccessorOp,
+                left_operand: l_left_operand,
+                ..
+            } => {
+                self.expr(l_left_operand, false);
+                let node = self.node(left_operand);
+                self.emit(Op::FieldTarget(node));
+                self.expr(right_operand, false);
+                self.emit(Op::SetField(node));
+            }
+            Node::BinaryExpression {
+                left_operand: l_left_operand,
+                ..
+            } => self.fail(Err {
+                message: t!(
+                    "errors.eval_binary_expr_node_e3",
+                    a = l_left_operand.string(),
+                    b = left_operand.position().string()
+                ),
+                reason: ErrorReason::Runtime,
+                span: Some(Span::at(left_operand.position())),
+                trace: None,
+            }),
+            _ => {
+                self.emit(Op::OutOfLoop);
+                self.expr(left_operand, false);
+                self.emit(Op::RestoreLoop);
+                let node = self.node(left_operand);
+                self.emit(Op::AssignError(node));
+            }
+        }
+    }
+}
+
+/// The parameters and the names assigned in the body of a function, that are bound in
+/// slots of its calls. Names that loops and match arms bind, and those whose frames
+/// are looked up by name, as the targets of indices and fields are, stay in the stack
+/// frames; all of them do if the body defines a function, which may capture them.
+fn locals(params: &[(Node, Node)], body: &[Node]) -> Vec<String> {
+    let mut names = Names::default();
+    for stmt in body {
+        names.scan(stmt);
+    }
+    if names.closure {
+        return Vec::new();
+    }
+
+    let mut locals = Vec::new();
+    let params = params.iter().filter_map(|(param, _)| match param {
+        Node::Identifier { value, .. } => Some(value.clone()),
+        _ => None,
+    });
+    for name in params.chain(names.assigned) {
+        if !names.framed.contains(&name) && !locals.contains(&name) {
+            locals.push(name);
+        }
+    }
+    locals
+}
+
+/// Names collects the names a function body binds, as `locals` resolves them.
+#[derive(Default)]
+struct Names {
+    assigned: Vec<String>,
+    framed: HashSet<String>,
+    closure: bool,
+}
+
+impl Names {
+    fn scan(&mut self, node: &Node) {
+        match node {
+            Node::ArrayLiteral { value, .. } => value.iter().for_each(|item| self.scan(item)),
+            Node::ObjectLiteral { value, .. } => {
+                value.iter().for_each(|(_, field)| self.scan(field))
+            }
+            Node::ModuleAccess { module, .. } => _ = self.framed.insert(module.clone()),
+            Node::UnaryExpression { operand, .. } => {
+                // negating a name is an error, which looks up the name
+                self.frame(operand);
+                self.scan(operand);
+            }
+            Node::BinaryExpression {
+                operator,
+                left_operand,
+                right_operand,
+                ..
+            } => {
+                if *operator == Kind::AssignOp {
+                    match left_operand.as_ref() {
+                        Node::Identifier { value, .. } => self.assigned.push(value.clone()),
+                        Node::IndexingOp { operand, .. } => self.frame(operand),
+                        Node::BinaryExpression {
+                            operator: Kind::AccessorOp,
+                            left_operand,
+                            ..
+                        } => self.frame(left_operand),
+                        _ => {}
+                    }
+                }
+                self.scan(left_operand);
+                self.scan(right_operand);
+            }
+            Node::IndexingOp { operand, index, .. } => {
+                self.scan(operand);
+                self.scan(index);
+            }
+            Node::SlicingOp {
+                operand,
+                start_inclusive,
+                end_exclusive,
+                ..
+            } => {
+                self.scan(operand);
+                for index in [start_inclusive, end_exclusive].into_iter().flatten() {
+                    self.scan(index);
+                }
+            }
+            Node::FunctionCall {
+                function,
+                arguments,
+                ..
+            } => {
+                self.scan(function);
+                arguments.iter().for_each(|arg| self.scan(arg));
+            }
+            Node::FunctionLiteral { .. } => self.closure = true,
+            Node::IfExpr {
+                condition,
+                on_true,
+                on_false,
+                ..
+            } => {
+                self.scan(condition);
+                for stmt in [on_true, on_false].into_iter().flatten().flatten() {
+                    self.scan(stmt);
+                }
+            }
+            Node::ForExpr {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.frame(variable);
+                self.scan(iterable);
+                body.iter().flatten().for_each(|stmt| self.scan(stmt));
+            }
+            Node::WhileExpr {
+                condition, body, ..
+            } => {
+                self.scan(condition);
+                body.iter().flatten().for_each(|stmt| self.scan(stmt));
+            }
+            Node::MatchExpr { value, arms, .. } => {
+                self.scan(value);
+                for arm in arms {
+                    self.pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.scan(guard);
+                    }
+                    self.scan(&arm.body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Keeps the name in the stack frames, if the node is one.
+    fn frame(&mut self, node: &Node) {
+        if let Node::Identifier { value, .. } = node {
+            self.framed.insert(value.clone());
+        }
+    }
+
+    /// Keeps the names the pattern binds in the stack frames.
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Binding { name, .. } => _ = self.framed.insert(name.clone()),
+            Pattern::Array { items, rest, .. } => {
+                items.iter().for_each(|item| self.pattern(item));
+                if let Some(rest) = rest {
+                    self.pattern(rest);
+                }
+            }
+            Pattern::Object { fields, .. } => {
+                fields.iter().for_each(|(_, field)| self.pattern(field))
+            }
+            Pattern::Wildcard { .. } | Pattern::Literal(_) => {}
+        }
+    }
+}
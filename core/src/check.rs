@@ -0,0 +1,938 @@
+//! The static type checker. It infers the types of the expressions of a program from
+//! its literals and the signatures of its functions, and reports every call, operation,
+//! array item and object field whose types cannot agree, before the program is run.
+//! Values whose types are only known when running, like those returned by native
+//! functions, are not checked. Match expressions that may not match every value are
+//! reported as warnings, which do not fail the check.
+
+use super::{
+    error::{Err, ErrorReason, Span},
+    eval::r#type::Type,
+    lexer::{Kind, Position},
+    parser::{MatchArm, Node, Pattern},
+    runtime::Context,
+};
+use std::collections::HashMap;
+
+/// Checks the types of the Speak program in `source`, written in the `speak` locale,
+/// returning the errors and warnings found. Programs with syntax errors are not checked,
+/// and the errors are returned instead.
+pub fn check_source(source: &str, speak: &str) -> Result<(), Vec<Err>> {
+    let (_, nodes, errs) = Context::new(&false).parse_source(speak, source);
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    let errs = check(&nodes);
+    match errs.is_empty() {
+        true => Ok(()),
+        false => Err(errs),
+    }
+}
+
+/// Checks the types of the statements of a program, returning every mismatch found in
+/// the order of the statements.
+pub fn check(nodes: &[Node]) -> Vec<Err> {
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        objects: HashMap::new(),
+        errs: Vec::new(),
+    };
+    for node in nodes {
+        checker.infer(node);
+    }
+    checker.errs
+}
+
+/// The type of an expression, as far as it is known before running.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Unknown,
+    Known(Type),
+    /// A function, with the parameter types and return type of its signature.
+    Function(Vec<String>, String),
+}
+
+impl Ty {
+    /// The type of the values of the expression, if known.
+    fn known(&self) -> Option<Type> {
+        match self {
+            Ty::Unknown => None,
+            Ty::Known(t) => Some(t.clone()),
+            Ty::Function(..) => Some(Type::Function),
+        }
+    }
+}
+
+struct Checker {
+    /// The types of the names bound in each frame, the innermost last.
+    scopes: Vec<HashMap<String, Ty>>,
+    /// The types of the fields of the objects of each name, from their literals.
+    objects: HashMap<String, HashMap<String, Ty>>,
+    errs: Vec<Err>,
+}
+
+impl Checker {
+    fn infer(&mut self, node: &Node) -> Ty {
+        match node {
+            Node::NumberLiteral { .. } => Ty::Known(Type::Number),
+            Node::StringLiteral { .. } => Ty::Known(Type::String),
+            Node::BoolLiteral { .. } => Ty::Known(Type::Bool),
+            Node::EmptyLiteral { .. } => Ty::Known(Type::Empty),
+            Node::Identifier { value, .. } => self.lookup(value),
+            Node::UnaryExpression { operand, .. } => match operand.as_ref() {
+                // only literals are negated
+                Node::NumberLiteral { .. } | Node::BoolLiteral { .. } => self.infer(operand),
+                _ => Ty::Unknown,
+            },
+            Node::ArrayLiteral { value, .. } => self.array(value),
+            Node::ObjectLiteral { name, value, .. } => {
+                for (field, node) in value {
+                    let ty = self.infer(node);
+                    let record = self.objects.entry(name.clone()).or_default();
+                    match record.get(field).and_then(Ty::known) {
+                        Some(want) => self.expect_field(name, field, &want, &ty, node.position()),
+                        None => _ = record.insert(field.clone(), ty),
+                    }
+                }
+                Ty::Known(Type::Object(name.clone()))
+            }
+            Node::BinaryExpression {
+                operator: Kind::AssignOp,
+                left_operand,
+                right_operand,
+                ..
+            } => self.assign(left_operand, right_operand),
+            Node::BinaryExpression {
+                operator: Kind::AccessorOp,
+                left_operand,
+                right_operand,
+                position,
+            } => {
+                let object = self.infer(left_operand);
+                self.field(&object, &right_operand.string(), position)
+            }
+            Node::BinaryExpression {
+                operator: Kind::EllipsisOp,
+                left_operand,
+                right_operand,
+                ..
+            } => {
+                for bound in [left_operand, right_operand] {
+                    match self.infer(bound).known() {
+                        Some(Type::Number) | None => {}
+                        Some(t) => {
+                            let message = t!(
+                                "errors.check_range_e",
+                                a = t.string(),
+                                b = Type::Number.string()
+                            );
+                            self.error(bound.position(), message);
+                        }
+                    }
+                }
+                Ty::Known(Type::Range)
+            }
+            Node::BinaryExpression { .. } => self.binary(node),
+            Node::IndexingOp { operand, index, .. } => match self.indexed(operand, &[index]) {
+                Some(Type::Array(t)) if *t != Type::Empty => Ty::Known(*t),
+                Some(Type::String) => Ty::Known(Type::String),
+                _ => Ty::Unknown,
+            },
+            Node::SlicingOp {
+                operand,
+                start_inclusive,
+                end_exclusive,
+                ..
+            } => {
+                let bounds = [start_inclusive, end_exclusive];
+                let indices = bounds
+                    .iter()
+                    .filter_map(|x| x.as_deref())
+                    .collect::<Vec<_>>();
+                match self.indexed(operand, &indices) {
+                    Some(t @ (Type::Array(_) | Type::String)) => Ty::Known(t),
+                    _ => Ty::Unknown,
+                }
+            }
+            Node::FunctionCall {
+                function,
+                arguments,
+                ..
+            } => self.call(function, arguments),
+            Node::FunctionLiteral { sign, body, .. } => self.function(sign, body),
+            Node::IfExpr {
+                condition,
+                on_true,
+                on_false,
+                ..
+            } => {
+                match self.infer(condition).known() {
+                    Some(Type::Bool | Type::String) | None => {}
+                    Some(t) => self.error(
+                        condition.position(),
+                        t!(
+                            "errors.check_if_e",
+                            a = t.string(),
+                            b = Type::Bool.string(),
+                            c = Type::String.string()
+                        ),
+                    ),
+                }
+
+                // an if expression is of the type of both of its clauses
+                let on_true = on_true.as_ref().map(|clause| self.clause(clause));
+                let on_false = on_false.as_ref().map(|clause| self.clause(clause));
+                match (on_true, on_false) {
+                    (Some(t), Some(f)) if t == f => t,
+                    _ => Ty::Unknown,
+                }
+            }
+            Node::ForExpr {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                let item = match self.infer(iterable).known() {
+                    Some(Type::Array(t)) if *t != Type::Empty => Ty::Known(*t),
+                    Some(Type::String) => Ty::Known(Type::String),
+                    Some(Type::Range) => Ty::Known(Type::Number),
+                    Some(Type::Array(_)) | None => Ty::Unknown,
+                    Some(t) => {
+                        self.error(
+                            iterable.position(),
+                            t!(
+                                "errors.check_for_e",
+                                a = t.string(),
+                                b = Type::String.string(),
+                                c = Type::Range.string()
+                            ),
+                        );
+                        Ty::Unknown
+                    }
+                };
+
+                // the loop variable is bound in a frame of the loop
+                let mut scope = HashMap::new();
+                if let Node::Identifier { value, .. } = variable.as_ref() {
+                    scope.insert(value.clone(), item);
+                }
+                self.scopes.push(scope);
+                for stmt in body.iter().flatten() {
+                    self.infer(stmt);
+                }
+                self.scopes.pop();
+                Ty::Unknown
+            }
+            Node::WhileExpr {
+                condition, body, ..
+            } => {
+                match self.infer(condition).known() {
+                    Some(Type::Bool | Type::String) | None => {}
+                    Some(t) => self.error(
+                        condition.position(),
+                        t!(
+                            "errors.check_while_e",
+                            a = t.string(),
+                            b = Type::Bool.string(),
+                            c = Type::String.string()
+                        ),
+                    ),
+                }
+
+                self.scopes.push(HashMap::new());
+                for stmt in body.iter().flatten() {
+                    self.infer(stmt);
+                }
+                self.scopes.pop();
+                Ty::Unknown
+            }
+            Node::MatchExpr {
+                value,
+                arms,
+                position,
+            } => {
+                let subject = self.infer(value);
+                let tys = arms
+                    .iter()
+                    .map(|arm| self.arm(arm, &subject))
+                    .collect::<Vec<_>>();
+
+                let exhaustive = exhaustive(arms, subject.known().as_ref());
+                if !exhaustive {
+                    self.errs.push(Err {
+                        message: t!("errors.check_match_e2", a = t!("literals.match")),
+                        reason: ErrorReason::Warning,
+                        span: Some(Span::at(position)),
+                        trace: None,
+                    });
+                }
+
+                // a match expression is of the type of all of its arms, if one is taken
+                match tys.split_first() {
+                    Some((t, rest)) if exhaustive && rest.iter().all(|ty| ty == t) => t.clone(),
+                    _ => Ty::Unknown,
+                }
+            }
+            Node::EmptyIdentifier { .. }
+            | Node::ModuleAccess { .. }
+            | Node::Continueliteral { .. }
+            | Node::BreakLiteral { .. } => Ty::Unknown,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Ty {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .unwrap_or(Ty::Unknown)
+    }
+
+    /// Binds the name where it is bound already, or else in the innermost frame. A name
+    /// rebound to a value of another type is of no known type from then on.
+    fn bind(&mut self, name: &str, ty: Ty) {
+        let scope = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find(|scope| scope.contains_key(name));
+        match scope {
+            Some(scope) => {
+                let bound = scope.get_mut(name).expect("the scope binds the name");
+                if *bound != ty {
+                    *bound = Ty::Unknown;
+                }
+            }
+            None => {
+                let scope = self
+                    .scopes
+                    .last_mut()
+                    .expect("the global frame is never popped");
+                scope.insert(name.to_string(), ty);
+            }
+        }
+    }
+
+    fn error(&mut self, position: &Position, message: String) {
+        self.errs.push(Err {
+            message,
+            reason: ErrorReason::Type,
+            span: Some(Span::at(position)),
+            trace: None,
+        });
+    }
+
+    /// Checks the statements of a clause of an if expression, returning the type of the
+    /// last.
+    fn clause(&mut self, stmts: &[Node]) -> Ty {
+        let tys = stmts
+            .iter()
+            .map(|stmt| self.infer(stmt))
+            .collect::<Vec<_>>();
+        tys.last().cloned().unwrap_or(Ty::Known(Type::Empty))
+    }
+
+    /// Checks the arm of a match expression with the names of its pattern bound, returning
+    /// the type of its body.
+    fn arm(&mut self, arm: &MatchArm, subject: &Ty) -> Ty {
+        let mut scope = HashMap::new();
+        self.pattern(&arm.pattern, subject, &mut scope);
+        self.scopes.push(scope);
+
+        if let Some(guard) = &arm.guard {
+            match self.infer(guard).known() {
+                Some(Type::Bool) | None => {}
+                Some(t) => {
+                    let message = t!(
+                        "errors.check_match_e3",
+                        a = t.string(),
+                        b = Type::Bool.string()
+                    );
+                    self.error(guard.position(), message);
+                }
+            }
+        }
+        let ty = self.infer(&arm.body);
+        self.scopes.pop();
+        ty
+    }
+
+    /// Checks that the pattern can match values of the type, binding its names to the
+    /// types of the parts of the value they match.
+    fn pattern(&mut self, pattern: &Pattern, ty: &Ty, scope: &mut HashMap<String, Ty>) {
+        let t = ty.known();
+        let matches = match (pattern, &t) {
+            (Pattern::Wildcard { .. }, _) => true,
+            (Pattern::Binding { name, .. }, _) => {
+                scope.insert(name.clone(), ty.clone());
+                true
+            }
+            (Pattern::Literal(node), t) => {
+                let literal = self.infer(node).known();
+                t.is_none() || literal == *t
+            }
+            (Pattern::Array { items, rest, .. }, Some(Type::Array(_)) | None) => {
+                let item = match &t {
+                    Some(Type::Array(t)) if **t != Type::Empty => Ty::Known(*t.clone()),
+                    _ => Ty::Unknown,
+                };
+                for pattern in items {
+                    self.pattern(pattern, &item, scope);
+                }
+                if let Some(rest) = rest {
+                    self.pattern(rest, ty, scope);
+                }
+                true
+            }
+            (Pattern::Object { name, fields, .. }, Some(Type::Object(_)) | None) => {
+                for (field, pattern) in fields {
+                    let object = Ty::Known(Type::Object(name.clone()));
+                    let field = self.field(&object, field, pattern.position());
+                    self.pattern(pattern, &field, scope);
+                }
+                t.is_none() || t == Some(Type::Object(name.clone()))
+            }
+            _ => false,
+        };
+
+        if let (false, Some(t)) = (matches, t) {
+            let message = t!(
+                "errors.check_match_e1",
+                a = pattern.string(),
+                b = t.string()
+            );
+            self.error(pattern.position(), message);
+        }
+    }
+
+    /// The items of an array must all be of the type of its first item.
+    fn array(&mut self, items: &[Node]) -> Ty {
+        let mut item_type = None;
+        for item in items {
+            let Some(t) = self.infer(item).known() else {
+                continue;
+            };
+            match &item_type {
+                None => item_type = Some(t),
+                Some(want) if *want != t => {
+                    let message = t!("errors.check_array_e", a = want.string(), b = t.string());
+                    self.error(item.position(), message);
+                }
+                Some(_) => {}
+            }
+        }
+
+        match (items.is_empty(), item_type) {
+            (true, _) => Ty::Known(Type::Array(Box::new(Type::Empty))),
+            (false, Some(t)) => Ty::Known(Type::Array(Box::new(t))),
+            (false, None) => Ty::Unknown,
+        }
+    }
+
+    fn assign(&mut self, target: &Node, value: &Node) -> Ty {
+        let ty = self.infer(value);
+        match target {
+            Node::Identifier { value, .. } => {
+                self.bind(value, ty.clone());
+                ty
+            }
+            Node::EmptyIdentifier { .. } => Ty::Known(Type::Empty),
+            // the item assigned to an array must be of the type of its items
+            Node::IndexingOp { operand, index, .. } => {
+                if let (Some(Type::Array(want)), Some(got)) =
+                    (self.indexed(operand, &[index]), ty.known())
+                {
+                    if *want != Type::Empty && *want != got {
+                        let message =
+                            t!("errors.check_array_e", a = want.string(), b = got.string());
+                        self.error(value.position(), message);
+                    }
+                }
+                ty
+            }
+            // the value assigned to a field must be of the type of the field
+            Node::BinaryExpression {
+                operator: Kind::AccessorOp,
+                left_operand,
+                right_operand,
+                position,
+            } => {
+                let object = self.infer(left_operand);
+                let field = right_operand.string();
+                if let (Some(want), Some(Type::Object(name))) = (
+                    self.field(&object, &field, position).known(),
+                    object.known(),
+                ) {
+                    self.expect_field(&name, &field, &want, &ty, value.position());
+                }
+                object
+            }
+            _ => Ty::Unknown,
+        }
+    }
+
+    /// The type of the field of an object, reporting fields that objects of its name
+    /// are not given by any of their literals.
+    fn field(&mut self, object: &Ty, field: &str, position: &Position) -> Ty {
+        let name = match object.known() {
+            Some(Type::Object(name)) => name,
+            Some(t) => {
+                let message = t!("errors.check_field_e2", a = t.string(), b = field);
+                self.error(position, message);
+                return Ty::Unknown;
+            }
+            None => return Ty::Unknown,
+        };
+
+        let Some(record) = self.objects.get(&name) else {
+            return Ty::Unknown;
+        };
+        match record.get(field) {
+            Some(ty) => ty.clone(),
+            None => {
+                self.error(position, t!("errors.check_field_e2", a = name, b = field));
+                Ty::Unknown
+            }
+        }
+    }
+
+    fn expect_field(&mut self, name: &str, field: &str, want: &Type, got: &Ty, at: &Position) {
+        match got.known() {
+            Some(got) if got != *want => {
+                let message = t!(
+                    "errors.check_field_e1",
+                    a = field,
+                    b = name,
+                    c = want.string(),
+                    d = got.string()
+                );
+                self.error(at, message);
+            }
+            _ => {}
+        }
+    }
+
+    /// The types of the operands of an arithmetic, logical or comparison operator must
+    /// be ones it applies to, as in `eval::binary_value`.
+    fn binary(&mut self, node: &Node) -> Ty {
+        let Node::BinaryExpression {
+            operator,
+            left_operand,
+            right_operand,
+            position,
+        } = node
+        else {
+            return Ty::Unknown;
+        };
+        let left = self.infer(left_operand).known();
+        let right = self.infer(right_operand).known();
+
+        use Type::{Array, Bool, Number, String};
+        let applies = |t: &Type| match operator {
+            Kind::AddOp => matches!(t, Number | String | Bool | Array(_)),
+            Kind::MultiplyOp | Kind::LogicalAndOp | Kind::LogicalOrOp => {
+                matches!(t, Number | Bool)
+            }
+            Kind::SubtractOp | Kind::DivideOp | Kind::ModulusOp => matches!(t, Number),
+            Kind::GreaterThanOp | Kind::LessThanOp => matches!(t, Number | String),
+            _ => true,
+        };
+        let result = match operator {
+            Kind::GreaterThanOp | Kind::LessThanOp | Kind::EqualOp => Some(Bool),
+            Kind::SubtractOp | Kind::DivideOp | Kind::ModulusOp => Some(Number),
+            _ => left.clone().or(right.clone()),
+        };
+
+        match (left, right) {
+            (Some(l), Some(r)) if operator != &Kind::EqualOp && (l != r || !applies(&l)) => {
+                let message = t!(
+                    "errors.check_binary_e",
+                    a = l.string(),
+                    b = r.string(),
+                    c = operator.string()
+                );
+                self.error(position, message);
+                Ty::Unknown
+            }
+            _ => result.map_or(Ty::Unknown, Ty::Known),
+        }
+    }
+
+    /// Checks the operand and the indices of an indexing or slicing operation, returning
+    /// the type of the operand if known.
+    fn indexed(&mut self, operand: &Node, indices: &[&Node]) -> Option<Type> {
+        for index in indices {
+            match self.infer(index).known() {
+                Some(Type::Number) | None => {}
+                Some(t) => {
+                    let message = t!(
+                        "errors.check_index_e2",
+                        a = t.string(),
+                        b = Type::Number.string()
+                    );
+                    self.error(index.position(), message);
+                }
+            }
+        }
+
+        let t = self.infer(operand).known()?;
+        if !matches!(t, Type::Array(_) | Type::String) {
+            let message = t!("errors.check_index_e1", a = t.string());
+            self.error(operand.position(), message);
+        }
+        Some(t)
+    }
+
+    /// The arguments of a call must be as many as the parameters of the function and of
+    /// their types, and the call is of its return type.
+    fn call(&mut self, function: &Node, arguments: &[Node]) -> Ty {
+        let args = arguments
+            .iter()
+            .map(|arg| self.infer(arg))
+            .collect::<Vec<_>>();
+
+        match self.infer(function) {
+            Ty::Function(params, ret) => {
+                // functions without parameters are called with `()`
+                let count = match arguments {
+                    [Node::EmptyLiteral { .. }] if params.is_empty() => 0,
+                    _ => arguments.len(),
+                };
+                if count != params.len() {
+                    let message = t!(
+                        "errors.check_call_e3",
+                        a = function.string(),
+                        b = params.len(),
+                        c = count
+                    );
+                    self.error(function.position(), message);
+                }
+                for (i, ((arg, ty), param)) in arguments.iter().zip(&args).zip(&params).enumerate()
+                {
+                    match ty.known() {
+                        Some(got) if !accepts(param, &got) => {
+                            let message = t!(
+                                "errors.check_call_e1",
+                                a = i + 1,
+                                b = function.string(),
+                                c = param,
+                                d = got.string()
+                            );
+                            self.error(arg.position(), message);
+                        }
+                        _ => {}
+                    }
+                }
+                Ty::Known(type_named(&ret))
+            }
+            Ty::Known(Type::Function) | Ty::Unknown => Ty::Unknown,
+            Ty::Known(t) => {
+                let message = t!(
+                    "errors.check_call_e2",
+                    a = function.string(),
+                    b = t.string()
+                );
+                self.error(function.position(), message);
+                Ty::Unknown
+            }
+        }
+    }
+
+    /// Checks the body of the function literal with its parameters bound, and that it
+    /// has a statement of its return type.
+    fn function(&mut self, sign: &(Box<Node>, Vec<(Node, Node)>, Box<Node>), body: &[Node]) -> Ty {
+        let (name, params, ret) = sign;
+        let ret_type = ret.string();
+        let ty = Ty::Function(
+            params.iter().map(|(_, t)| t.string()).collect(),
+            ret_type.clone(),
+        );
+
+        // bound before its body is checked, for it to call itself
+        if let Node::Identifier { value, .. } = name.as_ref() {
+            self.bind(value, ty.clone());
+        }
+
+        let scope = params
+            .iter()
+            .filter_map(|(param, t)| match param {
+                Node::Identifier { value, .. } => {
+                    Some((value.clone(), Ty::Known(type_named(&t.string()))))
+                }
+                _ => None,
+            })
+            .collect();
+        self.scopes.push(scope);
+        let tys = body.iter().map(|stmt| self.infer(stmt)).collect::<Vec<_>>();
+        self.scopes.pop();
+
+        // a function returns its first statement of its return type, an assignment only
+        // if it is the last statement, as in `eval::unwrap_thunk`
+        let returns = body.iter().zip(&tys).enumerate().any(|(i, (stmt, ty))| {
+            let assigns = matches!(
+                stmt,
+                Node::BinaryExpression {
+                    operator: Kind::AssignOp,
+                    ..
+                }
+            );
+            (!assigns || i + 1 == body.len()) && ty.known().is_none_or(|t| returns(&t, &ret_type))
+        });
+        if !returns {
+            let message = t!("errors.check_function_e", a = name.string(), b = ret_type);
+            self.error(ret.position(), message);
+        }
+
+        match name.as_ref() {
+            Node::Identifier { .. } => Ty::Known(Type::Empty),
+            _ => ty,
+        }
+    }
+}
+
+/// Whether the arms of a match expression match every value of the type. Arms with
+/// guards are not counted, as their guards may not hold.
+fn exhaustive(arms: &[MatchArm], t: Option<&Type>) -> bool {
+    let patterns = arms
+        .iter()
+        .filter(|arm| arm.guard.is_none())
+        .map(|arm| &arm.pattern)
+        .collect::<Vec<_>>();
+    if patterns.iter().any(|pattern| irrefutable(pattern, t)) {
+        return true;
+    }
+
+    match t {
+        Some(Type::Bool) => [true, false].iter().all(|want| {
+            patterns.iter().any(|pattern| {
+                matches!(pattern, Pattern::Literal(Node::BoolLiteral { value, .. }) if value == want)
+            })
+        }),
+        // arrays of every length up to that of a pattern with a rest pattern, of patterns
+        // matching any item
+        Some(Type::Array(_)) => {
+            let shapes = patterns.iter().filter_map(|pattern| match pattern {
+                Pattern::Array { items, rest, .. }
+                    if items.iter().all(|item| irrefutable(item, None)) =>
+                {
+                    Some((items.len(), rest.is_some()))
+                }
+                _ => None,
+            });
+            let (mut lengths, mut least) = (Vec::new(), None::<usize>);
+            for (len, rest) in shapes {
+                match rest {
+                    true => least = Some(least.map_or(len, |least| least.min(len))),
+                    false => lengths.push(len),
+                }
+            }
+            least.is_some_and(|least| (0..least).all(|len| lengths.contains(&len)))
+        }
+        _ => false,
+    }
+}
+
+/// Whether the pattern matches every value of the type.
+fn irrefutable(pattern: &Pattern, t: Option<&Type>) -> bool {
+    match (pattern, t) {
+        (Pattern::Wildcard { .. } | Pattern::Binding { .. }, _) => true,
+        (Pattern::Array { items, rest, .. }, Some(Type::Array(_))) => {
+            items.is_empty() && rest.as_ref().is_some_and(|rest| irrefutable(rest, t))
+        }
+        (Pattern::Object { name, fields, .. }, Some(Type::Object(object))) => {
+            name == object && fields.iter().all(|(_, field)| irrefutable(field, None))
+        }
+        _ => false,
+    }
+}
+
+/// The type named in a signature, as written by `Type::string`.
+fn type_named(name: &str) -> Type {
+    if let Some(item) = name.strip_prefix("[]") {
+        return Type::Array(Box::new(type_named(item)));
+    }
+    match name.strip_prefix(&format!("{}: ", t!("types.object"))) {
+        Some(object) => Type::Object(object.to_string()),
+        None => Type::to_type(name),
+    }
+}
+
+/// Whether a parameter of the type takes the argument, as in `eval::bind_arguments`. An
+/// empty array is taken by parameters of any type.
+fn accepts(param: &str, got: &Type) -> bool {
+    returns(got, param)
+}
+
+/// Whether a statement of the type is returned by a function of the return type, as in
+/// `eval::returns`.
+fn returns(t: &Type, ret: &str) -> bool {
+    match t {
+        Type::Object(obj) => obj == ret,
+        Type::Array(item) if **item == Type::Empty => true,
+        t => t.string() == ret,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_source;
+    use crate::runtime::{Captured, Context};
+    use std::io::BufReader;
+
+    /// The messages and lines of the type errors of the program.
+    fn check(program: &str) -> Vec<(String, usize)> {
+        match check_source(program, "en") {
+            Ok(()) => Vec::new(),
+            Err(errs) => errs
+                .into_iter()
+                .map(|err| (err.message, err.span.map_or(0, |span| span.start.line)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reports_every_mismatch() {
+        let program = "add: a number, b number -> number
+    a + b
+
+n is \"Osoro\"
+p is Person { name n }
+p.name is 4
+p.age
+add 1 \"two\"
+xs is [1, \"a\"]
+ys is [1, 2]
+ys[0] is true
+s is \"a\" - 1
+if 1 ? 2 ! 3
+for i in 5
+    i
+bad: x number -> string
+    x * 2
+while 1
+    2
+for i in \"a\"..3
+    i
+add 1
+add 1 2 3";
+
+        let want = [
+            ("field name of Person is of type (string), but got (number)", 6),
+            ("Person has no field age", 7),
+            ("argument 2 of add must be of type (number), but got (string)", 8),
+            ("the items of an array must be of one type, expected (number) but found (string)", 9),
+            ("the items of an array must be of one type, expected (number) but found (bool)", 11),
+            ("values of types (string) and (number) do not support the '-' operation", 12),
+            ("the condition of an if expression must be of type bool or string, but got (number)", 13),
+            ("a for loop iterates over values of type string, array or interval, but got (number)", 14),
+            ("bad has no statement of its return type (string)", 16),
+            ("the condition of a while loop must be of type bool or string, but got (number)", 18),
+            ("a range counts from a number to a number, but got (string)", 20),
+            ("add takes 2 arguments, but got 1", 22),
+            ("add takes 2 arguments, but got 3", 23),
+        ];
+        let want = want.map(|(message, line)| (message.to_string(), line));
+        assert_eq!(check(program), want);
+    }
+
+    #[test]
+    fn infers_through_calls_and_names() {
+        for program in [
+            // the types of calls are the return types of the functions
+            "double: x number -> number\n    x * 2\nhalf: x number -> number\n    x / 2\nhalf (double 3)",
+            // names rebound to another type are not checked from then on
+            "x is 1\nx is \"a\"\nf: s string -> string\n    s\nf x",
+            // values of native functions are of no known type
+            "xs is [1, 2]\nys is map xs (_: x number -> number x * 2)\nys + 1",
+            // an empty array is taken for arrays of any type
+            "total: xs []number -> number\n    len xs\nnone is []\ntotal none",
+            // ranges are values, whose items are numbers
+            "sum: r interval -> number\n    for i in r\n        i * 2\n    0\nr is 1..4\nsum r",
+            // functions without parameters are called with `()`
+            "one: -> number\n    1\none ()",
+        ] {
+            assert_eq!(check(program), Vec::new(), "{program}");
+        }
+
+        let program = "double: x number -> number\n    x * 2\ns is \"a\" + (double 2)";
+        let (message, _) = &check(program)[0];
+        assert_eq!(
+            message,
+            "values of types (string) and (number) do not support the '+' operation"
+        );
+    }
+
+    #[test]
+    fn match_patterns() {
+        let program = "n is 1
+s is match n
+    \"one\" ? 1
+    [x] ? 2
+    x if x ? 3
+    _ ? 4
+b is match true
+    true ? 1
+xs is [1, 2]
+match xs
+    [] ? 0
+    [x, ..rest] ? x
+match xs
+    [x, y] ? x";
+
+        let want = [
+            (
+                "the pattern \"one\" cannot match a value of type (number)",
+                3,
+            ),
+            ("the pattern [x] cannot match a value of type (number)", 4),
+            (
+                "the guard of a match arm must be of type bool, but got (number)",
+                5,
+            ),
+            (
+                "the match expression may not match every value, add an arm for `_`",
+                7,
+            ),
+            (
+                "the match expression may not match every value, add an arm for `_`",
+                13,
+            ),
+        ];
+        let want = want.map(|(message, line)| (message.to_string(), line));
+        assert_eq!(check(program), want);
+
+        // warnings do not stop the program from running
+        let mut ctx = Context::new(&false);
+        ctx.type_check = true;
+        ctx.colors = false;
+        let (out, err) = (Captured::default(), Captured::default());
+        ctx.set_stdout(out.clone());
+        ctx.set_stderr(err.clone());
+
+        let program = "x is match 2\n    1 ? 1\nprintln \"ran\"";
+        assert!(ctx.exec("en", BufReader::new(program.as_bytes())).is_ok());
+        assert_eq!(out.text(), "ran\n");
+        assert!(err.text().starts_with("Warning: the match expression"));
+    }
+
+    #[test]
+    fn checked_before_running() {
+        let mut ctx = Context::new(&false);
+        ctx.type_check = true;
+        let out = Captured::default();
+        ctx.set_stdout(out.clone());
+
+        let program = "println \"ran\"\nx is 1 + true";
+        let err = ctx
+            .exec("en", BufReader::new(program.as_bytes()))
+            .unwrap_err();
+        assert_eq!(
+            err.string(),
+            "Type error: values of types (number) and (bool) do not support the '+' operation"
+        );
+        assert_eq!(out.text(), "");
+    }
+}
@@ -0,0 +1,343 @@
+//! Conversions between Speak values and Rust values, for the functions an embedder
+//! registers with `Context::register_fn`. The arguments of a registered function are
+//! converted from Speak values and its result converted back, and a call with too few
+//! or too many arguments, or arguments of the wrong type, raises a localized error.
+
+use super::{
+    error::{Err, ErrorReason},
+    eval::{r#type::Type, value::Value},
+    locale,
+    runtime::NativeFunction,
+};
+use std::{collections::HashMap, fmt, rc::Rc};
+
+/// A Rust value that can be taken from a Speak value.
+pub trait FromValue: Sized {
+    /// The name of the Speak type converted from, in the active locale.
+    fn type_name() -> String;
+
+    /// The Rust value of the Speak value, if it is of the type converted from.
+    fn from_value(val: &Value) -> Option<Self>;
+}
+
+/// A Rust value that can be returned to Speak.
+pub trait IntoValue {
+    fn into_value(self) -> Result<Value, Err>;
+}
+
+impl FromValue for f64 {
+    fn type_name() -> String {
+        Type::Number.string()
+    }
+
+    fn from_value(val: &Value) -> Option<Self> {
+        match val {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn type_name() -> String {
+        Type::Bool.string()
+    }
+
+    fn from_value(val: &Value) -> Option<Self> {
+        match val {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    fn type_name() -> String {
+        Type::String.string()
+    }
+
+    fn from_value(val: &Value) -> Option<Self> {
+        match val {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn type_name() -> String {
+        format!("[]{}", T::type_name())
+    }
+
+    fn from_value(val: &Value) -> Option<Self> {
+        match val {
+            Value::Array(_, vals) => vals.iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Objects are taken as maps of their fields, whatever their type.
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn type_name() -> String {
+        t!("types.object")
+    }
+
+    fn from_value(val: &Value) -> Option<Self> {
+        match val {
+            Value::Object { body, .. } => body
+                .iter()
+                .map(|(field, (_, val))| Some((field.clone(), T::from_value(val)?)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Result<Value, Err> {
+        Ok(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Result<Value, Err> {
+        Ok(Value::Number(self))
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Result<Value, Err> {
+        Ok(Value::Bool(self))
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Result<Value, Err> {
+        Ok(Value::String(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Result<Value, Err> {
+        Ok(Value::String(self.to_string()))
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Result<Value, Err> {
+        Ok(Value::Empty)
+    }
+}
+
+/// Arrays are of the type of their first item, and their items must all be of one type.
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Result<Value, Err> {
+        let vals = self
+            .into_iter()
+            .map(T::into_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        Value::array(None, vals)
+    }
+}
+
+/// Maps are returned as objects of the `object` type, with a field for each key.
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Result<Value, Err> {
+        let mut body = HashMap::new();
+        for (field, val) in self {
+            let val = val.into_value()?;
+            body.insert(field, (val.value_type(), val));
+        }
+        Ok(Value::Object {
+            name: t!("types.object"),
+            body,
+        })
+    }
+}
+
+/// A registered function may fail with an error of its own.
+impl<T: IntoValue> IntoValue for Result<T, Err> {
+    fn into_value(self) -> Result<Value, Err> {
+        self?.into_value()
+    }
+}
+
+/// A Rust function that can be called from Speak with its arguments converted. It is
+/// implemented for closures of up to four arguments, whose types are `FromValue`.
+pub trait HostFn<Args>: 'static {
+    /// Calls the function named `name` with the Speak arguments.
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, Err>;
+}
+
+/// A function of no arguments is called as `f ()`, with the empty value.
+impl<F, R> HostFn<()> for F
+where
+    F: Fn() -> R + 'static,
+    R: IntoValue,
+{
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, Err> {
+        match args {
+            [] | [Value::Empty] => self().into_value(),
+            _ => Err(arity_e(name, 0, args.len())),
+        }
+    }
+}
+
+macro_rules! host_fn {
+    ($n:literal, $($arg:ident $i:tt),+) => {
+        impl<F, R, $($arg),+> HostFn<($($arg,)+)> for F
+        where
+            F: Fn($($arg),+) -> R + 'static,
+            R: IntoValue,
+            $($arg: FromValue),+
+        {
+            fn call(&self, name: &str, args: &[Value]) -> Result<Value, Err> {
+                if args.len() != $n {
+                    return Err(arity_e(name, $n, args.len()));
+                }
+                self($(argument::<$arg>(name, args, $i)?),+).into_value()
+            }
+        }
+    };
+}
+
+host_fn!(1, A 0);
+host_fn!(2, A 0, B 1);
+host_fn!(3, A 0, B 1, C 2);
+host_fn!(4, A 0, B 1, C 2, D 3);
+
+/// The argument at `i`, converted to the type of the parameter.
+fn argument<T: FromValue>(name: &str, args: &[Value], i: usize) -> Result<T, Err> {
+    T::from_value(&args[i]).ok_or_else(|| Err {
+        message: t!(
+            "errors.argument_e",
+            a = name,
+            b = T::type_name(),
+            c = i + 1,
+            d = args[i].value_type().string()
+        ),
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    })
+}
+
+fn arity_e(name: &str, want: usize, got: usize) -> Err {
+    Err {
+        message: t!("errors.arity_e1", a = name, b = want, c = got),
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    }
+}
+
+type HostCall = Rc<dyn Fn(&str, &[Value]) -> Result<Value, Err>>;
+
+/// A function registered by the embedder, with its name in each locale.
+#[derive(Clone)]
+pub(crate) struct HostFunction {
+    names: Vec<(String, String)>,
+    func: HostCall,
+}
+
+impl HostFunction {
+    pub(crate) fn new<Args>(names: &[(&str, &str)], func: impl HostFn<Args>) -> Self {
+        HostFunction {
+            names: names
+                .iter()
+                .map(|(speak, name)| (speak.to_string(), name.to_string()))
+                .collect(),
+            func: Rc::new(move |name, args| func.call(name, args)),
+        }
+    }
+
+    /// The name of the function in the active locale, or else in the default locale, or
+    /// else the first name it was given.
+    pub(crate) fn name(&self) -> Option<String> {
+        let named = |speak: &str| self.names.iter().find(|(s, _)| s == speak);
+        named(&locale::locale())
+            .or_else(|| named(locale::DEFAULT_LOCALE))
+            .or(self.names.first())
+            .map(|(_, name)| name.clone())
+    }
+
+    /// The native function bound to the name of the function in the active locale.
+    pub(crate) fn native(&self) -> Option<NativeFunction> {
+        let name = self.name()?;
+        let func = Rc::clone(&self.func);
+        Some(NativeFunction::new(name.clone(), move |_, _, args| {
+            func(&name, args)
+        }))
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostFunction {{ names: {:?} }}", self.names)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::Context;
+    use std::{cell::RefCell, collections::HashMap, io::BufReader, rc::Rc};
+
+    fn run(ctx: &mut Context, speak: &str, program: &str) -> Result<String, String> {
+        ctx.exec(speak, BufReader::new(program.as_bytes()))
+            .map(|(val, ..)| val.string())
+            .map_err(|err| err.message)
+    }
+
+    #[test]
+    fn registered_functions() {
+        for tree_walk in [true, false] {
+            let mut ctx = Context::new(&false);
+            ctx.tree_walk = tree_walk;
+
+            // closures keep the state of the host
+            let scores = Rc::new(RefCell::new(Vec::new()));
+            let recorded = Rc::clone(&scores);
+            ctx.register_fn(&[("en", "record"), ("sw", "rekodi")], move |score: f64| {
+                recorded.borrow_mut().push(score)
+            })
+            .register_fn(&[("en", "total")], |xs: Vec<f64>| xs.iter().sum::<f64>())
+            .register_fn(&[("en", "greet")], |name: String, loud: bool| match loud {
+                true => format!("HELLO {}", name.to_uppercase()),
+                false => format!("hello {name}"),
+            })
+            .register_fn(&[("en", "answer")], || 42.0)
+            .register_fn(&[("en", "field")], |obj: HashMap<String, String>| {
+                obj.get("name").cloned().unwrap_or_default()
+            });
+
+            assert_eq!(
+                run(&mut ctx, "en", "record 3\nrecord 4"),
+                Ok("".to_string())
+            );
+            assert_eq!(run(&mut ctx, "sw", "rekodi 5"), Ok("".to_string()));
+            assert_eq!(*scores.borrow(), vec![3.0, 4.0, 5.0]);
+            // the name in the default locale is used for other locales
+            assert_eq!(run(&mut ctx, "sw", "answer ()"), Ok("42".to_string()));
+
+            for (program, want) in [
+                ("xs is [1, 2, 3]\ntotal xs", Ok("6")),
+                ("greet \"juma\" true", Ok("HELLO JUMA")),
+                ("answer ()", Ok("42")),
+                (
+                    "n is \"Osoro\"\np is Person { name n }\nfield p",
+                    Ok("Osoro"),
+                ),
+                ("record", Ok("native function (record)")),
+                (
+                    "total 1",
+                    Err("argument 1 of total must be of type []number, but got (number)"),
+                ),
+                ("greet \"juma\"", Err("greet takes 2 arguments, but got 1")),
+            ] {
+                let want = want.map(str::to_string).map_err(str::to_string);
+                assert_eq!(run(&mut ctx, "en", program), want, "{program}");
+            }
+        }
+    }
+}
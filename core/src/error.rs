@@ -1,4 +1,4 @@
-use rust_i18n::t;
+use crate::lexer::Position;
 use std::sync::mpsc::SendError;
 
 // ErrorReason enums represent possible errors that the Speak interpreter
@@ -10,6 +10,9 @@ pub enum ErrorReason {
     Runtime,
     System,
     Assert,
+    Type,
+    /// Not an error, but a likely mistake the type checker reports without failing.
+    Warning,
 }
 
 impl ErrorReason {
@@ -19,6 +22,8 @@ impl ErrorReason {
             ErrorReason::Runtime => t!("errors.const.runtime"),
             ErrorReason::System => t!("errors.const.system"),
             ErrorReason::Assert => t!("errors.const.assert"),
+            ErrorReason::Type => t!("errors.const.type"),
+            ErrorReason::Warning => t!("errors.const.warning"),
         }
     }
 }
@@ -27,12 +32,178 @@ impl ErrorReason {
 pub struct Err {
     pub reason: ErrorReason,
     pub message: String,
+    pub span: Option<Span>,
+    /// The calls of Speak functions the error was raised in, none if it was raised
+    /// outside of them.
+    pub trace: Option<Box<Trace>>,
 }
 
 impl Err {
     pub fn string(&self) -> String {
         format!("{}: {}", self.reason.string(), self.message)
     }
+
+    /// Locates the error at the position, unless it is already located.
+    pub fn or_at(mut self, position: &Position) -> Self {
+        if self.span.is_none() {
+            self.span = Some(Span::at(position));
+        }
+        self
+    }
+
+    /// Records the call of the Speak function the error was raised in, which was merged
+    /// with `elided` calls before it by tail calls. It is located by `called_at`.
+    pub fn called(mut self, function: String, elided: usize) -> Self {
+        let trace = self.trace.get_or_insert_with(Box::default);
+        trace.frames.push(TraceFrame {
+            function,
+            position: None,
+            file: None,
+            elided,
+        });
+        self
+    }
+
+    /// Locates the calls of the trace not yet located at the position they were called at.
+    pub fn called_at(mut self, position: &Position) -> Self {
+        for frame in self.frames_mut().filter(|frame| frame.position.is_none()) {
+            frame.position = Some(position.clone());
+        }
+        self
+    }
+
+    /// Attaches the name of the file and the source lines of the span to the error,
+    /// unless they are already known, like for errors raised inside a module. The calls
+    /// of the trace not yet in a file are in the file too.
+    pub fn in_source(mut self, file: Option<&str>, source: &str) -> Self {
+        for frame in self.frames_mut().filter(|frame| frame.file.is_none()) {
+            frame.file = file.map(str::to_string);
+        }
+        if let Some(span) = &mut self.span {
+            if span.snippet.is_none() {
+                span.file = file.map(str::to_string);
+                span.snippet = Some(
+                    source
+                        .lines()
+                        .skip(span.start.line.saturating_sub(1))
+                        .take(span.end.line.saturating_sub(span.start.line) + 1)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                );
+            }
+        }
+        self
+    }
+
+    fn frames_mut(&mut self) -> impl Iterator<Item = &mut TraceFrame> {
+        self.trace
+            .iter_mut()
+            .flat_map(|trace| trace.frames.iter_mut())
+    }
+}
+
+/// Span is the region of a Speak program an error refers to, from the start position
+/// to the end position inclusive. The file and snippet are filled in by the Context
+/// executing the program.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Span {
+    pub file: Option<String>,
+    pub start: Position,
+    pub end: Position,
+    /// The source lines from the start to the end of the span.
+    pub snippet: Option<String>,
+}
+
+impl Span {
+    /// A span covering the char at the position.
+    pub fn at(position: &Position) -> Self {
+        Span {
+            start: position.clone(),
+            end: position.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// A span from the start to the end position.
+    pub fn new(start: &Position, end: &Position) -> Self {
+        Span {
+            start: start.clone(),
+            end: end.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Renders the first line of the snippet with the chars of the span underlined
+    /// by carets, in the manner of rustc. Returns None if the snippet is not known.
+    pub fn excerpt(&self) -> Option<String> {
+        let line = self.snippet.as_ref()?.lines().next().unwrap_or("");
+        let location = match &self.file {
+            Some(file) => format!("{}:{}", file, self.start.string()),
+            None => self.start.string(),
+        };
+
+        // the marker keeps the tabs of the line so the carets stay aligned
+        let offset = self.start.column.saturating_sub(1);
+        let width = match self.end.line > self.start.line {
+            true => line.chars().count().saturating_sub(offset),
+            false => (self.end.column + 1).saturating_sub(self.start.column),
+        };
+        let marker = line
+            .chars()
+            .take(offset)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .chain(std::iter::repeat_n('^', width.max(1)))
+            .collect::<String>();
+
+        let gutter = " ".repeat(self.start.line.to_string().len());
+        Some(format!(
+            "{gutter}--> {location}\n{gutter} |\n{} | {line}\n{gutter} | {marker}\n",
+            self.start.line
+        ))
+    }
+}
+
+/// Trace is the calls of Speak functions an error was raised in. Errors hold it boxed,
+/// as they are returned by value and most are raised outside of functions.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Trace {
+    /// The calls, the innermost first.
+    pub frames: Vec<TraceFrame>,
+}
+
+/// TraceFrame is a call of a Speak function on the way to an error.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraceFrame {
+    /// The name of the function, empty for anonymous functions.
+    pub function: String,
+    /// Where the function was called.
+    pub position: Option<Position>,
+    pub file: Option<String>,
+    /// The number of calls before this one that were merged into it by tail calls, and
+    /// are not in the trace.
+    pub elided: usize,
+}
+
+impl TraceFrame {
+    /// Renders the call in the active locale, like `in fib (main.spk:3:5)`.
+    pub fn string(&self) -> String {
+        let function = match self.function.as_str() {
+            "" => "_",
+            name => name,
+        };
+        let location = match (&self.file, &self.position) {
+            (Some(file), Some(position)) => format!("{}:{}", file, position.string()),
+            (None, Some(position)) => position.string(),
+            (Some(file), None) => file.clone(),
+            (None, None) => "?".to_string(),
+        };
+
+        let frame = t!("misc.trace_frame", a = function, b = location);
+        match self.elided {
+            0 => frame,
+            elided => format!("{frame}\n{}", t!("misc.trace_elided", a = elided)),
+        }
+    }
 }
 
 impl From<std::io::Error> for Err {
@@ -40,6 +211,8 @@ impl From<std::io::Error> for Err {
         Err {
             reason: ErrorReason::System,
             message: err.to_string(),
+            span: None,
+            trace: None,
         }
     }
 }
@@ -49,6 +222,47 @@ impl<T> From<SendError<T>> for Err {
         Err {
             reason: ErrorReason::System,
             message: err.to_string(),
+            span: None,
+            trace: None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn span_excerpt() {
+        let err = Err {
+            reason: ErrorReason::Runtime,
+            message: "x is not defined".to_string(),
+            span: Some(Span::new(
+                &Position { line: 2, column: 9 },
+                &Position {
+                    line: 2,
+                    column: 10,
+                },
+            )),
+            trace: None,
+        }
+        .in_source(Some("main.spk"), "x is 1\nprintln yy\n");
+
+        assert_eq!(
+            err.span
+                .and_then(|span| span.excerpt())
+                .expect("the snippet is known"),
+            " --> main.spk:2:9\n  |\n2 | println yy\n  |         ^^\n"
+        );
+
+        // spans past the end of the line, such as an unexpected end of input, and tabs
+        let span = Span {
+            snippet: Some("\tx is".to_string()),
+            ..Span::at(&Position { line: 1, column: 6 })
+        };
+        assert_eq!(
+            span.excerpt().expect("the snippet is known"),
+            " --> 1:6\n  |\n1 | \tx is\n  | \t    ^\n"
+        );
+    }
+}
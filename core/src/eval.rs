@@ -3,18 +3,18 @@ use self::{
     value::{Function, Value},
 };
 use super::{
-    error::{Err, ErrorReason},
+    error::{Err, ErrorReason, Span},
     lexer::{Kind, Position},
-    parser::Node,
-    runtime::{StackFrame, VTable},
+    parser::{MatchArm, Node, Pattern},
+    runtime::{Io, StackFrame, VTable},
 };
-use rust_i18n::t;
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc, vec};
+use unicode_segmentation::UnicodeSegmentation;
 
 pub mod r#type {
-    use rust_i18n::t;
+    use serde_derive::{Deserialize, Serialize};
 
-    #[derive(Debug, PartialEq, Eq, Clone)]
+    #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
     pub enum Type {
         /// Floating point number: f64
         Number,
@@ -34,6 +34,12 @@ pub mod r#type {
         /// Function type.
         Function,
 
+        /// Module type.
+        Module,
+
+        /// Range type, of the numbers from a start up to an end.
+        Range,
+
         /// Empty type.
         Empty,
     }
@@ -48,6 +54,8 @@ pub mod r#type {
                 Type::Object(obj) => format!("{}: {}", t!("types.object"), obj),
                 Type::Array(t) => format!("[]{}", t.string()),
                 Type::Function => t!("types.function"),
+                Type::Module => t!("types.module"),
+                Type::Range => t!("types.range"),
                 Type::Empty => "()".to_string(),
             }
         }
@@ -58,6 +66,7 @@ pub mod r#type {
                 x if x == t!("types.bool") => Type::Bool,
                 x if x == t!("types.string") => Type::String,
                 x if x == t!("types.function") => Type::Function,
+                x if x == t!("types.range") => Type::Range,
                 "()" => Type::Empty,
                 _ => Type::Object(type_name.to_string()), // If erroneous, fails at Runtime
             }
@@ -66,14 +75,21 @@ pub mod r#type {
 }
 
 pub mod value {
-    use rust_i18n::t;
-
     use super::r#type::Type;
     use crate::{
+        bytecode::Proto,
+        error::{Err, ErrorReason},
+        lexer::Position,
         parser::Node,
-        runtime::{NativeFn, VTable, MAX_PRINT_LEN},
+        runtime::{NativeFunction, StackFrame, VTable, MAX_PRINT_LEN},
+    };
+    use serde::{de, ser, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::{Map, Value as Json};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        fmt::{self, Debug},
+        rc::Rc,
     };
-    use std::{collections::HashMap, fmt::Debug};
 
     /// Value represents any value in the Speak programming language.
     /// Each value corresponds to some primitive or object value created
@@ -92,16 +108,31 @@ pub mod value {
 
         Array(Type, Vec<Value>),
 
+        /// This is a range of numbers, `start..end`, from the start up to the end excluded.
+        /// Its numbers are only counted when it is iterated.
+        Range {
+            start: f64,
+            end: f64,
+        },
+
         /// This is the value of any variables referencing functions defined in a Speak program.
         Function(Function),
 
         /// This is a function whose implementation is written in rust and is part of the interpreter.
-        NativeFunction(NativeFn),
+        NativeFunction(NativeFunction),
+
+        /// This is a loaded module, holding the top-level bindings of the module's file.
+        Module {
+            name: String,
+            vt: VTable,
+        },
 
         /// This is an internal representation of a lazy function evaluation used to implement tail call optimization.
         FunctionCallThunk {
             vt: VTable,
             func: Function,
+            /// Where the function was called, once the call has returned the thunk.
+            at: Option<Position>,
         },
 
         /// Assignment is a value that holds an assignment operation value after having been pushed to the stack.
@@ -115,17 +146,32 @@ pub mod value {
         BreakCalled,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Clone)]
     pub struct Function {
         // defn must be of variant `FunctionLiteral`.
-        pub defn: Node,
+        pub defn: Rc<Node>,
+        /// The function's body compiled to bytecode, if it was defined by the VM.
+        pub code: Option<Rc<Proto>>,
+        /// The stack frames the function was defined in, which its body runs on. Names
+        /// bound there later, or changed since, are seen by the function.
+        pub env: StackFrame,
+    }
+
+    impl Debug for Function {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            // the stack frames are left out, they may hold the function itself
+            f.debug_struct("Function")
+                .field("defn", &self.defn)
+                .finish_non_exhaustive()
+        }
     }
 
     impl Function {
         fn string(&self) -> String {
             let func_str = self.defn.string();
-            if func_str.len() > MAX_PRINT_LEN {
-                return format!("{}..", &func_str[..MAX_PRINT_LEN]);
+            if func_str.chars().count() > MAX_PRINT_LEN {
+                let func_str = func_str.chars().take(MAX_PRINT_LEN).collect::<String>();
+                return format!("{}..", func_str);
             }
 
             func_str
@@ -140,9 +186,11 @@ pub mod value {
                 Value::String(_) => Type::String,
                 Value::Object { name, .. } => Type::Object(name.clone()),
                 Value::Array(t, ..) => Type::Array(Box::new(t.clone())),
+                Value::Range { .. } => Type::Range,
                 Value::Function { .. }
                 | Value::FunctionCallThunk { .. }
                 | Value::NativeFunction(..) => Type::Function,
+                Value::Module { .. } => Type::Module,
                 Value::Empty => Type::Empty,
                 Value::Assignment(val) => val.value_type(),
                 Value::ContinueCalled | Value::BreakCalled => Type::Empty,
@@ -154,6 +202,9 @@ pub mod value {
                 (Value::Number(a), Value::Number(b)) => a == &b,
                 (Value::Bool(a), Value::Bool(b)) => a == &b,
                 (Value::String(a), Value::String(b)) => a == &b,
+                (Value::Range { start, end }, Value::Range { start: s, end: e }) => {
+                    start == &s && end == &e
+                }
                 (Value::Empty, Value::Empty) => true,
                 _ => false, // types here are incomparable
             }
@@ -164,16 +215,21 @@ pub mod value {
                 Value::Number(value) => value.to_string(),
                 Value::Bool(value) => value.to_string(),
                 Value::String(value) => value.to_string(),
+                // fields are written in the order of their names, for the same output
+                // on every run
                 Value::Object { name, body } => {
+                    let body = body.iter().collect::<BTreeMap<_, _>>();
                     format!("{} ({name}): {:?}", t!("types.object"), body)
                 }
                 Value::Array(t, value) => {
                     format!("{} ([]{}): {:?}", t!("types.array"), t.string(), value)
                 }
+                Value::Range { start, end } => format!("{start}..{end}"),
                 Value::Function(func) => func.string(),
                 Value::NativeFunction(func) => {
                     format!("{} ({})", t!("types.native_function"), func.0)
                 }
+                Value::Module { name, .. } => format!("{} ({name})", t!("types.module")),
                 Value::FunctionCallThunk { func, .. } => {
                     format!("Thunk {} ({})", t!("misc.of"), func.string())
                 }
@@ -183,6 +239,123 @@ pub mod value {
                 Value::BreakCalled => t!("literals.break"),
             }
         }
+
+        /// An array of the values, of the type `t` or else of the type of the first value.
+        /// The values must all be of the type of the array.
+        pub(crate) fn array(t: Option<Type>, vals: Vec<Value>) -> Result<Value, Err> {
+            let t = t.unwrap_or_else(|| vals.first().map_or(Type::Empty, Value::value_type));
+            if let Some(val) = vals.iter().find(|val| val.value_type() != t) {
+                return Err(Err {
+                    message: t!(
+                        "errors.into_value_e",
+                        a = t.string(),
+                        b = val.value_type().string()
+                    ),
+                    reason: ErrorReason::Runtime,
+                    span: None,
+                    trace: None,
+                });
+            }
+            Ok(Value::Array(t, vals))
+        }
+    }
+
+    /// Objects are serialized as maps of their `name` and `body`, and arrays as maps of
+    /// the `type` of their items and the `items`, so that they deserialize to the values
+    /// they were. `()` is serialized as unit. Functions and modules cannot be serialized.
+    impl Serialize for Value {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Value::Number(n) => serializer.serialize_f64(*n),
+                Value::Bool(b) => serializer.serialize_bool(*b),
+                Value::String(s) => serializer.serialize_str(s),
+                Value::Empty => serializer.serialize_unit(),
+                Value::Object { name, body } => {
+                    let body = body
+                        .iter()
+                        .map(|(field, (_, val))| (field, val))
+                        .collect::<BTreeMap<_, _>>();
+                    let mut map = serializer.serialize_map(Some(2))?;
+                    map.serialize_entry("name", name)?;
+                    map.serialize_entry("body", &body)?;
+                    map.end()
+                }
+                Value::Array(t, vals) => {
+                    let mut map = serializer.serialize_map(Some(2))?;
+                    map.serialize_entry("type", t)?;
+                    map.serialize_entry("items", vals)?;
+                    map.end()
+                }
+                Value::Assignment(val) => val.serialize(serializer),
+                val => Err(ser::Error::custom(t!(
+                    "errors.serialize_e",
+                    a = val.value_type().string()
+                ))),
+            }
+        }
+    }
+
+    /// Besides the serialized forms of values, plain sequences deserialize to arrays of
+    /// the type of their first item and other maps to objects of the `object` type, so
+    /// that any JSON document can be read.
+    impl<'de> Deserialize<'de> for Value {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            // buffered as JSON, to tell the serialized forms from other maps
+            let json = Json::deserialize(deserializer)?;
+            from_json(json).map_err(|err| de::Error::custom(err.message))
+        }
+    }
+
+    fn from_json(json: Json) -> Result<Value, Err> {
+        let val = match json {
+            Json::Null => Value::Empty,
+            Json::Bool(b) => Value::Bool(b),
+            Json::Number(n) => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+            Json::String(s) => Value::String(s),
+            Json::Array(items) => Value::array(None, from_items(items)?)?,
+            // the serialized forms of arrays and of objects, of two entries each
+            Json::Object(mut map) => {
+                let t = map.get("type").and_then(|t| Type::deserialize(t).ok());
+                match (
+                    map.len(),
+                    t,
+                    map.get("items"),
+                    map.get("name"),
+                    map.get("body"),
+                ) {
+                    (2, Some(t), Some(Json::Array(_)), ..) => {
+                        let items = match map.remove("items") {
+                            Some(Json::Array(items)) => items,
+                            _ => Vec::new(),
+                        };
+                        Value::array(Some(t), from_items(items)?)?
+                    }
+                    (2, _, _, Some(Json::String(name)), Some(Json::Object(_))) => {
+                        let name = name.clone();
+                        let body = match map.remove("body") {
+                            Some(Json::Object(body)) => body,
+                            _ => Map::new(),
+                        };
+                        object(name, body)?
+                    }
+                    _ => object(t!("types.object"), map)?,
+                }
+            }
+        };
+        Ok(val)
+    }
+
+    fn from_items(items: Vec<Json>) -> Result<Vec<Value>, Err> {
+        items.into_iter().map(from_json).collect()
+    }
+
+    fn object(name: String, fields: Map<String, Json>) -> Result<Value, Err> {
+        let mut body = HashMap::new();
+        for (field, json) in fields {
+            let val = from_json(json)?;
+            body.insert(field, (val.value_type(), val));
+        }
+        Ok(Value::Object { name, body })
     }
 }
 
@@ -207,29 +380,11 @@ impl Node {
             Node::StringLiteral { value, .. } => Ok(Value::String(value.clone())),
             Node::BoolLiteral { value, .. } => Ok(Value::Bool(*value)),
             Node::ArrayLiteral { value, .. } => {
-                let value_type = match value.is_empty() {
-                    true => Type::Empty,
-                    false => value[0].eval(ctx, stack, false)?.value_type(),
-                };
-                Ok(Value::Array(value_type.clone(), {
-                    let mut values = Vec::with_capacity(value.len());
-                    for node in value {
-                        let val = node.eval(ctx, stack, false)?;
-                        if val.value_type() != value_type {
-                            return Err(Err {
-                                message: t!(
-                                    "errors.eval_e1",
-                                    a = value_type.string(),
-                                    b = val.value_type().string(),
-                                    c = node.position().string()
-                                ),
-                                reason: ErrorReason::Runtime,
-                            });
-                        }
-                        values.push(val);
-                    }
-                    values
-                }))
+                let mut values = Vec::with_capacity(value.len());
+                for node in value.iter_mut() {
+                    values.push(node.eval(ctx, stack, false)?);
+                }
+                array_value(self, values)
             }
             Node::ObjectLiteral { name, value, .. } => {
                 let mut body = HashMap::new();
@@ -245,15 +400,8 @@ impl Node {
                 })
             }
             Node::EmptyLiteral { .. } | Node::EmptyIdentifier { .. } => Ok(Value::Empty),
-            Node::Identifier { value, position } => {
-                if let Some(val) = stack.get(value) {
-                    return Ok(val.clone());
-                }
-                Err(Err {
-                    message: t!("errors.eval_e2", a = value, b = position.string()),
-                    reason: ErrorReason::System,
-                })
-            }
+            Node::Identifier { .. } => identifier_value(self, stack),
+            Node::ModuleAccess { .. } => member_value(self, stack),
             Node::UnaryExpression {
                 operator,
                 operand,
@@ -272,6 +420,8 @@ impl Node {
                         _ => Err(Err {
                             message: t!("errors.eval_e3", a = op.string(), b = position.string()),
                             reason: ErrorReason::Runtime,
+                            span: Some(Span::at(position)),
+                            trace: None,
                         }),
                     }
                 };
@@ -283,14 +433,8 @@ impl Node {
                             Ok(mut_operand(operand)?)
                         }
 
-                        Node::Identifier { value, position } => {
-                            if let Some(_val) = stack.get(value) {
-                                return mut_operand(operand);
-                            }
-                            return Err(Err {
-                                message: t!("errors.eval_e2", a = value, b = position.string()),
-                                reason: ErrorReason::System,
-                            });
+                        Node::Identifier { .. } => {
+                            Err(negate_identifier_e(cl_operand, position, stack))
                         }
                         _ => Err(Err {
                             message: t!(
@@ -299,6 +443,8 @@ impl Node {
                                 b = position.string()
                             ),
                             reason: ErrorReason::Syntax,
+                            span: Some(Span::at(position)),
+                            trace: None,
                         }),
                     },
 
@@ -309,75 +455,39 @@ impl Node {
                             b = position.string()
                         ),
                         reason: ErrorReason::Syntax,
+                        span: Some(Span::at(position)),
+                        trace: None,
                     }),
                 }
             }
             Node::BinaryExpression { .. } => eval_binary_expr_node(ctx, self, stack),
             Node::IndexingOp { operand, index, .. } => {
-                match operand.eval(ctx, stack, false)? {
-                    Value::Array(_, vals) => {
-                        let idx = to_usize(&(to_number(index, stack)?), index.position())?;
-                        match idx >= vals.len() {
-                            true => Ok(Value::Empty), // index out of bounds return ()
-                            false => Ok(vals[idx].clone()),
-                        }
-                    }
-                    _ => Err(Err {
-                        message: t!(
-                            "errors.eval_e5",
-                            a = operand.string(),
-                            b = operand.position().string()
-                        ),
-                        reason: ErrorReason::Runtime,
-                    }),
-                }
+                let val = indexable(operand.eval(ctx, stack, false)?, operand)?;
+                let idx = to_index(index.eval(&UD, stack, false)?, index)?;
+                Ok(index_value(val, idx))
             }
             Node::SlicingOp {
                 operand,
                 start_inclusive,
                 end_exclusive,
                 ..
-            } => match operand.eval(ctx, stack, false)? {
-                Value::Array(t, mut vals) => match (start_inclusive, end_exclusive) {
-                    (Some(x), None) => {
-                        // array[start..]
-                        Ok(Value::Array(
-                            t,
-                            vals.split_off(to_usize(&(to_number(x, stack)?), x.position())?),
-                        ))
-                    }
-                    (None, Some(x)) => {
-                        // array[..end]
-                        _ = vals.split_off(to_usize(&(to_number(x, stack)?), x.position())?);
-                        Ok(Value::Array(t, vals))
-                    }
-                    (Some(x), Some(y)) => {
-                        // array[start:end]
-                        _ = vals.split_off(to_usize(&(to_number(y, stack)?), y.position())?);
-                        Ok(Value::Array(
-                            t,
-                            vals.split_off(to_usize(&(to_number(x, stack)?), x.position())?),
-                        ))
-                    }
-                    (None, None) => Err(Err {
-                        message: t!("errors.eval_e6"),
-                        reason: ErrorReason::Assert,
-                    }),
-                },
-                _ => Err(Err {
-                    message: t!(
-                        "errors.eval_e5",
-                        a = operand.string(),
-                        b = operand.position().string()
-                    ),
-                    reason: ErrorReason::Runtime,
-                }),
-            },
+            } => {
+                let val = indexable(operand.eval(ctx, stack, false)?, operand)?;
+                let end = match end_exclusive {
+                    Some(x) => Some(to_index(x.eval(&UD, stack, false)?, x)?),
+                    None => None,
+                };
+                let start = match start_inclusive {
+                    Some(x) => Some(to_index(x.eval(&UD, stack, false)?, x)?),
+                    None => None,
+                };
+                slice_value(val, start, end)
+            }
 
             Node::FunctionCall {
                 function,
                 arguments,
-                ..
+                position,
             } => {
                 let mut arg_results = Vec::new();
                 for arg in arguments {
@@ -386,57 +496,59 @@ impl Node {
 
                 let fn_value = &function.eval(ctx, stack, false)?;
 
-                let res = eval_speak_function(ctx, stack, fn_value, allow_thunk, &arg_results)?;
-
-                Ok(res)
+                // errors of native functions are located at the call, as are the calls
+                // of the trace made by it
+                let mut val = eval_speak_function(ctx, stack, fn_value, allow_thunk, &arg_results)
+                    .map_err(|err| err.or_at(position).called_at(position))?;
+                if let Value::FunctionCallThunk { at, .. } = &mut val {
+                    *at = Some(position.clone());
+                }
+                Ok(val)
             }
             Node::FunctionLiteral { sign, .. } => {
-                // place the function literal on the current stack and return no value
-                match sign.0.as_ref() {
-                    Node::Identifier { value, .. } => {
-                        stack.set(
-                            value.clone(),
-                            Value::Function(Function { defn: self.clone() }),
-                        );
+                let name = sign.0.as_ref().clone();
+                let func = Value::Function(Function {
+                    defn: Rc::new(self.clone()),
+                    code: None,
+                    env: stack.clone(),
+                });
 
+                // place the function literal on the current stack and return no value,
+                // while an anonymous function literal is a value
+                match &name {
+                    Node::Identifier { value, .. } => {
+                        stack.set(value.clone(), func);
                         Ok(Value::Empty)
                     }
+                    Node::EmptyIdentifier { .. } => Ok(func),
                     _ => Err(Err {
                         message: t!(
                             "errors.eval_e7",
-                            a = sign.0.string(),
-                            b = sign.0.position().string()
+                            a = name.string(),
+                            b = name.position().string()
                         ),
                         reason: ErrorReason::Assert,
+                        span: Some(Span::at(name.position())),
+                        trace: None,
                     }),
                 }
             }
             Node::IfExpr { .. } => eval_if_expr_node(ctx, self, stack, allow_thunk),
 
-            Node::ForExpr { .. } => eval_for_expr_node(ctx, self, stack, allow_thunk),
+            Node::ForExpr { .. } => eval_for_expr_node(ctx, self, stack),
+
+            Node::WhileExpr { .. } => eval_while_expr_node(self, stack),
+
+            Node::MatchExpr { .. } => eval_match_expr_node(ctx, self, stack, allow_thunk),
 
             Node::Continueliteral { .. } => match ctx {
                 CallerCtx::InLoop => Ok(Value::ContinueCalled),
-                _ => Err(Err {
-                    message: t!(
-                        "errors.eval_e8",
-                        a = self.string(),
-                        b = self.position().string()
-                    ),
-                    reason: ErrorReason::Runtime,
-                }),
+                _ => Err(outside_loop_e(self)),
             },
 
             Node::BreakLiteral { .. } => match ctx {
                 CallerCtx::InLoop => Ok(Value::BreakCalled),
-                _ => Err(Err {
-                    message: t!(
-                        "errors.eval_e8",
-                        a = self.string(),
-                        b = self.position().string()
-                    ),
-                    reason: ErrorReason::Runtime,
-                }),
+                _ => Err(outside_loop_e(self)),
             },
         }
     }
@@ -457,149 +569,142 @@ fn eval_if_expr_node(
     {
         // assert that condition evaluates to boolean value
         let mut condition = condition.as_ref().clone();
-        let val = condition.eval(ctx, stack, allow_thunk)?;
-
-        let mut ret = |val| {
-            if val {
-                return match on_true {
-                    Some(on_true) => {
-                        let mut on_true = on_true.as_ref().clone();
-                        on_true.eval(ctx, stack, allow_thunk)
-                    }
-                    None => Ok(Value::Empty),
-                };
+        let val = condition.eval(ctx, stack, false)?;
+
+        let clause = match condition_value(val, node)? {
+            true => on_true,
+            false => on_false,
+        };
+        // the clause's value is that of its last statement, which may be a tail call,
+        // unless a statement before it continues or breaks a loop
+        let mut val = Value::Empty;
+        for (i, stmt) in clause.iter().flatten().enumerate() {
+            let last = i + 1 == clause.as_ref().map_or(0, Vec::len);
+            val = stmt.clone().eval(ctx, stack, allow_thunk && last)?;
+            if let Value::ContinueCalled | Value::BreakCalled = val {
+                break;
             }
-            match on_false {
-                Some(on_false) => {
-                    let mut on_false = on_false.as_ref().clone();
-                    on_false.eval(ctx, stack, allow_thunk)
+        }
+        return Ok(val);
+    }
+
+    Err(Err {
+        reason: ErrorReason::System,
+        span: None,
+        message: "todo!".to_string(),
+        trace: None,
+    })
+}
+
+fn eval_for_expr_node(ctx: &CallerCtx, node: &Node, stack: &mut StackFrame) -> Result<Value, Err> {
+    if let Node::ForExpr { iterable, body, .. } = node {
+        let val = iterable.as_ref().clone().eval(ctx, stack, false)?;
+        let (var, items) = loop_items(node, val)?;
+
+        // we only loop if there's a body
+        if let Some(body) = body {
+            // the loop variable, and names first bound in the body, are in a frame of
+            // the loop; names of the enclosing frames are updated in place
+            let mut stack = stack.clone();
+            stack.push_frame(VTable(HashMap::new()));
+            'outer: for item in items {
+                stack.set(var.clone(), item);
+                let mut body = body.clone();
+                for stmt in body.iter_mut() {
+                    let ret = stmt.eval(&CallerCtx::InLoop, &mut stack, false)?;
+                    match ret {
+                        // if the loop returns an non empty value continue current loop
+                        Value::Empty | Value::Assignment(..) => continue,
+                        // if contine is called continue outer loop
+                        Value::ContinueCalled => continue 'outer,
+                        // if break is called break from outer loop
+                        Value::BreakCalled => break 'outer,
+                        // else return value
+                        _ => return Ok(ret),
+                    }
                 }
-                None => Ok(Value::Empty),
             }
-        };
+        }
 
-        return match val {
-            Value::Bool(val) => ret(val),
-            Value::String(str) => ret(str.is_empty()),
-            _ => Err(Err {
-                message: t!(
-                    "errors.eval_if_expr_node_e1",
-                    a = condition.string(),
-                    b = node.position().string()
-                ),
-                reason: ErrorReason::Runtime,
-            }),
-        };
+        return Ok(Value::Empty);
     }
 
     Err(Err {
         reason: ErrorReason::System,
+        span: None,
         message: "todo!".to_string(),
+        trace: None,
     })
 }
 
-fn eval_for_expr_node(
-    ctx: &CallerCtx,
-    node: &Node,
-    stack: &mut StackFrame,
-    allow_thunk: bool,
-) -> Result<Value, Err> {
-    if let Node::ForExpr {
-        variable,
-        iterable,
-        body,
-        position,
+fn eval_while_expr_node(node: &Node, stack: &mut StackFrame) -> Result<Value, Err> {
+    if let Node::WhileExpr {
+        condition, body, ..
     } = node
     {
-        let mut iterable = iterable.as_ref().clone();
-        let val = iterable.eval(ctx, stack, allow_thunk)?;
-
-        let var: String;
-        match variable.as_ref() {
-            Node::Identifier { value, .. } => {
-                var = value.clone();
+        // names first bound in the body are in a frame of the loop, as in a for loop
+        let mut stack = stack.clone();
+        stack.push_frame(VTable(HashMap::new()));
+        'outer: loop {
+            let val = condition
+                .as_ref()
+                .clone()
+                .eval(&CallerCtx::InLoop, &mut stack, false)?;
+            if !condition_value(val, node)? {
+                break;
             }
-            _ => {
-                return Err(Err {
-                    message: t!(
-                        "errors.eval_e7",
-                        a = variable.string(),
-                        b = position.string()
-                    ),
-                    reason: ErrorReason::Runtime,
-                });
+
+            let mut body = body.clone().unwrap_or_default();
+            for stmt in body.iter_mut() {
+                let ret = stmt.eval(&CallerCtx::InLoop, &mut stack, false)?;
+                match ret {
+                    Value::Empty | Value::Assignment(..) => continue,
+                    Value::ContinueCalled => continue 'outer,
+                    Value::BreakCalled => break 'outer,
+                    _ => return Ok(ret),
+                }
             }
         }
 
-        match val {
-            Value::String(val) => {
-                // we only loop if there's a body
-                if let Some(body) = body.as_ref().clone() {
-                    let mut stack = stack.clone();
-                    'outer: for c in val.chars() {
-                        stack.set(var.clone(), Value::Number(c as u8 as f64));
-                        let mut body = body.clone();
-                        for stmt in body.iter_mut() {
-                            let ret = stmt.eval(&CallerCtx::InLoop, &mut stack, allow_thunk)?;
-                            match ret {
-                                // if the loop returns an non empty value continue current loop
-                                Value::Empty | Value::Assignment(..) => continue,
-                                // if contine is called continue outer loop
-                                Value::ContinueCalled => continue 'outer,
-                                // if break is called break from outer loop
-                                Value::BreakCalled => break 'outer,
-                                // else return value
-                                _ => return Ok(ret),
-                            }
-                        }
-                    }
-                }
+        return Ok(Value::Empty);
+    }
+
+    Err(unexpected_node_e(node))
+}
 
-                return Ok(Value::Empty);
+fn eval_match_expr_node(
+    ctx: &CallerCtx,
+    node: &Node,
+    stack: &mut StackFrame,
+    allow_thunk: bool,
+) -> Result<Value, Err> {
+    if let Node::MatchExpr { value, arms, .. } = node {
+        let mut value = value.as_ref().clone();
+        let val = value.eval(ctx, stack, false)?;
+
+        for arm in arms {
+            let mut bindings = HashMap::new();
+            if !match_pattern(&arm.pattern, &val, &mut bindings) {
+                continue;
             }
 
-            Value::Array(_, items) => {
-                // we only loop if there's a body
-                if let Some(body) = body.as_ref().clone() {
-                    let mut stack = stack.clone();
-                    'outer: for item in items {
-                        stack.set(var.clone(), item);
-                        let mut body = body.clone();
-                        for stmt in body.iter_mut() {
-                            let ret = stmt.eval(&CallerCtx::InLoop, &mut stack, allow_thunk)?;
-                            match ret {
-                                // if the loop returns an non empty value continue current loop
-                                Value::Empty | Value::Assignment(..) => continue,
-                                // if contine is called continue outer loop
-                                Value::ContinueCalled => continue 'outer,
-                                // if break is called break from outer loop
-                                Value::BreakCalled => break 'outer,
-                                // else return value
-                                _ => return Ok(ret),
-                            }
-                        }
-                    }
+            // the names bound by the pattern are in a frame of the arm
+            let mut stack = stack.clone();
+            stack.push_frame(VTable(bindings));
+            if let Some(guard) = &arm.guard {
+                let val = guard.clone().eval(ctx, &mut stack, false)?;
+                if !guard_value(val, arm)? {
+                    continue;
                 }
-
-                return Ok(Value::Empty);
-            }
-            _ => {
-                return Err(Err {
-                    message: t!(
-                        "errors.eval_for_expr_node_e1",
-                        a = iterable.string(),
-                        b = position.string()
-                    ),
-                    reason: ErrorReason::Runtime,
-                })
             }
+            return arm.body.clone().eval(ctx, &mut stack, allow_thunk);
         }
+
+        // like an if expression without an else clause, no arm taken is empty
+        return Ok(Value::Empty);
     }
 
-    Err(Err {
-        reason: ErrorReason::System,
-        message: "todo!".to_string(),
-    })
+    Err(unexpected_node_e(node))
 }
 
 fn eval_binary_expr_node(
@@ -611,22 +716,9 @@ fn eval_binary_expr_node(
         operator,
         left_operand,
         right_operand,
-        position,
+        ..
     } = node
     {
-        let mut left_right = || -> Result<(Value, Value), Err> {
-            Ok((
-                {
-                    let mut l = left_operand.as_ref().clone();
-                    l.eval(ctx, stack, false)?
-                },
-                {
-                    let mut r = right_operand.as_ref().clone();
-                    r.eval(ctx, stack, false)?
-                },
-            ))
-        };
-
         match operator {
             Kind::AssignOp => {
                 match left_operand.as_ref() {
@@ -635,11 +727,7 @@ fn eval_binary_expr_node(
                         let mut r = right_operand.as_ref().clone();
                         let right_value = r.eval(ctx, stack, false)?;
 
-                        // try make an update first, if fails push value to stack
-                        if let Err(_) = stack.up(value.clone(), &right_value) {
-                            stack.set(value.clone(), right_value.clone());
-                        }
-
+                        assign(stack, value, right_value.clone());
                         return Ok(Value::Assignment(Box::new(right_value)));
                     }
 
@@ -653,362 +741,259 @@ fn eval_binary_expr_node(
 
                     Node::IndexingOp { operand, index, .. } => {
                         let mut operand = operand.as_ref().clone();
-                        match operand.eval(ctx, stack, false)? {
-                            Value::Array(arr_type, mut vals) => {
-                                let mut index = index.as_ref().clone();
-                                let idx =
-                                    to_usize(&(to_number(&mut index, stack)?), index.position())?;
-
-                                // if index out of bounds, extend vec
-                                if idx >= vals.len() {
-                                    vals.resize(idx + 1, Value::Empty);
-                                }
-
-                                // right operand node must evaluate to a value
-                                let mut r = right_operand.as_ref().clone();
-                                let right_value = r.eval(ctx, stack, false)?;
+                        let array = array_target(operand.eval(ctx, stack, false)?, &operand)?;
 
-                                vals[idx] = right_value.clone();
+                        let mut index = index.as_ref().clone();
+                        let idx = to_index(index.eval(&UD, stack, false)?, &index)?;
 
-                                // update stack
-                                stack.up(operand.string(), &Value::Array(arr_type, vals))?;
+                        // right operand node must evaluate to a value
+                        let mut r = right_operand.as_ref().clone();
+                        let right_value = r.eval(ctx, stack, false)?;
 
-                                return Ok(Value::Assignment(Box::new(right_value)));
-                            }
-                            _ => {
-                                return Err(Err {
-                                    message: t!(
-                                        "errors.eval_e5",
-                                        a = operand.string(),
-                                        b = operand.position().string()
-                                    ),
-                                    reason: ErrorReason::Runtime,
-                                });
-                            }
-                        }
+                        return set_index(stack, &operand, array, idx, right_value);
                     }
 
                     Node::BinaryExpression {
-                        operator: l_operator,
+                        operator: Kind::AccessorOp,
                         left_operand: l_left_operand,
                         right_operand: l_right_operand,
                         position: l_position,
                     } => {
-                        if let Kind::AccessorOp = l_operator {
-                            // left operand is stack name for object
-                            let object = l_left_operand.as_ref().clone().eval(ctx, stack, false)?;
-                            // right operand is the field value
-                            let object_field = l_right_operand.string();
-
-                            fn update_field(
-                                ctx: &CallerCtx,
-                                stack: &mut StackFrame,
-                                object: Value,
-                                object_field: String,
-                                l_left_operand: &Box<Node>,
-                                l_position: &Position,
-                                right_operand: &Box<Node>,
-                            ) -> Result<Value, Err> {
-                                // mutate field value
-                                match &mut object.clone() {
-                                    Value::Object { name, body } => {
-                                        match body.contains_key(&object_field) {
-                                            true => {
-                                                let right_value = right_operand
-                                                    .as_ref()
-                                                    .clone()
-                                                    .eval(ctx, stack, false)?;
-                                                body.insert(
-                                                    object_field,
-                                                    (right_value.value_type(), right_value),
-                                                );
-
-                                                let obj = Value::Object {
-                                                    name: name.to_string(),
-                                                    body: body.clone(),
-                                                };
-
-                                                stack.up(l_left_operand.string(), &obj)?;
-                                                return Ok(obj);
-                                            }
-                                            false => {
-                                                return Err(Err {
-                                                    message: t!(
-                                                        "errors.eval_binary_expr_node_e1",
-                                                        a = name,
-                                                        b = object.string(),
-                                                        c = l_position.string()
-                                                    ),
-                                                    reason: ErrorReason::Runtime,
-                                                });
-                                            }
-                                        }
-                                    }
-
-                                    Value::Assignment(val) => update_field(
-                                        ctx,
-                                        stack,
-                                        val.as_ref().clone(),
-                                        object_field,
-                                        l_left_operand,
-                                        l_position,
-                                        right_operand,
-                                    ),
-
-                                    _ => {
-                                        return Err(Err {
-                                            message: t!(
-                                                "errors.eval_binary_expr_node_e2",
-                                                a = object.string()
-                                            ),
-                                            reason: ErrorReason::System,
-                                        });
-                                    }
-                                }
-                            }
-
-                            return update_field(
-                                ctx,
-                                stack,
-                                object,
-                                object_field,
-                                l_left_operand,
-                                l_position,
-                                right_operand,
-                            );
-                        } else {
-                            return Err(Err {
-                                message: t!(
-                                    "errors.eval_binary_expr_node_e3",
-                                    a = l_left_operand.string(),
-                                    b = left_operand.position().string()
-                                ),
-                                reason: ErrorReason::Runtime,
-                            });
-                        }
+                        // left operand is stack name for object
+                        let object = l_left_operand.as_ref().clone().eval(ctx, stack, false)?;
+                        // right operand is the field value
+                        let object_field = l_right_operand.string();
+                        let object = field_target(object, &object_field, l_position)?;
+
+                        let right_value = right_operand.as_ref().clone().eval(ctx, stack, false)?;
+                        return set_field(stack, l_left_operand, object, object_field, right_value);
                     }
 
-                    _ => {
-                        let mut left_operand = left_operand.as_ref().clone();
+                    Node::BinaryExpression {
+                        left_operand: l_left_operand,
+                        ..
+                    } => {
                         return Err(Err {
                             message: t!(
                                 "errors.eval_binary_expr_node_e3",
-                                a = left_operand.eval(&UD, stack, false)?.string(),
+                                a = l_left_operand.string(),
                                 b = left_operand.position().string()
                             ),
                             reason: ErrorReason::Runtime,
+                            span: Some(Span::at(left_operand.position())),
+                            trace: None,
                         });
                     }
+
+                    _ => {
+                        let mut left_operand = left_operand.as_ref().clone();
+                        let left_value = left_operand.eval(&UD, stack, false)?;
+                        return Err(assign_e(&left_value, &left_operand));
+                    }
                 }
             }
 
             Kind::AccessorOp => {
                 // left operand is stack name for object; right operand is the value
                 let object = left_operand.as_ref().clone().eval(ctx, stack, false)?;
-                let object_field = right_operand.string();
-
-                fn get_field(
-                    object: Value,
-                    object_field: String,
-                    left_operand: &Box<Node>,
-                ) -> Result<Value, Err> {
-                    match &object {
-                        Value::Object { name, body } => match body.contains_key(&object_field) {
-                            true => {
-                                let (_, val) =
-                                    body.get(&object_field).expect("check done, value exists");
-                                return Ok(val.clone());
-                            }
-                            false => {
-                                return Err(Err {
-                                    message: t!(
-                                        "errors.eval_binary_expr_node_e1",
-                                        a = name,
-                                        b = object.string(),
-                                        c = left_operand.position().string()
-                                    ),
-                                    reason: ErrorReason::Runtime,
-                                });
-                            }
-                        },
+                return field_value(object, &right_operand.string(), left_operand);
+            }
 
-                        Value::Assignment(val) => {
-                            get_field(val.as_ref().clone(), object_field, left_operand)
-                        }
+            _ => {
+                let mut l = left_operand.as_ref().clone();
+                let left_value = l.eval(ctx, stack, false)?;
+                let mut r = right_operand.as_ref().clone();
+                let right_value = r.eval(ctx, stack, false)?;
+                return binary_value(node, left_value, right_value);
+            }
+        }
+    }
+    return Err(Err {
+        message: t!(
+            "errors.eval_binary_expr_node_e18",
+            a = node.string(),
+            b = node.position().string()
+        ),
+        reason: ErrorReason::Assert,
+        span: Some(Span::at(node.position())),
+        trace: None,
+    });
+}
 
-                        _ => {
-                            return Err(Err {
-                                message: t!("errors.eval_binary_expr_node_e2", a = object.string()),
-                                reason: ErrorReason::System,
-                            });
-                        }
+/// Applies the arithmetic, logical or comparison operator of the binary expression to
+/// the values of its operands.
+pub(crate) fn binary_value(
+    node: &Node,
+    left_value: Value,
+    right_value: Value,
+) -> Result<Value, Err> {
+    if let Node::BinaryExpression {
+        operator,
+        left_operand,
+        right_operand,
+        position,
+    } = node
+    {
+        match operator {
+            Kind::AddOp => match left_value {
+                Value::Number(left_num) => {
+                    if let Value::Number(right_num) = right_value {
+                        return Ok(Value::Number(left_num + right_num));
                     }
                 }
 
-                return get_field(object, object_field, left_operand);
-            }
-
-            Kind::AddOp => {
-                let (left_value, right_value) = left_right()?;
-                match left_value {
-                    Value::Number(left_num) => {
-                        if let Value::Number(right_num) = right_value {
-                            return Ok(Value::Number(left_num + right_num));
-                        }
+                Value::String(left_str) => {
+                    if let Value::String(right_str) = right_value {
+                        return Ok(Value::String(format!("{}{}", left_str, right_str)));
                     }
+                }
 
-                    Value::String(left_str) => {
-                        if let Value::String(right_str) = right_value {
-                            return Ok(Value::String(format!("{}{}", left_str, right_str)));
-                        }
+                Value::Bool(left_bool) => {
+                    if let Value::Bool(right_bool) = right_value {
+                        return Ok(Value::Bool(left_bool || right_bool));
                     }
+                }
 
-                    Value::Bool(left_bool) => {
-                        if let Value::Bool(right_bool) = right_value {
-                            return Ok(Value::Bool(left_bool || right_bool));
+                Value::Array(t_i, mut arr_i) => {
+                    if let Value::Array(t_j, arr_j) = right_value {
+                        if t_i == t_j {
+                            arr_i.extend(arr_j);
+                            return Ok(Value::Array(t_i, arr_i));
                         }
                     }
+                }
 
-                    Value::Array(t_i, mut arr_i) => {
-                        if let Value::Array(t_j, arr_j) = right_value {
-                            if t_i == t_j {
-                                arr_i.extend(arr_j);
-                                return Ok(Value::Array(t_i, arr_i));
-                            }
-                        }
+                _ => {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_binary_expr_node_e4",
+                            a = left_value.string(),
+                            b = right_value.string(),
+                            c = position.string()
+                        ),
+                        reason: ErrorReason::Syntax,
+                        span: Some(Span::at(position)),
+                        trace: None,
+                    });
+                }
+            },
+
+            Kind::SubtractOp => match left_value {
+                Value::Number(left_num) => {
+                    if let Value::Number(right_num) = right_value {
+                        return Ok(Value::Number(left_num - right_num));
                     }
+                }
 
-                    _ => {
-                        return Err(Err {
-                            message: t!(
-                                "errors.eval_binary_expr_node_e4",
-                                a = left_value.string(),
-                                b = right_value.string(),
-                                c = position.string()
-                            ),
-                            reason: ErrorReason::Syntax,
-                        });
-                    }
+                _ => {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_binary_expr_node_e5",
+                            a = left_value.string(),
+                            b = right_value.string(),
+                            c = position.string()
+                        ),
+                        reason: ErrorReason::Syntax,
+                        span: Some(Span::at(position)),
+                        trace: None,
+                    });
                 }
-            }
-
-            Kind::SubtractOp => {
-                let (left_value, right_value) = left_right()?;
-                match left_value {
-                    Value::Number(left_num) => {
-                        if let Value::Number(right_num) = right_value {
-                            return Ok(Value::Number(left_num - right_num));
-                        }
-                    }
+            },
 
-                    _ => {
-                        return Err(Err {
-                            message: t!(
-                                "errors.eval_binary_expr_node_e5",
-                                a = left_value.string(),
-                                b = right_value.string(),
-                                c = position.string()
-                            ),
-                            reason: ErrorReason::Syntax,
-                        });
+            Kind::MultiplyOp => match left_value {
+                Value::Number(left_num) => {
+                    if let Value::Number(right_num) = right_value {
+                        return Ok(Value::Number(left_num * right_num));
                     }
                 }
-            }
-
-            Kind::MultiplyOp => {
-                let (left_value, right_value) = left_right()?;
-                match left_value {
-                    Value::Number(left_num) => {
-                        if let Value::Number(right_num) = right_value {
-                            return Ok(Value::Number(left_num * right_num));
-                        }
-                    }
 
-                    Value::Bool(left_bool) => {
-                        if let Value::Bool(right_bool) = right_value {
-                            return Ok(Value::Bool(left_bool && right_bool));
-                        }
+                Value::Bool(left_bool) => {
+                    if let Value::Bool(right_bool) = right_value {
+                        return Ok(Value::Bool(left_bool && right_bool));
                     }
+                }
 
-                    _ => {
-                        return Err(Err {
-                            message: t!(
-                                "errors.eval_binary_expr_node_e6",
-                                a = left_value.string(),
-                                b = right_value.string(),
-                                c = position.string()
-                            ),
-                            reason: ErrorReason::Syntax,
-                        });
-                    }
+                _ => {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_binary_expr_node_e6",
+                            a = left_value.string(),
+                            b = right_value.string(),
+                            c = position.string()
+                        ),
+                        reason: ErrorReason::Syntax,
+                        span: Some(Span::at(position)),
+                        trace: None,
+                    });
                 }
-            }
+            },
 
-            Kind::DivideOp => {
-                let (left_value, right_value) = left_right()?;
-                match left_value {
-                    Value::Number(left_num) => {
-                        if let Value::Number(right_num) = right_value {
-                            if right_num == 0f64 {
-                                return Err(Err {
-                                    message: t!(
-                                        "errors.eval_binary_expr_node_e7",
-                                        a = right_operand.string()
-                                    ),
-                                    reason: ErrorReason::Runtime,
-                                });
-                            }
-                            return Ok(Value::Number(left_num / right_num));
+            Kind::DivideOp => match left_value {
+                Value::Number(left_num) => {
+                    if let Value::Number(right_num) = right_value {
+                        if right_num == 0f64 {
+                            return Err(Err {
+                                message: t!(
+                                    "errors.eval_binary_expr_node_e7",
+                                    a = right_operand.position().string()
+                                ),
+                                reason: ErrorReason::Runtime,
+                                span: Some(Span::at(right_operand.position())),
+                                trace: None,
+                            });
                         }
+                        return Ok(Value::Number(left_num / right_num));
                     }
+                }
 
-                    _ => {
-                        return Err(Err {
-                            message: t!(
-                                "errors.eval_binary_expr_node_e8",
-                                a = left_value.string(),
-                                b = right_value.string(),
-                                c = position.string()
-                            ),
-                            reason: ErrorReason::Syntax,
-                        });
-                    }
+                _ => {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_binary_expr_node_e8",
+                            a = left_value.string(),
+                            b = right_value.string(),
+                            c = position.string()
+                        ),
+                        reason: ErrorReason::Syntax,
+                        span: Some(Span::at(position)),
+                        trace: None,
+                    });
                 }
-            }
+            },
 
-            Kind::ModulusOp => {
-                let (left_value, right_value) = left_right()?;
-                match left_value {
-                    Value::Number(left_num) => {
-                        if let Value::Number(right_num) = right_value {
-                            if right_num == 0f64 {
-                                return Err(Err {
-                                    message: t!(
-                                        "errors.eval_binary_expr_node_e9",
-                                        a = right_operand.position().string()
-                                    ),
-                                    reason: ErrorReason::Runtime,
-                                });
-                            }
-                            return Ok(Value::Number(left_num % right_num));
+            Kind::ModulusOp => match left_value {
+                Value::Number(left_num) => {
+                    if let Value::Number(right_num) = right_value {
+                        if right_num == 0f64 {
+                            return Err(Err {
+                                message: t!(
+                                    "errors.eval_binary_expr_node_e9",
+                                    a = right_operand.position().string()
+                                ),
+                                reason: ErrorReason::Runtime,
+                                span: Some(Span::at(right_operand.position())),
+                                trace: None,
+                            });
                         }
+                        return Ok(Value::Number(left_num % right_num));
                     }
+                }
 
-                    _ => {
-                        return Err(Err {
-                            message: t!(
-                                "errors.eval_binary_expr_node_e10",
-                                a = right_value.string(),
-                                b = left_operand.position().string()
-                            ),
-                            reason: ErrorReason::Syntax,
-                        });
-                    }
+                _ => {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_binary_expr_node_e10",
+                            a = right_value.string(),
+                            b = left_operand.position().string()
+                        ),
+                        reason: ErrorReason::Syntax,
+                        span: Some(Span::at(left_operand.position())),
+                        trace: None,
+                    });
                 }
-            }
+            },
 
             Kind::LogicalAndOp => {
-                let (left_value, right_value) = left_right()?;
                 match left_value {
                     // the LogicalAndOp will perform a bitwise and; `&`.
                     Value::Number(left_num) => {
@@ -1030,6 +1015,8 @@ fn eval_binary_expr_node(
                                 c = position.string()
                             ),
                             reason: ErrorReason::Runtime,
+                            span: Some(Span::at(position)),
+                            trace: None,
                         });
                     }
 
@@ -1048,13 +1035,14 @@ fn eval_binary_expr_node(
                                 c = position.string()
                             ),
                             reason: ErrorReason::Syntax,
+                            span: Some(Span::at(position)),
+                            trace: None,
                         });
                     }
                 }
             }
 
             Kind::LogicalOrOp => {
-                let (left_value, right_value) = left_right()?;
                 match left_value {
                     // the LogicalOrOp will perform a bitwise or; `|`.
                     Value::Number(left_num) => {
@@ -1076,6 +1064,8 @@ fn eval_binary_expr_node(
                                 c = position.string()
                             ),
                             reason: ErrorReason::Runtime,
+                            span: Some(Span::at(position)),
+                            trace: None,
                         });
                     }
 
@@ -1094,78 +1084,81 @@ fn eval_binary_expr_node(
                                 c = position.string()
                             ),
                             reason: ErrorReason::Syntax,
+                            span: Some(Span::at(position)),
+                            trace: None,
                         });
                     }
                 }
             }
 
-            Kind::GreaterThanOp => {
-                let (left_value, right_value) = left_right()?;
-                match left_value {
-                    Value::Number(left_num) => {
-                        if let Value::Number(right_num) = right_value {
-                            return Ok(Value::Bool(left_num > right_num));
-                        }
+            Kind::GreaterThanOp => match left_value {
+                Value::Number(left_num) => {
+                    if let Value::Number(right_num) = right_value {
+                        return Ok(Value::Bool(left_num > right_num));
                     }
+                }
 
-                    Value::String(left_str) => {
-                        if let Value::String(right_str) = right_value {
-                            return Ok(Value::Bool(left_str > right_str));
-                        }
+                Value::String(left_str) => {
+                    if let Value::String(right_str) = right_value {
+                        return Ok(Value::Bool(left_str > right_str));
                     }
+                }
 
-                    _ => {
-                        return Err(Err {
-                            message: t!(
-                                "errors.eval_binary_expr_node_e15",
-                                a = left_value.string(),
-                                b = right_value.string(),
-                                c = position.string()
-                            ),
-                            reason: ErrorReason::Runtime,
-                        });
-                    }
+                _ => {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_binary_expr_node_e15",
+                            a = left_value.string(),
+                            b = right_value.string(),
+                            c = position.string()
+                        ),
+                        reason: ErrorReason::Runtime,
+                        span: Some(Span::at(position)),
+                        trace: None,
+                    });
                 }
-            }
+            },
 
-            Kind::LessThanOp => {
-                let (left_value, right_value) = left_right()?;
-                match left_value {
-                    Value::Number(left_num) => {
-                        if let Value::Number(right_num) = right_value {
-                            return Ok(Value::Bool(left_num < right_num));
-                        }
+            Kind::LessThanOp => match left_value {
+                Value::Number(left_num) => {
+                    if let Value::Number(right_num) = right_value {
+                        return Ok(Value::Bool(left_num < right_num));
                     }
+                }
 
-                    Value::String(left_str) => {
-                        if let Value::String(right_str) = right_value {
-                            return Ok(Value::Bool(left_str < right_str));
-                        }
+                Value::String(left_str) => {
+                    if let Value::String(right_str) = right_value {
+                        return Ok(Value::Bool(left_str < right_str));
                     }
+                }
 
-                    _ => {
-                        return Err(Err {
-                            message: t!(
-                                "errors.eval_binary_expr_node_e15",
-                                a = left_value.string(),
-                                b = right_value.string(),
-                                c = position.string()
-                            ),
-                            reason: ErrorReason::Runtime,
-                        });
-                    }
+                _ => {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_binary_expr_node_e15",
+                            a = left_value.string(),
+                            b = right_value.string(),
+                            c = position.string()
+                        ),
+                        reason: ErrorReason::Runtime,
+                        span: Some(Span::at(position)),
+                        trace: None,
+                    });
                 }
-            }
+            },
 
             Kind::EqualOp => {
-                let (left_value, right_value) = left_right()?;
                 return Ok(Value::Bool(left_value.equals(right_value)));
             }
 
+            Kind::EllipsisOp => return range_value(node, left_value, right_value),
+
             _ => {
                 return Err(Err {
                     reason: ErrorReason::Assert,
+                    span: None,
                     message: t!("errors.eval_binary_expr_node_e16", a = operator.string()),
+                    trace: None,
                 })
             }
         }
@@ -1179,6 +1172,8 @@ fn eval_binary_expr_node(
                 d = node.position().string()
             ),
             reason: ErrorReason::Runtime,
+            span: Some(Span::at(node.position())),
+            trace: None,
         });
     }
     return Err(Err {
@@ -1188,11 +1183,13 @@ fn eval_binary_expr_node(
             b = node.position().string()
         ),
         reason: ErrorReason::Assert,
+        span: Some(Span::at(node.position())),
+        trace: None,
     });
 }
 
 // Calls into a Speak callback function synchronously.
-fn eval_speak_function(
+pub(crate) fn eval_speak_function(
     ctx: &CallerCtx,
     stack: &mut StackFrame,
     fn_value: &Value,
@@ -1201,162 +1198,747 @@ fn eval_speak_function(
 ) -> Result<Value, Err> {
     match fn_value {
         Value::Function(func) => {
-            match &func.defn {
-                Node::FunctionLiteral { sign, .. } => {
-                    let mut arg_vtable = HashMap::new();
-                    for (i, (arg_ident, arg_type)) in sign.1.iter().enumerate() {
-                        if i < args.len() {
-                            // assert the arg value types match
-                            let want_arg_type = args[i].value_type().string();
-                            if want_arg_type != arg_type.string() && want_arg_type != "[]()" {
-                                return Err(Err {
-                                    message: t!(
-                                        "errors.eval_speak_function_e1",
-                                        a = arg_type.string(),
-                                        b = want_arg_type,
-                                        c = i + 1,
-                                        d = fn_value.string()
-                                    ),
-                                    reason: ErrorReason::Runtime,
-                                });
-                            }
+            let mut return_thunk = Value::FunctionCallThunk {
+                vt: bind_arguments(func, fn_value, args)?,
+                func: func.clone(),
+                at: None,
+            };
 
-                            if let Node::Identifier { value, .. } = arg_ident {
-                                arg_vtable.insert(value.clone(), args[i].clone());
-                            } else {
-                                return Err(Err {
-                                    message: t!(
-                                        "errors.eval_speak_function_e2",
-                                        a = arg_ident.string()
-                                    ),
-                                    reason: ErrorReason::Assert,
-                                });
-                            }
+            if allow_thunk {
+                return Ok(return_thunk);
+            }
+
+            unwrap_thunk(ctx, &mut return_thunk)
+        }
+
+        // stack is used in the mod function only to load
+        Value::NativeFunction(func) => func.1(stack, &Io::current(), args),
+
+        _ => Err(not_callable_e(fn_value)),
+    }
+}
+
+// Expands out a recursive structure of thunks into a flat for loop control structure.
+// A call in the tail position of the body takes over the running call when its value is
+// of the same return type, as tail calls do in the VM.
+fn unwrap_thunk(ctx: &CallerCtx, thunk: &mut Value) -> Result<Value, Err> {
+    // the calls merged into the running one, which errors leave out of the trace, and
+    // where the last of them was called
+    let mut elided = 0;
+    let mut tail_at = None::<Position>;
+    'UNWRAP: loop {
+        // the body runs on the stack frames the function was defined in
+        let (defn, mut stack) = match thunk {
+            Value::FunctionCallThunk { func, vt, .. } => {
+                let mut stack = func.env.clone();
+                stack.push_frame(vt.clone());
+                (Rc::clone(&func.defn), stack)
+            }
+            _ => {
+                return Err(Err {
+                    message: t!("errors.unwrap_thunk_e3", a = thunk.string()),
+                    reason: ErrorReason::Assert,
+                    span: None,
+                    trace: None,
+                });
+            }
+        };
+
+        match defn.as_ref() {
+            Node::FunctionLiteral { sign, body, .. } => {
+                let ret = sign.2.string();
+                let traced = |err: Err| match &tail_at {
+                    Some(at) => err.or_at(at).called(sign.0.string(), elided).called_at(at),
+                    None => err.called(sign.0.string(), elided),
+                };
+                for (i, stmt) in body.iter().enumerate() {
+                    let last = i + 1 == body.len();
+                    let mut val = stmt.clone().eval(ctx, &mut stack, last).map_err(traced)?;
+                    if let Value::FunctionCallThunk { func, at, .. } = &val {
+                        if return_type(func).as_ref() == Some(&ret) {
+                            tail_at = at.clone();
+                            *thunk = val;
+                            elided += 1;
+                            continue 'UNWRAP;
                         }
-                    }
 
-                    let mut return_thunk = Value::FunctionCallThunk {
-                        vt: VTable(arg_vtable),
-                        func: func.clone(),
-                    };
+                        // a call of another return type is not merged, but runs as any other
+                        let at = at.clone();
+                        val = unwrap_thunk(ctx, &mut val)
+                            .map_err(|err| match &at {
+                                Some(at) => err.or_at(at).called_at(at),
+                                None => err,
+                            })
+                            .map_err(traced)?;
+                    }
 
-                    if allow_thunk {
-                        return Ok(return_thunk);
+                    // if there's a next evaluation, assignment does not count
+                    if let Value::Assignment(_) = val {
+                        if i + 1 < body.len() {
+                            continue;
+                        }
                     }
 
-                    // assert that the return value is what was in the function signature
-                    let res = unwrap_thunk(ctx, stack, &mut return_thunk)?;
-                    match sign.2.as_ref() {
-                        Node::Identifier { .. } => Ok(res),
-                        _ => Err(Err {
-                            message: t!("errors.eval_speak_function_e3", a = sign.2.string()),
-                            reason: ErrorReason::Assert,
-                        }),
+                    // if the return type is that of the signature, return
+                    if returns(&val, &ret) {
+                        return Ok(val);
                     }
                 }
+                return Err(traced(no_return_e(&ret)));
+            }
+            _ => {
+                return Err(Err {
+                    message: t!("errors.unwrap_thunk_e2", a = defn.string()),
+                    reason: ErrorReason::Assert,
+                    span: None,
+                    trace: None,
+                });
+            }
+        }
+    }
+}
+
+/// The return type of the Speak function, as written in its signature.
+fn return_type(func: &Function) -> Option<String> {
+    match func.defn.as_ref() {
+        Node::FunctionLiteral { sign, .. } => Some(sign.2.string()),
+        _ => None,
+    }
+}
 
-                _ => Err(Err {
-                    message: "".to_string(),
-                    reason: ErrorReason::System,
+/// Binds the arguments of a call to the parameters of the Speak function, asserting
+/// that their types are those of the function's signature.
+pub(crate) fn bind_arguments(
+    func: &Function,
+    fn_value: &Value,
+    args: &[Value],
+) -> Result<VTable, Err> {
+    match func.defn.as_ref() {
+        Node::FunctionLiteral { sign, .. } => {
+            if !matches!(sign.2.as_ref(), Node::Identifier { .. }) {
+                return Err(Err {
+                    message: t!("errors.eval_speak_function_e3", a = sign.2.string()),
+                    reason: ErrorReason::Assert,
+                    span: None,
+                    trace: None,
+                });
+            }
+
+            let mut arg_vtable = HashMap::new();
+            for (i, (arg_ident, arg_type)) in sign.1.iter().enumerate().take(args.len()) {
+                // assert the arg value types match, objects by the name of their type
+                let want_arg_type = args[i].value_type().string();
+                if !returns(&args[i], &arg_type.string()) && want_arg_type != "[]()" {
+                    return Err(Err {
+                        message: t!(
+                            "errors.eval_speak_function_e1",
+                            a = arg_type.string(),
+                            b = want_arg_type,
+                            c = i + 1,
+                            d = fn_value.string()
+                        ),
+                        reason: ErrorReason::Runtime,
+                        span: None,
+                        trace: None,
+                    });
+                }
+
+                if let Node::Identifier { value, .. } = arg_ident {
+                    arg_vtable.insert(value.clone(), args[i].clone());
+                } else {
+                    return Err(Err {
+                        message: t!("errors.eval_speak_function_e2", a = arg_ident.string()),
+                        reason: ErrorReason::Assert,
+                        span: None,
+                        trace: None,
+                    });
+                }
+            }
+
+            Ok(VTable(arg_vtable))
+        }
+
+        _ => Err(Err {
+            message: "".to_string(),
+            reason: ErrorReason::System,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// Whether a statement of a function body is the function's return value, by having
+/// the return type of the function's signature.
+pub(crate) fn returns(val: &Value, ret: &str) -> bool {
+    match (val, val.value_type()) {
+        (_, Type::Object(obj)) => obj == ret,
+        // an empty array is of any array type
+        (Value::Array(Type::Empty, arr), _) if arr.is_empty() => true,
+        (_, t) => t.string() == ret,
+    }
+}
+
+pub(crate) fn no_return_e(ret: &str) -> Err {
+    Err {
+        message: t!("errors.unwrap_thunk_e1", a = ret),
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    }
+}
+
+pub(crate) fn not_callable_e(fn_value: &Value) -> Err {
+    Err {
+        message: t!(
+            "errors.eval_speak_function_e4",
+            a = fn_value.string(),
+            b = fn_value.value_type().string()
+        ),
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    }
+}
+
+/// The error of a node passed to a function that evaluates nodes of another kind.
+fn unexpected_node_e(node: &Node) -> Err {
+    Err {
+        message: t!(
+            "errors.eval_e11",
+            a = node.string(),
+            b = node.position().string()
+        ),
+        reason: ErrorReason::Assert,
+        span: Some(Span::at(node.position())),
+        trace: None,
+    }
+}
+
+pub(crate) fn outside_loop_e(node: &Node) -> Err {
+    Err {
+        message: t!(
+            "errors.eval_e8",
+            a = node.string(),
+            b = node.position().string()
+        ),
+        reason: ErrorReason::Runtime,
+        span: Some(Span::at(node.position())),
+        trace: None,
+    }
+}
+
+/// Gets the value bound to the identifier.
+pub(crate) fn identifier_value(node: &Node, stack: &StackFrame) -> Result<Value, Err> {
+    if let Node::Identifier { value, position } = node {
+        if let Some(val) = stack.get(value) {
+            return Ok(val.clone());
+        }
+        return Err(Err {
+            message: t!("errors.eval_e2", a = value, b = position.string()),
+            reason: ErrorReason::System,
+            span: Some(Span::at(position)),
+            trace: None,
+        });
+    }
+
+    Err(Err {
+        message: t!(
+            "errors.eval_e2",
+            a = node.string(),
+            b = node.position().string()
+        ),
+        reason: ErrorReason::Assert,
+        span: Some(Span::at(node.position())),
+        trace: None,
+    })
+}
+
+/// Gets the value bound to the member of a loaded module.
+pub(crate) fn member_value(node: &Node, stack: &StackFrame) -> Result<Value, Err> {
+    if let Node::ModuleAccess {
+        module,
+        member,
+        position,
+    } = node
+    {
+        return match stack.get(module) {
+            Some(Value::Module { vt, .. }) => match vt.0.get(member) {
+                Some(val) => Ok(val.clone()),
+                None => Err(Err {
+                    message: t!(
+                        "errors.eval_e10",
+                        a = module,
+                        b = member,
+                        c = position.string()
+                    ),
+                    reason: ErrorReason::Runtime,
+                    span: Some(Span::at(position)),
+                    trace: None,
                 }),
+            },
+            Some(_) => Err(Err {
+                message: t!("errors.eval_e9", a = module, b = position.string()),
+                reason: ErrorReason::Runtime,
+                span: Some(Span::at(position)),
+                trace: None,
+            }),
+            None => Err(Err {
+                message: t!("errors.eval_e2", a = module, b = position.string()),
+                reason: ErrorReason::Runtime,
+                span: Some(Span::at(position)),
+                trace: None,
+            }),
+        };
+    }
+
+    Err(Err {
+        message: t!(
+            "errors.eval_e2",
+            a = node.string(),
+            b = node.position().string()
+        ),
+        reason: ErrorReason::Assert,
+        span: Some(Span::at(node.position())),
+        trace: None,
+    })
+}
+
+/// The error of negating an identifier, whose value cannot be negated in place.
+pub(crate) fn negate_identifier_e(operand: &Node, position: &Position, stack: &StackFrame) -> Err {
+    match stack.get(&operand.string()) {
+        Some(_) => Err {
+            message: t!(
+                "errors.eval_e3",
+                a = operand.string(),
+                b = position.string()
+            ),
+            reason: ErrorReason::Runtime,
+            span: Some(Span::at(position)),
+            trace: None,
+        },
+        None => Err {
+            message: t!(
+                "errors.eval_e2",
+                a = operand.string(),
+                b = operand.position().string()
+            ),
+            reason: ErrorReason::System,
+            span: Some(Span::at(operand.position())),
+            trace: None,
+        },
+    }
+}
+
+/// Makes an array of the values of an array literal, which must all be of the type of
+/// the first value.
+pub(crate) fn array_value(node: &Node, values: Vec<Value>) -> Result<Value, Err> {
+    let value_type = match values.first() {
+        Some(val) => val.value_type(),
+        None => Type::Empty,
+    };
+
+    if let Node::ArrayLiteral { value, .. } = node {
+        for (val, node) in values.iter().zip(value) {
+            if val.value_type() != value_type {
+                return Err(Err {
+                    message: t!(
+                        "errors.eval_e1",
+                        a = value_type.string(),
+                        b = val.value_type().string(),
+                        c = node.position().string()
+                    ),
+                    reason: ErrorReason::Runtime,
+                    span: Some(Span::at(node.position())),
+                    trace: None,
+                });
             }
         }
+    }
 
-        // stack is used in the mod function only to load
-        Value::NativeFunction(func) => func.1(stack, args),
+    Ok(Value::Array(value_type, values))
+}
 
+/// Asserts that the value of the operand of an indexing or slicing operation is an array.
+pub(crate) fn array_target(val: Value, operand: &Node) -> Result<(Type, Vec<Value>), Err> {
+    match val {
+        Value::Array(t, vals) => Ok((t, vals)),
         _ => Err(Err {
             message: t!(
-                "errors.eval_speak_function_e4",
-                a = fn_value.string(),
-                b = fn_value.value_type().string()
+                "errors.eval_e5",
+                a = operand.string(),
+                b = operand.position().string()
             ),
             reason: ErrorReason::Runtime,
+            span: Some(Span::at(operand.position())),
+            trace: None,
         }),
     }
 }
 
-// Expands out a recursive structure of thunks into a flat for loop control structure
-fn unwrap_thunk(ctx: &CallerCtx, stack: &mut StackFrame, thunk: &mut Value) -> Result<Value, Err> {
-    let mut is_thunk = true;
-    let mut stacks_added = 0;
-    'UNWRAP: while is_thunk {
-        match thunk {
-            Value::FunctionCallThunk { func, vt, .. } => {
-                stack.push_frame(vt.clone());
-                stacks_added += 1;
-
-                let defn = &mut func.defn;
-                match defn {
-                    Node::FunctionLiteral { sign, body, .. } => {
-                        let mut val: Value;
-                        for (i, stmt) in body.iter().enumerate() {
-                            val = stmt.clone().eval(ctx, stack, false)?;
-                            match val {
-                                Value::FunctionCallThunk { .. } => {
-                                    is_thunk = true;
-                                    continue 'UNWRAP;
-                                }
+/// Asserts that the value of the operand of an indexing or slicing operation is an array
+/// or a string.
+pub(crate) fn indexable(val: Value, operand: &Node) -> Result<Value, Err> {
+    match val {
+        Value::Array(..) | Value::String(_) => Ok(val),
+        _ => Err(Err {
+            message: t!(
+                "errors.indexable_e",
+                a = operand.string(),
+                b = operand.position().string()
+            ),
+            reason: ErrorReason::Runtime,
+            span: Some(Span::at(operand.position())),
+            trace: None,
+        }),
+    }
+}
 
-                                _ => {
-                                    // if there's a next evaluation, assignment does not count
-                                    if let Value::Assignment(_) = val {
-                                        if i + 1 < body.len() {
-                                            continue;
-                                        }
-                                    }
-
-                                    // if the return type is that of the signature, return
-                                    if match val.value_type() {
-                                        Type::Object(obj) => obj,
-                                        Type::Array(..) => {
-                                            let v = val.value_type().string();
-                                            if let Value::Array(t, arr) = &val {
-                                                if arr.is_empty() && t == &Type::Empty {
-                                                    sign.2.string()
-                                                } else {
-                                                    v
-                                                }
-                                            } else {
-                                                v
-                                            }
-                                        }
-                                        _ => val.value_type().string(),
-                                    } == sign.2.string()
-                                    {
-                                        // pop stacks that were added, to free memory
-                                        for _ in 1..=stacks_added {
-                                            stack.pop_frame()?;
-                                        }
-
-                                        return Ok(val);
-                                    }
-                                }
-                            }
-                        }
-                        return Err(Err {
-                            message: t!("errors.unwrap_thunk_e1", a = sign.2.string()),
-                            reason: ErrorReason::Runtime,
-                        });
-                    }
-                    _ => {
-                        return Err(Err {
-                            message: t!("errors.unwrap_thunk_e2", a = defn.string()),
-                            reason: ErrorReason::Assert,
-                        });
-                    }
+/// The characters of the string, its grapheme clusters, as one-character strings.
+pub(crate) fn characters(s: &str) -> Vec<Value> {
+    s.graphemes(true)
+        .map(|c| Value::String(c.to_string()))
+        .collect()
+}
+
+/// The item of the array, or the character of the string, at the index.
+pub(crate) fn index_value(val: Value, idx: usize) -> Value {
+    let mut vals = match val {
+        Value::String(s) => characters(&s),
+        Value::Array(_, vals) => vals,
+        _ => return Value::Empty,
+    };
+    match idx >= vals.len() {
+        true => Value::Empty, // index out of bounds return ()
+        false => vals.swap_remove(idx),
+    }
+}
+
+/// Slices the array, or the characters of the string, from the start index, inclusive,
+/// to the end index, exclusive.
+pub(crate) fn slice_value(
+    val: Value,
+    start: Option<usize>,
+    end: Option<usize>,
+) -> Result<Value, Err> {
+    let (t, mut vals) = match val {
+        Value::String(s) => {
+            let chars = characters(&s);
+            return match slice_value(Value::Array(Type::String, chars), start, end)? {
+                Value::Array(_, chars) => {
+                    Ok(Value::String(chars.iter().map(Value::string).collect()))
                 }
+                val => Ok(val),
+            };
+        }
+        Value::Array(t, vals) => (t, vals),
+        _ => return Ok(Value::Empty),
+    };
+    match (start, end) {
+        // array[start..]
+        (Some(start), None) => Ok(Value::Array(t, vals.split_off(start))),
+        // array[..end]
+        (None, Some(end)) => {
+            _ = vals.split_off(end);
+            Ok(Value::Array(t, vals))
+        }
+        // array[start:end]
+        (Some(start), Some(end)) => {
+            _ = vals.split_off(end);
+            Ok(Value::Array(t, vals.split_off(start)))
+        }
+        (None, None) => Err(Err {
+            message: t!("errors.eval_e6"),
+            reason: ErrorReason::Assert,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// Binds the value to the name, updating it up the stack if it is bound already.
+pub(crate) fn assign(stack: &mut StackFrame, name: &str, val: Value) {
+    match stack.contains(name) {
+        true => _ = stack.up(name.to_string(), &val),
+        false => stack.set(name.to_string(), val),
+    }
+}
+
+/// Sets the item of the array at the index, extending the array if the index is out of
+/// bounds, and updates the array bound to the operand.
+pub(crate) fn set_index(
+    stack: &mut StackFrame,
+    operand: &Node,
+    (arr_type, mut vals): (Type, Vec<Value>),
+    idx: usize,
+    val: Value,
+) -> Result<Value, Err> {
+    // if index out of bounds, extend vec
+    if idx >= vals.len() {
+        vals.resize(idx + 1, Value::Empty);
+    }
+    vals[idx] = val.clone();
+
+    // update stack
+    stack.up(operand.string(), &Value::Array(arr_type, vals))?;
+    Ok(Value::Assignment(Box::new(val)))
+}
+
+/// The fields of an object, with their types and values.
+pub(crate) type Fields = HashMap<String, (Type, Value)>;
+
+/// Asserts that the value is an object with the field, returning its name and body.
+pub(crate) fn field_target(
+    object: Value,
+    object_field: &str,
+    position: &Position,
+) -> Result<(String, Fields), Err> {
+    match object {
+        Value::Object { name, body } => {
+            if body.contains_key(object_field) {
+                return Ok((name, body));
             }
-            _ => {
-                return Err(Err {
-                    message: t!("errors.unwrap_thunk_e3", a = thunk.string()),
-                    reason: ErrorReason::Assert,
-                });
+
+            let object = Value::Object {
+                name: name.clone(),
+                body,
+            };
+            Err(Err {
+                message: t!(
+                    "errors.eval_binary_expr_node_e1",
+                    a = name,
+                    b = object.string(),
+                    c = position.string()
+                ),
+                reason: ErrorReason::Runtime,
+                span: Some(Span::at(position)),
+                trace: None,
+            })
+        }
+
+        Value::Assignment(val) => field_target(*val, object_field, position),
+
+        _ => Err(Err {
+            message: t!("errors.eval_binary_expr_node_e2", a = object.string()),
+            reason: ErrorReason::System,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// Gets the field of the object value of the left operand of an accessor.
+pub(crate) fn field_value(
+    object: Value,
+    object_field: &str,
+    left_operand: &Node,
+) -> Result<Value, Err> {
+    let (_, mut body) = field_target(object, object_field, left_operand.position())?;
+    let (_, val) = body.remove(object_field).expect("check done, value exists");
+    Ok(val)
+}
+
+/// Sets the field of the object, and updates the object bound to the left operand of
+/// the accessor.
+pub(crate) fn set_field(
+    stack: &mut StackFrame,
+    l_left_operand: &Node,
+    (name, mut body): (String, Fields),
+    object_field: String,
+    val: Value,
+) -> Result<Value, Err> {
+    body.insert(object_field, (val.value_type(), val));
+
+    let obj = Value::Object { name, body };
+    stack.up(l_left_operand.string(), &obj)?;
+    Ok(obj)
+}
+
+/// The error of assigning to an operand which is not a name, an array item or a field.
+pub(crate) fn assign_e(left_value: &Value, left_operand: &Node) -> Err {
+    Err {
+        message: t!(
+            "errors.eval_binary_expr_node_e3",
+            a = left_value.string(),
+            b = left_operand.position().string()
+        ),
+        reason: ErrorReason::Runtime,
+        span: Some(Span::at(left_operand.position())),
+        trace: None,
+    }
+}
+
+/// Whether the if expression takes its true clause, or the while loop runs its body,
+/// for the value of its condition.
+pub(crate) fn condition_value(val: Value, node: &Node) -> Result<bool, Err> {
+    match (val, node) {
+        (Value::Bool(val), _) => Ok(val),
+        (Value::String(str), _) => Ok(str.is_empty()),
+        (_, Node::IfExpr { condition, .. } | Node::WhileExpr { condition, .. }) => Err(Err {
+            message: t!(
+                "errors.eval_if_expr_node_e1",
+                a = condition.string(),
+                b = node.position().string()
+            ),
+            reason: ErrorReason::Runtime,
+            span: Some(Span::at(node.position())),
+            trace: None,
+        }),
+        _ => Err(unexpected_node_e(node)),
+    }
+}
+
+/// Whether the value matches the pattern, binding the names of the pattern to the parts
+/// of the value they match. The bindings are incomplete if it does not match.
+pub(crate) fn match_pattern(
+    pattern: &Pattern,
+    val: &Value,
+    bindings: &mut HashMap<String, Value>,
+) -> bool {
+    match (pattern, val) {
+        (Pattern::Wildcard { .. }, _) => true,
+        (Pattern::Binding { name, .. }, val) => {
+            bindings.insert(name.clone(), val.clone());
+            true
+        }
+        (Pattern::Literal(node), val) => {
+            let literal = match node {
+                Node::NumberLiteral { value, .. } => Value::Number(*value),
+                Node::StringLiteral { value, .. } => Value::String(value.clone()),
+                Node::BoolLiteral { value, .. } => Value::Bool(*value),
+                _ => Value::Empty,
+            };
+            val.equals(literal)
+        }
+        (Pattern::Array { items, rest, .. }, Value::Array(t, vals)) => {
+            let fits = match rest {
+                Some(_) => vals.len() >= items.len(),
+                None => vals.len() == items.len(),
+            };
+            fits && items
+                .iter()
+                .zip(vals)
+                .all(|(item, val)| match_pattern(item, val, bindings))
+                && rest.as_ref().is_none_or(|rest| {
+                    let rest_val = Value::Array(t.clone(), vals[items.len()..].to_vec());
+                    match_pattern(rest, &rest_val, bindings)
+                })
+        }
+        (Pattern::Object { name, fields, .. }, Value::Object { name: n, body }) => {
+            name == n
+                && fields.iter().all(|(field, pattern)| {
+                    body.get(field)
+                        .is_some_and(|(_, val)| match_pattern(pattern, val, bindings))
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Whether the match arm is taken, for the value of its guard.
+pub(crate) fn guard_value(val: Value, arm: &MatchArm) -> Result<bool, Err> {
+    match (val, &arm.guard) {
+        (Value::Bool(val), _) => Ok(val),
+        (_, Some(guard)) => Err(Err {
+            message: t!(
+                "errors.eval_match_expr_node_e",
+                a = guard.string(),
+                b = guard.position().string()
+            ),
+            reason: ErrorReason::Runtime,
+            span: Some(Span::at(guard.position())),
+            trace: None,
+        }),
+        _ => Ok(true),
+    }
+}
+
+/// The items a for loop takes, one at a time: those of an array or the characters of a
+/// string, or the numbers of a range, counted as they are taken.
+pub(crate) enum LoopItems {
+    Values(vec::IntoIter<Value>),
+    Count { next: f64, end: f64 },
+}
+
+impl Iterator for LoopItems {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            LoopItems::Values(items) => items.next(),
+            LoopItems::Count { next, end } if *next < *end => {
+                let n = *next;
+                *next += 1.0;
+                Some(Value::Number(n))
             }
+            LoopItems::Count { .. } => None,
         }
     }
+}
 
-    unimplemented!("this code is never called")
+/// Gets the loop variable of the for expression, and the items it takes from the value
+/// of the iterable. The numbers of a range are counted as they are taken.
+pub(crate) fn loop_items(node: &Node, val: Value) -> Result<(String, LoopItems), Err> {
+    if let Node::ForExpr {
+        iterable, position, ..
+    } = node
+    {
+        let var = loop_variable(node)?;
+        return match val {
+            Value::String(val) => Ok((var, LoopItems::Values(characters(&val).into_iter()))),
+            Value::Array(_, items) => Ok((var, LoopItems::Values(items.into_iter()))),
+            Value::Range { start, end } => Ok((var, LoopItems::Count { next: start, end })),
+            _ => Err(Err {
+                message: t!(
+                    "errors.eval_for_expr_node_e1",
+                    a = iterable.string(),
+                    b = position.string()
+                ),
+                reason: ErrorReason::Runtime,
+                span: Some(Span::at(position)),
+                trace: None,
+            }),
+        };
+    }
+
+    Err(unexpected_node_e(node))
+}
+
+/// The range of the range expression, `start..end`, whose bounds must be numbers.
+fn range_value(node: &Node, start: Value, end: Value) -> Result<Value, Err> {
+    match (start, end) {
+        (Value::Number(start), Value::Number(end)) => Ok(Value::Range { start, end }),
+        (Value::Number(_), val) | (val, _) => Err(Err {
+            message: t!(
+                "errors.eval_range_e",
+                a = val.value_type().string(),
+                b = node.position().string()
+            ),
+            reason: ErrorReason::Runtime,
+            span: Some(Span::at(node.position())),
+            trace: None,
+        }),
+    }
+}
+
+fn loop_variable(node: &Node) -> Result<String, Err> {
+    match node {
+        Node::ForExpr {
+            variable, position, ..
+        } => match variable.as_ref() {
+            Node::Identifier { value, .. } => Ok(value.clone()),
+            _ => Err(Err {
+                message: t!(
+                    "errors.eval_e7",
+                    a = variable.string(),
+                    b = position.string()
+                ),
+                reason: ErrorReason::Runtime,
+                span: Some(Span::at(position)),
+                trace: None,
+            }),
+        },
+        _ => Err(unexpected_node_e(node)),
+    }
 }
 
 fn is_intable(num: &f64) -> bool {
@@ -1370,14 +1952,16 @@ fn to_usize(num: &f64, pos: &Position) -> Result<usize, Err> {
         false => Err(Err {
             message: t!("errors.to_usize_e", a = num, b = pos.string()),
             reason: ErrorReason::Runtime,
+            span: Some(Span::at(pos)),
+            trace: None,
         }),
     }
 }
 
-#[inline]
-fn to_number(node: &mut Node, stack: &mut StackFrame) -> Result<f64, Err> {
-    match node.eval(&UD, stack, false)? {
-        Value::Number(idx) => Ok(idx),
+/// Converts the value of an index expression to an index.
+pub(crate) fn to_index(val: Value, node: &Node) -> Result<usize, Err> {
+    match val {
+        Value::Number(idx) => to_usize(&idx, node.position()),
         _ => Err(Err {
             message: t!(
                 "errors.to_number_e",
@@ -1385,6 +1969,8 @@ fn to_number(node: &mut Node, stack: &mut StackFrame) -> Result<f64, Err> {
                 b = node.position().string()
             ),
             reason: ErrorReason::Runtime,
+            span: Some(Span::at(node.position())),
+            trace: None,
         }),
     }
 }
@@ -1392,10 +1978,10 @@ fn to_number(node: &mut Node, stack: &mut StackFrame) -> Result<f64, Err> {
 #[cfg(test)]
 mod test {
     use crate::{
-        eval::{value::Value, UD},
+        eval::{r#type::Type, value::Value, UD},
         lexer::Position,
         parser::Node,
-        runtime::{load_builtins, Context},
+        runtime::{load_builtins, Context, NativeFunction},
     };
 
     #[test]
@@ -1460,4 +2046,42 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn serde_round_trip() {
+        let person = Value::Object {
+            name: "Person".to_string(),
+            body: [
+                (
+                    "name".to_string(),
+                    (Type::String, Value::String("Osoro".to_string())),
+                ),
+                ("age".to_string(), (Type::Number, Value::Number(23.0))),
+            ]
+            .into(),
+        };
+        let people = Value::Array(Type::Object("Person".to_string()), vec![person]);
+
+        let json = serde_json::to_string(&people).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":{"Object":"Person"},"items":[{"name":"Person","body":{"age":23.0,"name":"Osoro"}}]}"#
+        );
+        let back = serde_json::from_str::<Value>(&json).unwrap();
+        assert_eq!(back.value_type(), people.value_type());
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+
+        // empty arrays keep their type
+        let empty = Value::Array(Type::Number, vec![]);
+        let back = serde_json::from_str::<Value>(&serde_json::to_string(&empty).unwrap());
+        assert_eq!(back.unwrap().value_type(), empty.value_type());
+
+        // functions have no serialized form
+        let func = NativeFunction::new("f".to_string(), |_, _, _| Ok(Value::Empty));
+        let err = serde_json::to_string(&Value::NativeFunction(func));
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "values of type function cannot be serialized"
+        );
+    }
 }
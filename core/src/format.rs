@@ -0,0 +1,325 @@
+//! The canonical formatting of Speak programs. Blocks are indented by four spaces per
+//! level, operators and commas are spaced consistently, and number literals are
+//! grouped by thousands. Comments, strings and the words of the program are kept as
+//! they are written.
+
+use super::{
+    error::Err,
+    lexer::{Kind, Tok},
+    runtime::Context,
+};
+
+const INDENT: &str = "    ";
+
+/// Formats the Speak program in `source`, written in the `speak` locale. Programs with
+/// syntax errors are not formatted, and the errors are returned instead.
+pub fn format_source(source: &str, speak: &str) -> Result<String, Vec<Err>> {
+    let (tokens, _, errs) = Context::new(&false).parse_source(speak, source);
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    let mut lines = Vec::<String>::new();
+    // comments and blank lines are indented like the code line that follows them
+    let mut pending = Vec::<Option<String>>::new();
+    // the columns of the enclosing blocks' headers, as written
+    let mut blocks = Vec::<usize>::new();
+    // the brackets left open by the previous lines, and the indent of their statement
+    let mut open = Vec::<Kind>::new();
+    let mut stmt_depth = 0;
+
+    let mut rest = &tokens[..];
+    for (i, line) in source.lines().enumerate() {
+        let chars = line.chars().collect::<Vec<_>>();
+        let comment_start = comment_start(&chars);
+        let comment = comment_start.map(|start| chars[start..].iter().collect::<String>());
+
+        let len = rest
+            .iter()
+            .take_while(|tok| tok.position.line == i + 1)
+            .count();
+        let (line_toks, next) = rest.split_at(len);
+        rest = next;
+
+        if line_toks.is_empty() {
+            pending.push(comment.map(|comment| comment.trim_end().to_string()));
+            continue;
+        }
+
+        let depth = match open.is_empty() {
+            true => {
+                let column = line_toks[0].position.column;
+                while blocks.last().is_some_and(|header| column <= *header) {
+                    blocks.pop();
+                }
+                stmt_depth = blocks.len();
+                if opens_block(line_toks) {
+                    blocks.push(column);
+                }
+                stmt_depth
+            }
+            // a line continuing the brackets of a statement
+            false => {
+                let closes = matches!(
+                    line_toks[0].kind,
+                    Kind::RightParen | Kind::RightBracket | Kind::RightBrace
+                );
+                stmt_depth + open.len() - closes as usize
+            }
+        };
+
+        let indent = INDENT.repeat(depth);
+        for entry in pending.drain(..) {
+            lines.push(match entry {
+                Some(comment) => format!("{indent}{comment}"),
+                None => String::new(),
+            });
+        }
+
+        let end = comment_start.unwrap_or(chars.len());
+        let mut code = format!(
+            "{indent}{}",
+            format_tokens(line_toks, &chars[..end], &mut open)
+        );
+        if let Some(comment) = comment {
+            code = format!("{code} {}", comment.trim_end());
+        }
+        lines.push(code);
+    }
+    for entry in pending {
+        lines.push(entry.unwrap_or_default());
+    }
+
+    // blank lines are collapsed, and dropped at the start and end of the program
+    let mut formatted = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() && (formatted.is_empty() || lines[i - 1].is_empty()) {
+            continue;
+        }
+        formatted.push_str(line);
+        formatted.push('\n');
+    }
+    while formatted.ends_with("\n\n") {
+        formatted.pop();
+    }
+
+    Ok(formatted)
+}
+
+/// Whether the statement on the line opens an indented block, its body.
+fn opens_block(line_toks: &[Tok]) -> bool {
+    match line_toks {
+        [first, second, ..]
+            if matches!(first.kind, Kind::Identifier | Kind::EmptyIdentifier)
+                && second.kind == Kind::Colon =>
+        {
+            true
+        }
+        [first, ..] if matches!(first.kind, Kind::For | Kind::While) => true,
+        // the arms of a match expression
+        _ if line_toks.iter().any(|tok| tok.kind == Kind::Match) => true,
+        // a clause of an if expression, whose block is not on the line of its `?` or `!`
+        [.., last] if matches!(last.kind, Kind::QuestionMark | Kind::Bang) => true,
+        // an anonymous function, whose body is not on the line of its signature
+        [.., arrow, _] => arrow.kind == Kind::FunctionArrow,
+        _ => false,
+    }
+}
+
+/// Joins the tokens of a line with canonical spacing, keeping the text of each token as
+/// written in the line, except for number literals which are normalised.
+fn format_tokens(line_toks: &[Tok], chars: &[char], open: &mut Vec<Kind>) -> String {
+    let mut code = String::new();
+    let mut prev: Option<(&Tok, bool)> = None;
+
+    for (j, tok) in line_toks.iter().enumerate() {
+        let start = tok.position.column - 1;
+        let end = line_toks
+            .get(j + 1)
+            .map_or(chars.len(), |next| next.position.column - 1);
+        let text = chars[start..end].iter().collect::<String>();
+        let text = match tok.kind {
+            Kind::NumberLiteral => format_number(text.trim_end()),
+            _ => text.trim_end().to_string(),
+        };
+
+        let unary = tok.kind == Kind::SubtractOp
+            && prev.is_none_or(|(prev, prev_unary)| prev_unary || is_operand_start(&prev.kind));
+        if let Some((prev, prev_unary)) = prev {
+            if spaced(prev, prev_unary, tok, open.last()) {
+                code.push(' ');
+            }
+        }
+        code.push_str(&text);
+
+        match tok.kind {
+            Kind::LeftParen | Kind::LeftBracket | Kind::LeftBrace => open.push(tok.kind.clone()),
+            Kind::RightParen | Kind::RightBracket | Kind::RightBrace => {
+                open.pop();
+            }
+            _ => {}
+        }
+        prev = Some((tok, unary));
+    }
+
+    code
+}
+
+/// Whether an operand is expected after the token, so that a following `-` negates it.
+fn is_operand_start(kind: &Kind) -> bool {
+    matches!(
+        kind,
+        Kind::AssignOp
+            | Kind::AddOp
+            | Kind::SubtractOp
+            | Kind::MultiplyOp
+            | Kind::DivideOp
+            | Kind::ModulusOp
+            | Kind::LogicalAndOp
+            | Kind::LogicalOrOp
+            | Kind::GreaterThanOp
+            | Kind::LessThanOp
+            | Kind::EqualOp
+            | Kind::NegationOp
+            | Kind::Separator
+            | Kind::Colon
+            | Kind::Bang
+            | Kind::QuestionMark
+            | Kind::FunctionArrow
+            | Kind::In
+            | Kind::LeftParen
+            | Kind::LeftBracket
+            | Kind::LeftBrace
+    )
+}
+
+/// Whether a space separates the two tokens.
+fn spaced(prev: &Tok, prev_unary: bool, tok: &Tok, open: Option<&Kind>) -> bool {
+    match (&prev.kind, &tok.kind) {
+        // `_ ` must be followed by a space to be lexed as the empty identifier, or start
+        // an anonymous function, `_:`
+        (Kind::EmptyIdentifier, Kind::Colon) => false,
+        (Kind::EmptyIdentifier, _) => true,
+        // the rest pattern of an array pattern, `[first, ..rest]`
+        (Kind::Separator, Kind::EllipsisOp) => true,
+        (
+            _,
+            Kind::RightParen
+            | Kind::RightBracket
+            | Kind::Separator
+            | Kind::AccessorOp
+            | Kind::ModuleAccessor
+            | Kind::EllipsisOp,
+        ) => false,
+        (
+            Kind::LeftParen
+            | Kind::LeftBracket
+            | Kind::AccessorOp
+            | Kind::ModuleAccessor
+            | Kind::EllipsisOp
+            | Kind::NegationOp,
+            _,
+        ) => false,
+        (Kind::SubtractOp, Kind::NumberLiteral | Kind::Identifier | Kind::LeftParen) => !prev_unary,
+        // indexing and slicing
+        (Kind::Identifier, Kind::LeftBracket) => false,
+        (_, Kind::Colon) => false,
+        (Kind::Colon, _) => open != Some(&Kind::LeftBracket),
+        _ => true,
+    }
+}
+
+/// Normalises a number literal: no leading zeros, and the digits of the integer part
+/// grouped by thousands with underscores.
+fn format_number(text: &str) -> String {
+    let text = text.replace('_', "");
+    let (sign, text) = match text.strip_prefix(['+', '-']) {
+        Some(rest) => (&text[..1], rest),
+        None => ("", text.as_str()),
+    };
+    let (int, frac) = text.split_once('.').unwrap_or((text, ""));
+
+    let int = int.trim_start_matches('0');
+    let int = if int.is_empty() { "0" } else { int };
+    let mut grouped = String::new();
+    for (i, digit) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push('_');
+        }
+        grouped.push(digit);
+    }
+
+    match frac.is_empty() {
+        true => format!("{sign}{grouped}"),
+        false => format!("{sign}{grouped}.{frac}"),
+    }
+}
+
+/// Returns the index of the char that starts a comment on the line, if any.
+fn comment_start(chars: &[char]) -> Option<usize> {
+    let mut in_string = false;
+    for (i, c) in chars.iter().enumerate() {
+        match c {
+            '"' => in_string = !in_string,
+            '/' if !in_string && chars.get(i + 1) == Some(&'/') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use regex::Regex;
+    use std::{env, fs};
+
+    /// The syntax tree of the program, without the positions of its nodes.
+    fn syntax_tree(source: &str, speak: &str) -> String {
+        let (_, nodes, errs) = Context::new(&false).parse_source(speak, source);
+        assert!(errs.is_empty(), "{:?}", errs);
+        Regex::new(r"position: Position \{[^}]*\}")
+            .expect("regex is valid")
+            .replace_all(&format!("{:?}", nodes), "")
+            .to_string()
+    }
+
+    #[test]
+    fn format_samples() {
+        let cwd = env::current_dir().expect("there must be a wd");
+        for entry in fs::read_dir(cwd.join("samples")).expect("the samples exist") {
+            let path = entry.expect("the sample exists").path();
+            let source = fs::read_to_string(&path).expect("the sample is readable");
+            let name = path.display().to_string();
+            let speak = if name.contains("_swa") || name.contains("habari") {
+                "sw"
+            } else {
+                "en"
+            };
+
+            let formatted = format_source(&source, speak).expect("the sample is valid");
+            assert_eq!(
+                format_source(&formatted, speak).expect("the sample is valid"),
+                formatted,
+                "{name} is formatted idempotently"
+            );
+            assert_eq!(
+                syntax_tree(&source, speak),
+                syntax_tree(&formatted, speak),
+                "{name} means the same formatted"
+            );
+        }
+    }
+
+    #[test]
+    fn format_messy_source() {
+        let source = "\n\n// squares\nsquare:n number->number\n  // the square\n  n*n\n\n\n\nmemo is [0,1,1000000]\nlist is [ 1 ,2 ]\nfor x in list\n      println \"{} // {}\" x (square x)   // trailing\n      y is memo[ x ]+1\nn is 0012.50\nx is Person {\n name ,\n  age n,\n }\n\n";
+        assert_eq!(
+            format_source(source, "en").expect("the program is valid"),
+            "// squares\nsquare: n number -> number\n    // the square\n    n * n\n\nmemo is [0, 1, 1_000_000]\nlist is [1, 2]\nfor x in list\n    println \"{} // {}\" x (square x) // trailing\n    y is memo[x] + 1\nn is 12.50\nx is Person {\n    name,\n    age n,\n}\n"
+        );
+
+        let errs = format_source("x is (1 + 2", "en").expect_err("the program is invalid");
+        assert_eq!(errs.len(), 1);
+    }
+}
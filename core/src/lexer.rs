@@ -1,19 +1,21 @@
 use super::{
-    error::{Err, ErrorReason},
+    error::{Err, ErrorReason, Span},
     eval::r#type::Type,
     log::log_debug,
 };
 use regex::Regex;
-use rust_i18n::t;
 use std::io::{BufRead, BufReader};
+use unicode_normalization::UnicodeNormalization;
 
 lazy_static! {
-    static ref IDENTIFIER_REGEX: Regex =
-        Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").expect("regex identifier pattern is valid");
+    pub(crate) static ref IDENTIFIER_REGEX: Regex =
+        Regex::new(r"^[\p{XID_Start}_]\p{XID_Continue}*$")
+            .expect("regex identifier pattern is valid");
     static ref NUMBER_REGEX: Regex =
-        Regex::new(r"^[+-]?\d+(_\d+)*(\.\d+)?$").expect("regex number pattern is valid");
+        Regex::new(r"^[+-]?[0-9]+(_[0-9]+)*(\.[0-9]+)?$").expect("regex number pattern is valid");
     static ref ARRAY_TYPE_REGEX: Regex =
-        Regex::new(r"^(\[\])+([a-z]+)$").expect("regex array pattern is valid");
+        Regex::new(r"^(\[\])+([\p{XID_Start}_]\p{XID_Continue}*)$")
+            .expect("regex array pattern is valid");
 }
 
 // Kind is the sum type of all possible types of tokens in a Speak program.
@@ -25,6 +27,8 @@ pub enum Kind {
     If,
     For,
     In,
+    Match,
+    While,
 
     ContinueLiteral,
     BreakLiteral,
@@ -77,6 +81,8 @@ impl Kind {
             Kind::If => t!("literals.if"),
             Kind::For => t!("literals.for"),
             Kind::In => t!("literals.in"),
+            Kind::Match => t!("literals.match"),
+            Kind::While => t!("literals.while"),
             Kind::ContinueLiteral => t!("literals.continue"),
             Kind::BreakLiteral => t!("literals.break"),
 
@@ -175,8 +181,8 @@ pub fn tokenize(
     tokens: &mut Vec<Tok>,
     debug_lexer: bool,
 ) -> Result<(), Err> {
-    // helper calculate column fn
-    let col_fn = |col, len| if len == 1 { col } else { col - (len - 1) };
+    // helper calculate column fn, from the column of the entry's end and its length in chars
+    let col_fn = |col: usize, len: usize| col + 1 - len;
 
     // read a complete line while parsing
     let mut line = 1;
@@ -221,7 +227,7 @@ pub fn tokenize(
                         tokens,
                         &debug_lexer,
                         line,
-                        col_fn(column, entry.len()),
+                        col_fn(column, entry.chars().count()),
                     )?;
                     entry.clear();
                     return Ok(true);
@@ -235,6 +241,10 @@ pub fn tokenize(
                 }
                 '"' => {
                     // start of a string literal, assert as literals
+                    let start = Position {
+                        line,
+                        column: column + 1,
+                    };
                     loop {
                         match buf_iter.next() {
                             Some((column, c)) => match c {
@@ -247,7 +257,7 @@ pub fn tokenize(
                                             num: None,
                                             position: Position {
                                                 line,
-                                                column: col_fn(column, entry.len()) - 1,
+                                                column: col_fn(column, entry.chars().count()) - 1,
                                             },
                                         },
                                         tokens,
@@ -266,6 +276,14 @@ pub fn tokenize(
                                 return Err(Err {
                                     message: t!("errors.tokenize_e"),
                                     reason: ErrorReason::Syntax,
+                                    span: Some(Span::new(
+                                        &start,
+                                        &Position {
+                                            line,
+                                            column: buf.chars().count(),
+                                        },
+                                    )),
+                                    trace: None,
                                 });
                             }
                         };
@@ -287,7 +305,8 @@ pub fn tokenize(
                 '_' => {
                     if entry.is_empty() {
                         if let Some((_, _c)) = buf_iter.peek() {
-                            if *_c == ' ' {
+                            // `_:` starts an anonymous function literal
+                            if *_c == ' ' || *_c == ':' {
                                 token_commit(Kind::EmptyIdentifier, tokens);
                                 continue;
                             }
@@ -320,7 +339,7 @@ pub fn tokenize(
                             tokens,
                             &debug_lexer,
                             line,
-                            col_fn(column, entry.len()),
+                            col_fn(column, entry.chars().count()),
                         )?;
 
                         entry.clear();
@@ -376,6 +395,8 @@ pub fn tokenize(
                             entry.push(c);
                             entry.push(*k);
                             buf_iter.next();
+                            last_line_column.0 = line;
+                            last_line_column.1 = column + 2;
                             continue;
                         }
                     }
@@ -454,7 +475,7 @@ pub fn tokenize(
                 tokens,
                 &debug_lexer,
                 last_line_column.0,
-                col_fn(last_line_column.1, entry.len()),
+                col_fn(last_line_column.1, entry.chars().count()),
             )?;
         }
 
@@ -483,6 +504,9 @@ fn commit_arbitrary(
         return Ok(());
     }
 
+    // equivalent spellings of identifiers and keywords are the same word
+    let entry = entry.nfc().collect::<String>();
+
     let commit_token = |kind, tokens| {
         commit(
             Tok {
@@ -504,6 +528,8 @@ fn commit_arbitrary(
 
         x if x == t!("types.string") => commit_token(Kind::TypeName(Type::String), tokens),
 
+        x if x == t!("types.function") => commit_token(Kind::TypeName(Type::Function), tokens),
+
         x if x == t!("literals.true") => commit_token(Kind::TrueLiteral, tokens),
 
         x if x == t!("literals.false") => commit_token(Kind::FalseLiteral, tokens),
@@ -514,6 +540,9 @@ fn commit_arbitrary(
 
         x if x == t!("literals.in") => commit_token(Kind::In, tokens),
 
+        x if x == t!("literals.match") => commit_token(Kind::Match, tokens),
+        x if x == t!("literals.while") => commit_token(Kind::While, tokens),
+
         x if x == t!("literals.continue") => commit_token(Kind::ContinueLiteral, tokens),
 
         x if x == t!("literals.break") => commit_token(Kind::BreakLiteral, tokens),
@@ -613,6 +642,14 @@ fn commit_arbitrary(
                 false => Err(Err {
                     message: t!("errors.commit_arbitrary_e", a = entry),
                     reason: ErrorReason::Syntax,
+                    span: Some(Span::new(
+                        &Position { line, column },
+                        &Position {
+                            line,
+                            column: column + entry.chars().count() - 1,
+                        },
+                    )),
+                    trace: None,
                 }),
             }
         }
@@ -686,7 +723,12 @@ mod test {
             commit_arbitrary("123abc".to_string(), &mut tokens, &false, 1, 1).unwrap_err(),
             Err {
                 reason: ErrorReason::Syntax,
-                message: "invalid identifier: (\"123abc\")".to_string()
+                span: Some(Span::new(
+                    &Position { line: 1, column: 1 },
+                    &Position { line: 1, column: 6 }
+                )),
+                message: "invalid identifier: (\"123abc\")".to_string(),
+                trace: None,
             }
         );
     }
@@ -934,6 +976,88 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_unicode_identifiers() {
+        let mut tokens = Vec::new();
+
+        // native-script identifiers, with columns counted in characters
+        let mut buf_reader = BufReader::new("ሰላም is \"ዓለም\", नमस्ते".as_bytes());
+        if let Err(err) = tokenize(&mut buf_reader, &mut tokens, true) {
+            panic!("error: {}", err.message);
+        }
+
+        assert_eq!(
+            tokens,
+            vec![
+                Tok {
+                    kind: Kind::Identifier,
+                    str: Some("ሰላም".to_string()),
+                    num: None,
+                    position: Position { line: 1, column: 1 }
+                },
+                Tok {
+                    kind: Kind::AssignOp,
+                    str: None,
+                    num: None,
+                    position: Position { line: 1, column: 5 }
+                },
+                Tok {
+                    kind: Kind::StringLiteral,
+                    str: Some("ዓለም".to_string()),
+                    num: None,
+                    position: Position { line: 1, column: 8 }
+                },
+                Tok {
+                    kind: Kind::Separator,
+                    str: None,
+                    num: None,
+                    position: Position {
+                        line: 1,
+                        column: 13
+                    }
+                },
+                Tok {
+                    kind: Kind::Identifier,
+                    str: Some("नमस्ते".to_string()),
+                    num: None,
+                    position: Position {
+                        line: 1,
+                        column: 15
+                    }
+                },
+            ]
+        );
+
+        // composed and decomposed spellings are the same identifier
+        tokens.clear();
+        buf_reader = BufReader::new("caf\u{e9} cafe\u{301}".as_bytes());
+        if let Err(err) = tokenize(&mut buf_reader, &mut tokens, true) {
+            panic!("error: {}", err.message);
+        }
+        assert_eq!(tokens[0].str, Some("caf\u{e9}".to_string()));
+        assert_eq!(tokens[1].str, tokens[0].str);
+        assert_eq!(tokens[1].position, Position { line: 1, column: 6 });
+
+        // empty string literals and non-ascii digits
+        tokens.clear();
+        buf_reader = BufReader::new("\"\" ४२".as_bytes());
+        assert_eq!(
+            tokenize(&mut buf_reader, &mut tokens, true)
+                .expect_err("non-ascii digits are not numbers nor identifiers")
+                .reason,
+            ErrorReason::Syntax
+        );
+        assert_eq!(
+            tokens[0],
+            Tok {
+                kind: Kind::StringLiteral,
+                str: Some("".to_string()),
+                num: None,
+                position: Position { line: 1, column: 1 }
+            }
+        );
+    }
+
     #[test]
     fn test_speak_files() {
         let cwd = env::current_dir().expect("there must be a wd");
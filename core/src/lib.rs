@@ -1,11 +1,19 @@
+#[macro_use]
+pub mod locale;
+
+pub mod bytecode;
+pub mod check;
+pub mod embed;
 pub mod error;
 pub mod eval;
+pub mod format;
 pub mod lexer;
 pub mod log;
 pub mod parser;
 pub mod runtime;
+mod stdlib;
+pub mod translate;
+pub mod vm;
 
 #[macro_use]
 extern crate lazy_static;
-
-rust_i18n::i18n!("locales");
@@ -0,0 +1,384 @@
+//! Locales map the keywords, builtin names and messages of the interpreter to a
+//! written human language. The locales in the `locales` directory are compiled into
+//! the interpreter; further locales can be loaded from YAML or TOML files at runtime.
+
+use crate::error::{Err, ErrorReason};
+use std::{cell::RefCell, collections::HashMap, fs, path::Path, sync::RwLock};
+use unicode_normalization::UnicodeNormalization;
+
+/// Translates the key to the text of the active locale, replacing any `%{name}`
+/// placeholders with the provided values.
+macro_rules! t {
+    ($key:expr) => {
+        $crate::locale::translate(&$crate::locale::locale(), $key)
+    };
+
+    ($key:expr, locale = $locale:expr) => {
+        $crate::locale::translate($locale, $key)
+    };
+
+    ($key:expr, locale = $locale:expr, $($var_name:ident = $var_val:expr),+ $(,)?) => {{
+        let mut message = $crate::locale::translate($locale, $key);
+        $(
+            message = message.replace(
+                concat!("%{", stringify!($var_name), "}"),
+                &format!("{}", $var_val),
+            );
+        )+
+        message
+    }};
+
+    ($key:expr, $($var_name:ident = $var_val:expr),+ $(,)?) => {
+        t!($key, locale = &$crate::locale::locale(), $($var_name = $var_val),+)
+    };
+}
+
+mod compiled {
+    rust_i18n::i18n!("locales");
+}
+
+/// The locale loaded locales fall back to for keys they do not define.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// The key prefixes every locale must define, these are the words of the language.
+const REQUIRED_PREFIXES: [&str; 3] = ["types.", "literals.", "builtins."];
+
+/// The keys that are lexed as keywords; these must be valid identifiers.
+const KEYWORD_TYPES: [&str; 4] = [
+    "types.number",
+    "types.bool",
+    "types.string",
+    "types.function",
+];
+
+thread_local! {
+    static LOCALE: RefCell<String> = RefCell::new(DEFAULT_LOCALE.to_string());
+}
+
+lazy_static! {
+    /// Locales loaded at runtime, keyed by the locale name.
+    static ref LOADED: RwLock<HashMap<String, HashMap<String, String>>> =
+        RwLock::new(HashMap::new());
+
+    /// The keys of the default locale, which is the reference for loaded locales.
+    static ref DEFAULT_KEYS: Vec<String> = {
+        let table = serde_yaml::from_str::<serde_yaml::Value>(include_str!("../locales/en.yml"))
+            .expect("the default locale is valid yaml");
+        let mut keys = Vec::new();
+        flatten_yaml("", &table, &mut |key, _| keys.push(key));
+        keys
+    };
+}
+
+/// Sets the locale used by the interpreter on the current thread.
+pub fn set_locale(locale: &str) {
+    LOCALE.with(|l| *l.borrow_mut() = locale.to_string());
+}
+
+/// Returns the locale used by the interpreter on the current thread.
+pub fn locale() -> String {
+    LOCALE.with(|l| l.borrow().clone())
+}
+
+/// Gets the text of the key in the provided locale. Locales loaded at runtime take
+/// precedence over the compiled locales, and fall back to the default locale.
+pub fn translate(locale: &str, key: &str) -> String {
+    if let Some(table) = LOADED.read().expect("lock is not poisoned").get(locale) {
+        return match table.get(key) {
+            Some(text) => text.clone(),
+            None => compiled::translate(DEFAULT_LOCALE, key),
+        };
+    }
+
+    compiled::translate(locale, key)
+}
+
+/// Returns whether the locale is compiled into the interpreter or has been loaded.
+pub fn is_available(locale: &str) -> bool {
+    compiled::available_locales().contains(&locale)
+        || LOADED
+            .read()
+            .expect("lock is not poisoned")
+            .contains_key(locale)
+}
+
+/// Returns the keys of the words that are lexed as keywords or builtin names.
+pub fn keyword_keys() -> Vec<&'static str> {
+    let mut keywords = DEFAULT_KEYS
+        .iter()
+        .filter(|key| {
+            KEYWORD_TYPES.contains(&key.as_str())
+                || key.starts_with("literals.")
+                || key.starts_with("builtins.")
+                || key.starts_with("math.")
+        })
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    keywords.sort();
+    keywords
+}
+
+/// Loads a locale definition file, with the same keys as `locales/en.yml`, from a YAML
+/// or TOML file. The locale is named after the file and its name is returned.
+pub fn load_locale(path: &str) -> Result<String, Err> {
+    let file = Path::new(path);
+    let name = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let format = file.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if !matches!(format, "yml" | "yaml" | "toml") {
+        return Err(Err {
+            message: t!("errors.load_locale_e1", a = path),
+            reason: ErrorReason::System,
+            span: None,
+            trace: None,
+        });
+    }
+
+    let data = fs::read_to_string(file).map_err(|err| Err {
+        message: t!("errors.exec_path_e", a = err),
+        reason: ErrorReason::System,
+        span: None,
+        trace: None,
+    })?;
+
+    let parse_e = |err: &dyn std::fmt::Display| Err {
+        message: t!("errors.load_locale_e2", a = path, b = err),
+        reason: ErrorReason::System,
+        span: None,
+        trace: None,
+    };
+
+    // words are normalized as the lexer normalizes identifiers and keywords
+    let mut table = HashMap::new();
+    if format == "toml" {
+        let value = data.parse::<toml::Value>().map_err(|e| parse_e(&e))?;
+        flatten_toml("", &value, &mut |key, text| {
+            table.insert(key, text.nfc().collect());
+        });
+    } else {
+        let value = serde_yaml::from_str::<serde_yaml::Value>(&data).map_err(|e| parse_e(&e))?;
+        flatten_yaml("", &value, &mut |key, text| {
+            table.insert(key, text.nfc().collect());
+        });
+    }
+
+    validate(&name, &table)?;
+
+    LOADED
+        .write()
+        .expect("lock is not poisoned")
+        .insert(name.clone(), table);
+    Ok(name)
+}
+
+/// Asserts that a locale defines all the words of the language, that the keywords are
+/// distinct identifiers, and that no type is named as a builtin or a literal is.
+fn validate(name: &str, table: &HashMap<String, String>) -> Result<(), Err> {
+    let missing = DEFAULT_KEYS
+        .iter()
+        .filter(|key| REQUIRED_PREFIXES.iter().any(|p| key.starts_with(p)))
+        .filter(|key| table.get(*key).is_none_or(|text| text.trim().is_empty()))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(Err {
+            message: t!("errors.load_locale_e3", a = name, b = missing.join(", ")),
+            reason: ErrorReason::System,
+            span: None,
+            trace: None,
+        });
+    }
+
+    let collision_e = |key: &str, word: &str, other: &str| Err {
+        message: t!(
+            "errors.load_locale_e5",
+            a = name,
+            b = key,
+            c = word,
+            d = other
+        ),
+        reason: ErrorReason::System,
+        span: None,
+        trace: None,
+    };
+
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for key in keyword_keys() {
+        let word = table[key].as_str();
+        if !crate::lexer::IDENTIFIER_REGEX.is_match(word) {
+            return Err(Err {
+                message: t!("errors.load_locale_e4", a = name, b = key, c = word),
+                reason: ErrorReason::System,
+                span: None,
+                trace: None,
+            });
+        }
+
+        if let Some(other) = seen.insert(word, key) {
+            return Err(collision_e(key, word, other));
+        }
+    }
+
+    // the types not lexed as keywords are written as names, like builtins
+    for key in DEFAULT_KEYS.iter().filter(|key| key.starts_with("types.")) {
+        let word = table[key].as_str();
+        match seen.get(word) {
+            Some(other) if other.starts_with("builtins.") || other.starts_with("literals.") => {
+                return Err(collision_e(key, word, other));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn flatten_yaml(prefix: &str, value: &serde_yaml::Value, f: &mut dyn FnMut(String, String)) {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            for (k, v) in map {
+                let key = match k {
+                    serde_yaml::Value::String(k) => k.clone(),
+                    serde_yaml::Value::Bool(k) => k.to_string(),
+                    serde_yaml::Value::Number(k) => k.to_string(),
+                    _ => continue,
+                };
+                let key = match prefix.is_empty() {
+                    true => key,
+                    false => format!("{prefix}.{key}"),
+                };
+                flatten_yaml(&key, v, f);
+            }
+        }
+        serde_yaml::Value::String(text) => f(prefix.to_string(), text.clone()),
+        serde_yaml::Value::Bool(text) => f(prefix.to_string(), text.to_string()),
+        serde_yaml::Value::Number(text) => f(prefix.to_string(), text.to_string()),
+        _ => {}
+    }
+}
+
+fn flatten_toml(prefix: &str, value: &toml::Value, f: &mut dyn FnMut(String, String)) {
+    match value {
+        toml::Value::Table(map) => {
+            for (k, v) in map {
+                let key = match prefix.is_empty() {
+                    true => k.clone(),
+                    false => format!("{prefix}.{k}"),
+                };
+                flatten_toml(&key, v, f);
+            }
+        }
+        toml::Value::String(text) => f(prefix.to_string(), text.clone()),
+        toml::Value::Boolean(text) => f(prefix.to_string(), text.to_string()),
+        toml::Value::Integer(text) => f(prefix.to_string(), text.to_string()),
+        toml::Value::Float(text) => f(prefix.to_string(), text.to_string()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{eval::value::Value, runtime::Context};
+    use std::{env, io::BufReader};
+
+    /// The default locale as a TOML document with the keywords in French.
+    fn french_toml() -> String {
+        let yaml = serde_yaml::from_str::<serde_yaml::Value>(include_str!("../locales/en.yml"))
+            .expect("the default locale is valid yaml");
+        let mut lines = Vec::new();
+        flatten_yaml("", &yaml, &mut |key, text| {
+            let text = match key.as_str() {
+                "types.number" => "nombre".to_string(),
+                "literals.if" => "si".to_string(),
+                "literals.is" => "est".to_string(),
+                "literals.true" => "vrai".to_string(),
+                "literals.false" => "faux".to_string(),
+                "builtins.sprint" => "chaine".to_string(),
+                _ => text,
+            };
+            lines.push(format!("\"{key}\" = {:?}", text));
+        });
+        lines.join("\n")
+    }
+
+    #[test]
+    fn load_locale_file() {
+        let dir = env::temp_dir().join("speak_load_locale_file");
+        fs::create_dir_all(&dir).expect("temp dir is writable");
+
+        let path = dir.join("fr.toml");
+        fs::write(&path, french_toml()).expect("temp dir is writable");
+        let name =
+            load_locale(path.to_str().expect("this path exists")).expect("the locale is complete");
+        assert_eq!(name, "fr");
+
+        let mut ctx_test = Context::new(&false);
+        let program = "double: n nombre -> nombre\n    n * 2\nx est double 21\nsi x = 42 ? chaine \"oui {}\" x ! \"non\"";
+        match ctx_test.exec("fr", BufReader::new(program.as_bytes())) {
+            Ok((val, _, _)) => assert!(val.equals(Value::String("oui 42".to_string()))),
+            Err(err) => panic!("{:?}", err),
+        }
+
+        // messages not defined by the locale fall back to the default locale
+        assert_eq!(translate("fr", "errors.const.syntax"), "Syntax error");
+        set_locale(DEFAULT_LOCALE);
+    }
+
+    #[test]
+    fn load_invalid_locale_file() {
+        let dir = env::temp_dir().join("speak_load_invalid_locale_file");
+        fs::create_dir_all(&dir).expect("temp dir is writable");
+
+        // missing keywords
+        let path = dir.join("incomplete.yml");
+        fs::write(&path, "literals:\n  if: si\n").expect("temp dir is writable");
+        let err = load_locale(path.to_str().expect("this path exists"))
+            .expect_err("the locale is incomplete");
+        assert_eq!(err.reason, ErrorReason::System);
+        assert!(err.message.contains("types.number"));
+
+        // keywords that collide
+        let path = dir.join("ambiguous.toml");
+        fs::write(&path, french_toml().replace("\"si\"", "\"est\"")).expect("temp dir is writable");
+        let err = load_locale(path.to_str().expect("this path exists"))
+            .expect_err("the locale is ambiguous");
+        assert!(err.message.contains("literals.if"));
+        assert!(err.message.contains("literals.is"));
+
+        // a type named as a builtin
+        let path = dir.join("ambiguous_type.toml");
+        let toml = french_toml().replace(
+            "\"types.range\" = \"interval\"",
+            "\"types.range\" = \"range\"",
+        );
+        fs::write(&path, toml).expect("temp dir is writable");
+        let err = load_locale(path.to_str().expect("this path exists"))
+            .expect_err("the locale is ambiguous");
+        assert!(err.message.contains("types.range"));
+        assert!(err.message.contains("builtins.range"));
+
+        // unsupported format
+        let err = load_locale("locale.json").expect_err("the format is unsupported");
+        assert_eq!(err.reason, ErrorReason::System);
+    }
+
+    #[test]
+    fn compiled_locales_are_valid() {
+        for (name, yaml) in [
+            ("en", include_str!("../locales/en.yml")),
+            ("sw", include_str!("../locales/sw.yml")),
+        ] {
+            let yaml =
+                serde_yaml::from_str::<serde_yaml::Value>(yaml).expect("the locale is valid yaml");
+            let mut table = HashMap::new();
+            flatten_yaml("", &yaml, &mut |key, text| _ = table.insert(key, text));
+            if let Err(err) = validate(name, &table) {
+                panic!("{}", err.message);
+            }
+        }
+    }
+}
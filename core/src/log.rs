@@ -1,12 +1,12 @@
-use crate::error::ErrorReason;
+use crate::error::Err;
 use std::io::{self, Write};
 
-static ANSI_RESET: &str = "[0;0m";
-static ANSI_BLUE: &str = "[34;22m";
-static ANSI_RED: &str = "[31;22m";
-static ANSI_BLUE_BOLD: &str = "[34;1m";
-static ANSI_GREEN_BOLD: &str = "[32;1m";
-static ANSI_RED_BOLD: &str = "[31;1m";
+static ANSI_RESET: &str = "[0;0m";
+static ANSI_BLUE: &str = "[34;22m";
+static ANSI_RED: &str = "[31;22m";
+static ANSI_BLUE_BOLD: &str = "[34;1m";
+static ANSI_GREEN_BOLD: &str = "[32;1m";
+static ANSI_RED_BOLD: &str = "[31;1m";
 
 pub fn log_debug(args: &str) {
     println!(
@@ -17,15 +17,54 @@ pub fn log_debug(args: &str) {
 }
 
 pub fn log_interactive(args: &str) {
-    print!("{}{}{}", ANSI_GREEN_BOLD, args, ANSI_RESET);
-    io::stdout().flush().unwrap()
+    write_interactive(&mut io::stdout(), args, true).unwrap()
 }
 
-pub fn log_safe_err(reason: &ErrorReason, args: &str) {
-    let err_str = reason.string();
-    eprintln!(
-        "{}{}: {}{}{}",
-        ANSI_RED_BOLD, err_str, ANSI_RED, args, ANSI_RESET
-    );
-    io::stderr().flush().unwrap()
+/// Logs the error to stderr, followed by an excerpt of the source it occurred in if
+/// it is known.
+pub fn log_safe_err(err: &Err) {
+    write_err(&mut io::stderr(), err, true).unwrap()
+}
+
+/// Writes the output of an interactive session, the values of a REPL, to `out`.
+pub fn write_interactive(out: &mut dyn Write, args: &str, colors: bool) -> io::Result<()> {
+    match colors {
+        true => write!(out, "{}{}{}", ANSI_GREEN_BOLD, args, ANSI_RESET)?,
+        false => write!(out, "{}", args)?,
+    }
+    out.flush()
+}
+
+/// Writes the error to `out`, followed by an excerpt of the source it occurred in if it
+/// is known, and the calls it was raised in.
+pub fn write_err(out: &mut dyn Write, err: &Err, colors: bool) -> io::Result<()> {
+    let err_str = err.reason.string();
+    let excerpt = err.span.as_ref().and_then(|span| span.excerpt());
+    match colors {
+        true => {
+            writeln!(
+                out,
+                "{}{}: {}{}{}",
+                ANSI_RED_BOLD, err_str, ANSI_RED, err.message, ANSI_RESET
+            )?;
+            if let Some(excerpt) = excerpt {
+                write!(out, "{}{}{}", ANSI_BLUE, excerpt, ANSI_RESET)?;
+            }
+        }
+        false => {
+            writeln!(out, "{}: {}", err_str, err.message)?;
+            if let Some(excerpt) = excerpt {
+                write!(out, "{}", excerpt)?;
+            }
+        }
+    }
+    if let Some(trace) = &err.trace {
+        writeln!(out, "{}", t!("misc.trace"))?;
+        for frame in &trace.frames {
+            for line in frame.string().lines() {
+                writeln!(out, "  {}", line)?;
+            }
+        }
+    }
+    out.flush()
 }
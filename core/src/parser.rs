@@ -1,11 +1,15 @@
 use super::{
-    error::{Err, ErrorReason},
+    error::{Err, ErrorReason, Span},
     eval::r#type::Type,
     lexer::{Kind, Position, Tok},
     log::log_debug,
 };
-use rust_i18n::t;
-use std::fmt::Debug;
+use std::{cell::RefCell, fmt::Debug};
+
+thread_local! {
+    /// The syntax errors recovered from while parsing in recovering mode, None otherwise.
+    static RECOVERED: RefCell<Option<Vec<Err>>> = const { RefCell::new(None) };
+}
 
 /// Node represents an abstract syntax tree (AST) node in a Speak program.
 #[derive(Debug, Clone, PartialEq)]
@@ -41,6 +45,11 @@ pub enum Node {
         value: String,
         position: Position,
     },
+    ModuleAccess {
+        module: String,
+        member: String,
+        position: Position,
+    },
     UnaryExpression {
         operator: Kind,
         operand: Box<Node>,
@@ -75,8 +84,9 @@ pub enum Node {
     },
     IfExpr {
         condition: Box<Node>,
-        on_true: Option<Box<Node>>,
-        on_false: Option<Box<Node>>,
+        /// The statements of the clauses, whose value is that of their last statement.
+        on_true: Option<Vec<Node>>,
+        on_false: Option<Vec<Node>>,
         position: Position,
     },
     ForExpr {
@@ -85,6 +95,16 @@ pub enum Node {
         body: Option<Vec<Node>>,
         position: Position,
     },
+    WhileExpr {
+        condition: Box<Node>,
+        body: Option<Vec<Node>>,
+        position: Position,
+    },
+    MatchExpr {
+        value: Box<Node>,
+        arms: Vec<MatchArm>,
+        position: Position,
+    },
     Continueliteral {
         position: Position,
     },
@@ -93,6 +113,77 @@ pub enum Node {
     },
 }
 
+/// MatchArm is an arm of a match expression, `pattern if guard ? body`, whose body is the
+/// value of the match if the pattern matches the value and the guard holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Node>,
+    pub body: Node,
+}
+
+/// Pattern is the shape of the values a match arm matches, binding names to their parts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_`, any value.
+    Wildcard { position: Position },
+    /// Any value, bound to the name.
+    Binding { name: String, position: Position },
+    /// A number, string, bool or empty literal, the values equal to it.
+    Literal(Node),
+    /// Arrays of an item for each pattern, or of at least as many items if there is a rest
+    /// pattern, `[first, ..rest]`, which matches the array of the items after them.
+    Array {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+        position: Position,
+    },
+    /// Objects of the name, with a field matching each pattern, `Person { name n }`. A
+    /// field without a pattern is bound to its own name.
+    Object {
+        name: String,
+        fields: Vec<(String, Pattern)>,
+        position: Position,
+    },
+}
+
+impl Pattern {
+    pub fn string(&self) -> String {
+        match self {
+            Pattern::Wildcard { .. } => "_".to_string(),
+            Pattern::Binding { name, .. } => name.clone(),
+            Pattern::Literal(node) => match node {
+                Node::StringLiteral { value, .. } => format!("{value:?}"),
+                node => node.string(),
+            },
+            Pattern::Array { items, rest, .. } => {
+                let mut parts = items.iter().map(Pattern::string).collect::<Vec<_>>();
+                if let Some(rest) = rest {
+                    parts.push(format!("..{}", rest.string()));
+                }
+                format!("[{}]", parts.join(", "))
+            }
+            Pattern::Object { name, fields, .. } => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, pattern)| format!("{field} {}", pattern.string()))
+                    .collect::<Vec<_>>();
+                format!("{name} {{ {} }}", fields.join(", "))
+            }
+        }
+    }
+
+    pub fn position(&self) -> &Position {
+        match self {
+            Pattern::Wildcard { position }
+            | Pattern::Binding { position, .. }
+            | Pattern::Array { position, .. }
+            | Pattern::Object { position, .. } => position,
+            Pattern::Literal(node) => node.position(),
+        }
+    }
+}
+
 impl Node {
     pub fn string(&self) -> String {
         match self {
@@ -131,6 +222,7 @@ impl Node {
             Node::EmptyLiteral { .. } => "()".to_string(),
             Node::EmptyIdentifier { .. } => "".to_string(),
             Node::Identifier { value, .. } => value.clone(),
+            Node::ModuleAccess { module, member, .. } => format!("{module}::{member}"),
             Node::UnaryExpression {
                 operator, operand, ..
             } => format!("Unary {} ({})", operator.string(), operand.string()),
@@ -212,11 +304,18 @@ impl Node {
                     position.string(),
                     condition.string()
                 );
+                let clause = |stmts: &Vec<Node>| {
+                    stmts
+                        .iter()
+                        .map(Node::string)
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                };
                 if let Some(true_clause) = &on_true {
-                    s.push_str(&format!("? ({})", true_clause.string()));
+                    s.push_str(&format!("? ({})", clause(true_clause)));
                 }
                 if let Some(false_clause) = &on_false {
-                    s.push_str(&format!("! ({})", false_clause.string()));
+                    s.push_str(&format!("! ({})", clause(false_clause)));
                 }
                 s
             }
@@ -234,6 +333,24 @@ impl Node {
                     iterable.string()
                 )
             }
+            Node::WhileExpr {
+                condition,
+                position,
+                ..
+            } => format!(
+                "{} ({}): ({})",
+                t!("literals.while"),
+                position.string(),
+                condition.string()
+            ),
+            Node::MatchExpr {
+                value, position, ..
+            } => format!(
+                "{} ({}): ({})",
+                t!("literals.match"),
+                position.string(),
+                value.string()
+            ),
             Node::Continueliteral { position } => {
                 return format!("{} ({})", t!("literals.continue"), position.string())
             }
@@ -253,6 +370,7 @@ impl Node {
             Node::EmptyLiteral { position } => position,
             Node::EmptyIdentifier { position } => position,
             Node::Identifier { position, .. } => position,
+            Node::ModuleAccess { position, .. } => position,
             Node::UnaryExpression { position, .. } => position,
             Node::BinaryExpression { position, .. } => position,
             Node::IndexingOp { position, .. } => position,
@@ -261,6 +379,8 @@ impl Node {
             Node::FunctionLiteral { position, .. } => position,
             Node::IfExpr { position, .. } => position,
             Node::ForExpr { position, .. } => position,
+            Node::WhileExpr { position, .. } => position,
+            Node::MatchExpr { position, .. } => position,
             Node::Continueliteral { position } => position,
             Node::BreakLiteral { position } => position,
         }
@@ -284,6 +404,83 @@ pub fn parse(tokens: &[Tok], nodes: &mut Vec<Node>, debug_parser: bool) -> Resul
     Ok(())
 }
 
+/// Parses the tokens like `parse`, but recovers from syntax errors, resuming at the next
+/// statement of the block the error occurred in. The nodes are the statements that
+/// parsed, and the syntax errors of all the statements that did not are returned.
+pub fn parse_recovering(tokens: &[Tok], nodes: &mut Vec<Node>, debug_parser: bool) -> Vec<Err> {
+    RECOVERED.with(|errs| *errs.borrow_mut() = Some(Vec::new()));
+
+    let (mut idx, length) = (0, tokens.len());
+    while idx < length {
+        match parse_expression(&tokens[idx..], false, 1) {
+            Ok((node, consumed)) => {
+                if debug_parser {
+                    log_debug(&format!("parse -> {}", node.string()));
+                }
+
+                idx += consumed;
+                nodes.push(node);
+            }
+            Err(err) => {
+                RECOVERED.with(|errs| errs.borrow_mut().get_or_insert_with(Vec::new).push(err));
+                idx = synchronize(tokens, idx);
+            }
+        }
+    }
+
+    RECOVERED
+        .with(|errs| errs.borrow_mut().take())
+        .unwrap_or_default()
+}
+
+/// Parses the statements of a block, those indented beyond the column bound. A statement
+/// that fails to parse aborts the block, unless parsing in recovering mode. The block of
+/// an anonymous function passed in parentheses ends at the closing parenthesis.
+fn parse_block(
+    tokens: &[Tok],
+    mut idx: usize,
+    col_bound: usize,
+) -> Result<(Vec<Node>, usize), Err> {
+    let mut body = Vec::new();
+    while idx < tokens.len()
+        && tokens[idx].position.column > col_bound
+        && tokens[idx].kind != Kind::RightParen
+    {
+        match parse_expression(&tokens[idx..], false, col_bound) {
+            Ok((stmt, consumed)) => {
+                body.push(stmt);
+                idx += consumed;
+            }
+            Err(err) => {
+                RECOVERED.with(|errs| match errs.borrow_mut().as_mut() {
+                    Some(errs) => {
+                        errs.push(err);
+                        Ok(())
+                    }
+                    None => Err(err),
+                })?;
+                idx = synchronize(tokens, idx);
+            }
+        }
+    }
+
+    Ok((body, idx))
+}
+
+/// Skips the tokens of the statement starting at the index, which failed to parse.
+/// Parsing resumes at the next line indented no deeper than the statement, that is at
+/// the next statement of the block or at the end of the block.
+fn synchronize(tokens: &[Tok], idx: usize) -> usize {
+    let start = &tokens[idx].position;
+    let mut idx = idx + 1;
+    while idx < tokens.len()
+        && (tokens[idx].position.line == start.line || tokens[idx].position.column > start.column)
+    {
+        idx += 1;
+    }
+    idx
+}
+
 #[inline]
 fn get_op_priority(t: &Tok) -> i8 {
     // higher number means higher priority
@@ -292,6 +489,7 @@ fn get_op_priority(t: &Tok) -> i8 {
         Kind::ModulusOp => 40,
         Kind::MultiplyOp | Kind::DivideOp => 25,
         Kind::AddOp | Kind::SubtractOp => 20,
+        Kind::EllipsisOp => 17,
         Kind::GreaterThanOp | Kind::LessThanOp | Kind::EqualOp => 15,
         Kind::AssignOp => 0,
         _ => -1,
@@ -311,10 +509,20 @@ fn is_binary_op(t: &Tok) -> bool {
             | Kind::GreaterThanOp
             | Kind::LessThanOp
             | Kind::EqualOp
+            | Kind::EllipsisOp
             | Kind::AssignOp,
     )
 }
 
+/// Whether the token at the index is the `..` of a slice with no end, `array[start..]`,
+/// rather than of a range.
+fn is_open_slice(tokens: &[Tok], idx: usize) -> bool {
+    tokens[idx].kind == Kind::EllipsisOp
+        && tokens
+            .get(idx + 1)
+            .is_none_or(|tok| tok.kind == Kind::RightBracket)
+}
+
 fn parse_expression(
     tokens: &[Tok],
     parsing_fn_args: bool,
@@ -333,12 +541,11 @@ fn parse_expression(
     idx += 1;
 
     match &next_tok.kind {
-        Kind::RightParen
-        | Kind::QuestionMark
-        | Kind::Bang
-        | Kind::RightBracket
-        | Kind::In
-        | Kind::EllipsisOp => {
+        Kind::RightParen | Kind::QuestionMark | Kind::Bang | Kind::RightBracket | Kind::In => {
+            Ok((atom, idx - 1)) // consumed by caller
+        }
+
+        Kind::EllipsisOp if is_open_slice(tokens, idx - 1) => {
             Ok((atom, idx - 1)) // consumed by caller
         }
 
@@ -355,7 +562,8 @@ fn parse_expression(
         | Kind::LessThanOp
         | Kind::EqualOp
         | Kind::AssignOp
-        | Kind::AccessorOp => {
+        | Kind::AccessorOp
+        | Kind::EllipsisOp => {
             let (bin_expr, consumed) =
                 parse_binary_expr(atom, next_tok, &tokens[idx..], -1, col_bound)?;
             idx += consumed;
@@ -371,6 +579,8 @@ fn parse_expression(
                     b = next_tok.position.string()
                 ),
                 reason: ErrorReason::Syntax,
+                span: Some(Span::at(&next_tok.position)),
+                trace: None,
             }),
         },
     }
@@ -390,7 +600,7 @@ fn parse_binary_expr(
 
     // build up a list of binary operations, with tree nodes
     // where there are higher-precedence operations
-    while tokens.len() > idx && is_binary_op(&tokens[idx]) {
+    while tokens.len() > idx && is_binary_op(&tokens[idx]) && !is_open_slice(tokens, idx) {
         if previous_priority >= get_op_priority(&tokens[idx]) {
             // Priority is lower than the previous op, so we're done
             break;
@@ -457,6 +667,10 @@ fn parse_atom(
 
         Kind::For => return parse_for_expr(tok, &tokens[idx..], col_bound),
 
+        Kind::While => return parse_while_expr(tok, &tokens[idx..], col_bound),
+
+        Kind::Match => return parse_match_expr(tok, &tokens[idx..], col_bound),
+
         Kind::LeftParen => return parse_capsulated_expr(tokens, idx, col_bound),
 
         Kind::LeftBracket => return parse_array_literal(tokens, col_bound),
@@ -549,10 +763,15 @@ fn parse_atom(
                         (atom, idx) = parse_object_literal(&tokens[idx + 1..], atom)?;
                         idx += 2; // +1 for Kind::LeftBrace, +1 for previous overwritten value of idx
                     }
-                    Kind::LeftBracket => {
-                        // this is the start of an array operation
+                    Kind::LeftBracket if tokens[idx].position.line == tok.position.line => {
+                        // this is the start of an array operation, unless the bracket
+                        // opens the next line, like an array pattern of a match arm
                         (atom, idx) = parse_array_op(atom, tokens, col_bound)?;
                     }
+                    Kind::ModuleAccessor => {
+                        // this is an access to a loaded module's binding
+                        (atom, idx) = parse_module_access(tokens)?;
+                    }
                     _ => {}
                 }
             }
@@ -568,12 +787,15 @@ fn parse_atom(
         }
 
         Kind::EmptyIdentifier => {
-            return Ok((
-                Node::EmptyIdentifier {
-                    position: tok.position.clone(),
-                },
-                idx,
-            ));
+            atom = Node::EmptyIdentifier {
+                position: tok.position.clone(),
+            };
+            if idx < tokens.len() && tokens[idx].kind == Kind::Colon {
+                // colon after the empty identifier means an anonymous function literal
+                (atom, idx) = parse_function_literal(tokens, col_bound)?;
+            } else {
+                return Ok((atom, idx));
+            }
         }
 
         _ => {
@@ -584,6 +806,8 @@ fn parse_atom(
                     b = tok.position.string()
                 ),
                 reason: ErrorReason::Syntax,
+                span: Some(Span::at(&tok.position)),
+                trace: None,
             });
         }
     }
@@ -598,6 +822,7 @@ fn parse_atom(
             | Kind::NumberLiteral
             | Kind::TrueLiteral
             | Kind::FalseLiteral
+            | Kind::EmptyLiteral
             | Kind::LeftParen => {
                 let (_atom, consumed) = parse_function_call(&atom, &tokens[idx..], col_bound)?;
                 idx += consumed;
@@ -631,6 +856,8 @@ fn parse_capsulated_expr(
                 a = tokens[idx - 1].string()
             ),
             reason: ErrorReason::Syntax,
+            span: Some(Span::at(&tokens[idx - 1].position)),
+            trace: None,
         }),
     }
 }
@@ -672,27 +899,29 @@ fn parse_array_op(
         if !matches!(node, &Node::NumberLiteral { .. } | &Node::Identifier { .. }) {
             return Err(Err {
                 reason: ErrorReason::Syntax,
+                span: Some(Span::at(node.position())),
                 message: format!(
                     "expected number literal or identifier for indexing, found ({}) at [{}]",
                     node.string(),
                     node.position().string()
                 ),
+                trace: None,
             });
         }
         Ok(())
     };
 
-    // array[start..end]
-    let slicing_op = |mut idx: usize, start_inclusive| {
-        let end_exclusive = {
-            match tokens[idx].kind {
-                Kind::RightBracket => None::<Box<Node>>,
-                _ => {
-                    let (end, consumed) = parse_expression(&tokens[idx..], false, col_bound)?;
-                    assert_index(&end)?;
-                    idx += consumed;
-                    Some(Box::new(end))
-                }
+    // array[start..end], the end parsed here unless it was parsed with the start as a
+    // range
+    let slicing_op = |mut idx: usize, start_inclusive, end_exclusive: Option<Box<Node>>| {
+        let end_exclusive = match (end_exclusive, &tokens[idx].kind) {
+            (Some(end), _) => Some(end),
+            (None, Kind::RightBracket) => None,
+            (None, _) => {
+                let (end, consumed) = parse_expression(&tokens[idx..], false, col_bound)?;
+                assert_index(&end)?;
+                idx += consumed;
+                Some(Box::new(end))
             }
         };
 
@@ -714,6 +943,8 @@ fn parse_array_op(
                     b = tokens[idx].position.string(),
                 ),
                 reason: ErrorReason::Syntax,
+                span: Some(Span::at(&tokens[idx].position)),
+                trace: None,
             }),
         }
     };
@@ -723,21 +954,34 @@ fn parse_array_op(
         Kind::EllipsisOp => {
             idx += 1; // +1 for Kind::Ellipsis
             guard_unexpected_input_end(tokens, idx)?;
-            slicing_op(idx, None)
+            slicing_op(idx, None, None)
         }
 
         _ => {
             let (start_inclusive, consumed) = parse_expression(&tokens[idx..], false, col_bound)?;
-            assert_index(&start_inclusive)?;
             idx += consumed;
+            guard_unexpected_input_end(tokens, idx)?;
+
+            // array[start..end]
+            if let Node::BinaryExpression {
+                operator: Kind::EllipsisOp,
+                left_operand,
+                right_operand,
+                ..
+            } = start_inclusive
+            {
+                assert_index(&left_operand)?;
+                assert_index(&right_operand)?;
+                return slicing_op(idx, Some(left_operand), Some(right_operand));
+            }
+            assert_index(&start_inclusive)?;
 
             match tokens[idx].kind {
                 // array[start..]
-                // array[start..end]
                 Kind::EllipsisOp => {
                     idx += 1; // +1 for Kind::Ellipsis
                     guard_unexpected_input_end(tokens, idx)?;
-                    slicing_op(idx, Some(Box::new(start_inclusive)))
+                    slicing_op(idx, Some(Box::new(start_inclusive)), None)
                 }
 
                 // array[idx]
@@ -758,12 +1002,46 @@ fn parse_array_op(
                         b = tokens[idx].position.string()
                     ),
                     reason: ErrorReason::Syntax,
+                    span: Some(Span::at(&tokens[idx].position)),
+                    trace: None,
                 }),
             }
         }
     }
 }
 
+fn parse_module_access(tokens: &[Tok]) -> Result<(Node, usize), Err> {
+    let idx = 2; // +1 for Identifier, +1 for Kind::ModuleAccessor
+    guard_unexpected_input_end(tokens, idx)?;
+
+    match tokens[idx].kind {
+        Kind::Identifier => Ok((
+            Node::ModuleAccess {
+                module: tokens[0]
+                    .str
+                    .clone()
+                    .expect("identifier kind always has this value present in it's token"),
+                member: tokens[idx]
+                    .str
+                    .clone()
+                    .expect("identifier kind always has this value present in it's token"),
+                position: tokens[0].position.clone(),
+            },
+            idx + 1, // +1 for Kind::Identifier consumed
+        )),
+        _ => Err(Err {
+            message: t!(
+                "errors.parse_module_access_e",
+                a = tokens[idx].string(),
+                b = tokens[idx].position.string()
+            ),
+            reason: ErrorReason::Syntax,
+            span: Some(Span::at(&tokens[idx].position)),
+            trace: None,
+        }),
+    }
+}
+
 fn parse_object_literal(tokens: &[Tok], name: Node) -> Result<(Node, usize), Err> {
     let mut idx = 0;
     let mut value = Vec::new();
@@ -784,6 +1062,8 @@ fn parse_object_literal(tokens: &[Tok], name: Node) -> Result<(Node, usize), Err
                     a = tokens[idx].position.string()
                 ),
                 reason: ErrorReason::Syntax,
+                span: Some(Span::at(&tokens[idx].position)),
+                trace: None,
             }),
         }?;
         idx += 1; // +1 for Kind::Identifier consumed
@@ -820,6 +1100,8 @@ fn parse_object_literal(tokens: &[Tok], name: Node) -> Result<(Node, usize), Err
                             a = tokens[idx].position.string()
                         ),
                         reason: ErrorReason::Syntax,
+                        span: Some(Span::at(&tokens[idx].position)),
+                        trace: None,
                     }),
                 }
             }
@@ -829,6 +1111,8 @@ fn parse_object_literal(tokens: &[Tok], name: Node) -> Result<(Node, usize), Err
                     a = tokens[idx].position.string(),
                 ),
                 reason: ErrorReason::Syntax,
+                span: Some(Span::at(&tokens[idx].position)),
+                trace: None,
             }),
         }?;
 
@@ -846,25 +1130,61 @@ fn parse_object_literal(tokens: &[Tok], name: Node) -> Result<(Node, usize), Err
     ))
 }
 
+/// Parses an if expression, its condition and its clauses. A clause is the rest of the
+/// line after its `?` or `!`, or else the block of lines indented as deep as its first
+/// line, which is deeper than the if expression. A clause that starts a line of its own,
+/// like the `!` of an else clause after a block, belongs to the innermost if expression
+/// it is not indented deeper than, so that `! if` chains alternatives.
 fn parse_if_expr(if_token: &Tok, tokens: &[Tok], col_bound: usize) -> Result<(Node, usize), Err> {
     let (condition, mut idx) = parse_expression(tokens, false, col_bound)?;
-    let mut if_arms = [None::<Box<Node>>, None::<Box<Node>>];
-
-    let arms =
-        |idx: usize| tokens[idx].kind == Kind::QuestionMark || tokens[idx].kind == Kind::Bang;
+    let mut if_arms = [None::<Vec<Node>>, None::<Vec<Node>>];
+
+    let arms = |idx: usize| {
+        let tok = &tokens[idx];
+        let on_own_line = idx > 0 && tok.position.line > tokens[idx - 1].position.line;
+        matches!(tok.kind, Kind::QuestionMark | Kind::Bang)
+            && (!on_own_line
+                || (tok.position.column >= col_bound
+                    && tok.position.column <= if_token.position.column))
+    };
 
     while idx < tokens.len() && arms(idx) {
         guard_unexpected_input_end(tokens, idx + 1)?;
-
-        let (arm, consumed) = parse_expression(&tokens[idx + 1..], false, col_bound)?;
-        let kind = tokens[idx].kind.clone();
+        let (marker, next) = (&tokens[idx], &tokens[idx + 1]);
+
+        // a block is indented deeper than its `?` or `!` when it starts a line of its own,
+        // or else than the if expression
+        let on_own_line = idx > 0 && marker.position.line > tokens[idx - 1].position.line;
+        let block_bound = match on_own_line {
+            true => marker.position.column,
+            false => if_token.position.column,
+        };
+        let (arm, consumed) = match next.position.line > marker.position.line {
+            true if next.position.column > block_bound.max(col_bound) => {
+                let (block, end) = parse_block(tokens, idx + 1, next.position.column - 1)?;
+                (block, end - idx - 1)
+            }
+            true => {
+                return Err(Err {
+                    message: t!("errors.parse_if_expr_e", a = next.position.string()),
+                    reason: ErrorReason::Syntax,
+                    span: Some(Span::at(&next.position)),
+                    trace: None,
+                })
+            }
+            false => {
+                let (stmt, consumed) = parse_expression(&tokens[idx + 1..], false, col_bound)?;
+                (vec![stmt], consumed)
+            }
+        };
+        let kind = marker.kind.clone();
 
         idx += consumed + 1; // +1 for Node::QuestionMark || Node::Bang
 
         if kind == Kind::QuestionMark {
-            if_arms[0] = Some(Box::new(arm));
+            if_arms[0] = Some(arm);
         } else {
-            if_arms[1] = Some(Box::new(arm));
+            if_arms[1] = Some(arm);
         }
     }
 
@@ -892,6 +1212,8 @@ fn parse_for_expr(for_token: &Tok, tokens: &[Tok], col_bound: usize) -> Result<(
                 b = tokens[idx].position.string(),
             ),
             reason: ErrorReason::Syntax,
+            span: Some(Span::at(&tokens[idx].position)),
+            trace: None,
         });
     }
 
@@ -925,16 +1247,7 @@ fn parse_for_expr(for_token: &Tok, tokens: &[Tok], col_bound: usize) -> Result<(
         return Ok(empty_loop.clone());
     }
 
-    let body = {
-        let col_bound = for_token.position.column;
-        let mut body = Vec::new();
-        while idx < tokens.len() && tokens[idx].position.column > col_bound {
-            let (stmt, consumed) = parse_expression(&tokens[idx..], false, col_bound)?;
-            body.push(stmt);
-            idx += consumed;
-        }
-        body
-    };
+    let (body, idx) = parse_block(tokens, idx, for_token.position.column)?;
 
     return Ok((
         Node::ForExpr {
@@ -947,6 +1260,267 @@ fn parse_for_expr(for_token: &Tok, tokens: &[Tok], col_bound: usize) -> Result<(
     ));
 }
 
+/// Parses a while loop, its condition and the body run while the condition holds.
+fn parse_while_expr(
+    while_token: &Tok,
+    tokens: &[Tok],
+    col_bound: usize,
+) -> Result<(Node, usize), Err> {
+    let (condition, idx) = parse_expression(tokens, false, col_bound)?;
+
+    // the body should be nested beyond the while token's column
+    if idx == tokens.len() || while_token.position.column >= tokens[idx].position.column {
+        return Ok((
+            Node::WhileExpr {
+                condition: Box::new(condition),
+                body: None,
+                position: while_token.position.clone(),
+            },
+            idx + 1, // +1 for Node::While consumed by caller
+        ));
+    }
+
+    let (body, idx) = parse_block(tokens, idx, while_token.position.column)?;
+    Ok((
+        Node::WhileExpr {
+            condition: Box::new(condition),
+            body: Some(body),
+            position: while_token.position.clone(),
+        },
+        idx + 1, // +1 for Node::While consumed by caller
+    ))
+}
+
+/// Parses a match expression, the value matched and its arms. The arms are on the lines
+/// after the value, each starting at the column of the first.
+fn parse_match_expr(
+    match_token: &Tok,
+    tokens: &[Tok],
+    col_bound: usize,
+) -> Result<(Node, usize), Err> {
+    let (value, mut idx) = parse_expression(tokens, false, col_bound)?;
+
+    let column = match tokens.get(idx) {
+        Some(tok)
+            if tok.position.line > value.position().line && tok.position.column > col_bound =>
+        {
+            tok.position.column
+        }
+        _ => {
+            return Err(Err {
+                message: t!(
+                    "errors.parse_match_expr_e1",
+                    a = t!("literals.match"),
+                    b = match_token.position.string()
+                ),
+                reason: ErrorReason::Syntax,
+                span: Some(Span::at(&match_token.position)),
+                trace: None,
+            })
+        }
+    };
+
+    let mut arms = Vec::new();
+    while idx < tokens.len() && tokens[idx].position.column == column {
+        let (pattern, consumed) = parse_pattern(&tokens[idx..])?;
+        idx += consumed;
+
+        guard_unexpected_input_end(tokens, idx)?;
+        let guard = match tokens[idx].kind {
+            Kind::If => {
+                guard_unexpected_input_end(tokens, idx + 1)?;
+                let (guard, consumed) = parse_expression(&tokens[idx + 1..], false, col_bound)?;
+                idx += consumed + 1; // +1 for Kind::If
+                Some(guard)
+            }
+            _ => None,
+        };
+
+        guard_unexpected_input_end(tokens, idx)?;
+        if tokens[idx].kind != Kind::QuestionMark {
+            return Err(Err {
+                message: t!(
+                    "errors.parse_match_expr_e2",
+                    a = tokens[idx].string(),
+                    b = tokens[idx].position.string()
+                ),
+                reason: ErrorReason::Syntax,
+                span: Some(Span::at(&tokens[idx].position)),
+                trace: None,
+            });
+        }
+
+        guard_unexpected_input_end(tokens, idx + 1)?;
+        let (body, consumed) = parse_expression(&tokens[idx + 1..], false, col_bound)?;
+        idx += consumed + 1; // +1 for Kind::QuestionMark
+
+        arms.push(MatchArm {
+            pattern,
+            guard,
+            body,
+        });
+    }
+
+    Ok((
+        Node::MatchExpr {
+            value: Box::new(value),
+            arms,
+            position: match_token.position.clone(),
+        },
+        idx + 1, // +1 for Node::Match consumed by caller
+    ))
+}
+
+/// Parses the pattern of a match arm.
+fn parse_pattern(tokens: &[Tok]) -> Result<(Pattern, usize), Err> {
+    guard_unexpected_input_end(tokens, 0)?;
+    let tok = &tokens[0];
+    let position = tok.position.clone();
+
+    let literal = |node| Ok((Pattern::Literal(node), 1));
+    match &tok.kind {
+        Kind::NumberLiteral => literal(Node::NumberLiteral {
+            value: tok.num.expect("this node has this value present"),
+            position,
+        }),
+        Kind::StringLiteral => literal(Node::StringLiteral {
+            value: tok.str.clone().expect("this node has this value present"),
+            position,
+        }),
+        Kind::TrueLiteral | Kind::FalseLiteral => literal(Node::BoolLiteral {
+            value: tok.kind == Kind::TrueLiteral,
+            position,
+        }),
+        Kind::EmptyLiteral => literal(Node::EmptyLiteral { position }),
+        Kind::EmptyIdentifier => Ok((Pattern::Wildcard { position }, 1)),
+        Kind::Identifier if tokens.get(1).is_some_and(|tok| tok.kind == Kind::LeftBrace) => {
+            parse_object_pattern(tokens)
+        }
+        Kind::Identifier => {
+            let name = tok.str.clone().expect("this node has this value present");
+            match name.as_str() {
+                "_" => Ok((Pattern::Wildcard { position }, 1)),
+                _ => Ok((Pattern::Binding { name, position }, 1)),
+            }
+        }
+        Kind::LeftBracket => parse_array_pattern(tokens),
+        _ => Err(pattern_e(tok)),
+    }
+}
+
+/// Parses an array pattern, `[first, second, ..rest]`.
+fn parse_array_pattern(tokens: &[Tok]) -> Result<(Pattern, usize), Err> {
+    let mut idx = 1; // +1 for Kind::LeftBracket
+    let (mut items, mut rest) = (Vec::new(), None);
+
+    loop {
+        guard_unexpected_input_end(tokens, idx)?;
+        match tokens[idx].kind {
+            Kind::RightBracket => break,
+            // the rest is the last pattern of the array, and is any array if unnamed
+            Kind::EllipsisOp if rest.is_none() => {
+                idx += 1; // +1 for Kind::EllipsisOp
+                guard_unexpected_input_end(tokens, idx)?;
+                rest = Some(Box::new(match tokens[idx].kind {
+                    Kind::RightBracket => Pattern::Wildcard {
+                        position: tokens[idx - 1].position.clone(),
+                    },
+                    Kind::Identifier | Kind::EmptyIdentifier => {
+                        let (pattern, consumed) = parse_pattern(&tokens[idx..])?;
+                        idx += consumed;
+                        pattern
+                    }
+                    _ => return Err(pattern_e(&tokens[idx])),
+                }));
+            }
+            _ if rest.is_none() => {
+                let (pattern, consumed) = parse_pattern(&tokens[idx..])?;
+                items.push(pattern);
+                idx += consumed;
+            }
+            _ => return Err(pattern_e(&tokens[idx])),
+        }
+
+        guard_unexpected_input_end(tokens, idx)?;
+        if tokens[idx].kind == Kind::Separator {
+            idx += 1; // +1 for Kind::Separator consumed
+        }
+    }
+
+    Ok((
+        Pattern::Array {
+            items,
+            rest,
+            position: tokens[0].position.clone(),
+        },
+        idx + 1, // +1 for Kind::RightBracket consumed
+    ))
+}
+
+/// Parses an object pattern, `Person { name, age a }`.
+fn parse_object_pattern(tokens: &[Tok]) -> Result<(Pattern, usize), Err> {
+    let mut idx = 2; // +1 for Kind::Identifier, +1 for Kind::LeftBrace
+    let mut fields = Vec::new();
+
+    loop {
+        guard_unexpected_input_end(tokens, idx)?;
+        let field = match &tokens[idx].kind {
+            Kind::RightBrace => break,
+            Kind::Identifier => tokens[idx]
+                .str
+                .clone()
+                .expect("identifier kind always has this value present in it's token"),
+            _ => return Err(pattern_e(&tokens[idx])),
+        };
+        let position = tokens[idx].position.clone();
+        idx += 1; // +1 for Kind::Identifier consumed
+
+        guard_unexpected_input_end(tokens, idx)?;
+        let pattern = match tokens[idx].kind {
+            Kind::Separator | Kind::RightBrace => Pattern::Binding {
+                name: field.clone(),
+                position,
+            },
+            _ => {
+                let (pattern, consumed) = parse_pattern(&tokens[idx..])?;
+                idx += consumed;
+                pattern
+            }
+        };
+        fields.push((field, pattern));
+
+        guard_unexpected_input_end(tokens, idx)?;
+        if tokens[idx].kind == Kind::Separator {
+            idx += 1; // +1 for Kind::Separator consumed
+        }
+    }
+
+    Ok((
+        Pattern::Object {
+            name: tokens[0]
+                .str
+                .clone()
+                .expect("identifier kind always has this value present in it's token"),
+            fields,
+            position: tokens[0].position.clone(),
+        },
+        idx + 1, // +1 for Kind::RightBrace consumed
+    ))
+}
+
+fn pattern_e(tok: &Tok) -> Err {
+    Err {
+        message: t!(
+            "errors.parse_pattern_e",
+            a = tok.string(),
+            b = tok.position.string()
+        ),
+        reason: ErrorReason::Syntax,
+        span: Some(Span::at(&tok.position)),
+        trace: None,
+    }
+}
+
 fn parse_function_call(
     func: &Node,
     tokens: &[Tok],
@@ -988,6 +1562,8 @@ fn parse_function_literal(tokens: &[Tok], col_bound: usize) -> Result<(Node, usi
                 a = tokens[0].position.string()
             ),
             reason: ErrorReason::Syntax,
+            span: Some(Span::at(&tokens[0].position)),
+            trace: None,
         });
     }
 
@@ -1006,6 +1582,8 @@ fn parse_function_literal(tokens: &[Tok], col_bound: usize) -> Result<(Node, usi
         _ => Err(Err {
             message: "".to_string(),
             reason: ErrorReason::Assert,
+            span: None,
+            trace: None,
         }),
     }?;
 
@@ -1038,19 +1616,38 @@ fn parse_function_literal(tokens: &[Tok], col_bound: usize) -> Result<(Node, usi
                 b = tokens[idx].position.string()
             ),
             reason: ErrorReason::Syntax,
+            span: Some(Span::at(&tokens[idx].position)),
+            trace: None,
         }),
     }?;
     idx += 1; // +1 for the Kind::Identifier consumed
 
-    // parse the function's body
+    // parse the function's body. The body of an anonymous function is the rest of the
+    // line, or else the block of lines indented as deep as its first line
     guard_unexpected_input_end(tokens, idx)?;
-    let col_bound = fn_name.position().column;
-    let mut body = Vec::new();
-    while idx < tokens.len() && tokens[idx].position.column > col_bound {
-        let (stmt, consumed) = parse_expression(&tokens[idx..], false, col_bound)?;
-        body.push(stmt);
-        idx += consumed;
-    }
+    let (body, idx) = match fn_name {
+        Node::EmptyIdentifier { .. }
+            if tokens[idx].position.line == tokens[idx - 1].position.line =>
+        {
+            let (stmt, consumed) = parse_expression(&tokens[idx..], false, col_bound)?;
+            (vec![stmt], idx + consumed)
+        }
+        Node::EmptyIdentifier { .. } if tokens[idx].position.column > col_bound => {
+            parse_block(tokens, idx, tokens[idx].position.column - 1)?
+        }
+        Node::EmptyIdentifier { .. } => {
+            return Err(Err {
+                message: t!(
+                    "errors.parse_function_literal_e3",
+                    a = tokens[idx].position.string()
+                ),
+                reason: ErrorReason::Syntax,
+                span: Some(Span::at(&tokens[idx].position)),
+                trace: None,
+            })
+        }
+        _ => parse_block(tokens, idx, fn_name.position().column)?,
+    };
 
     // compose the parsed components into a function literal
     let position = fn_name.position().clone();
@@ -1073,6 +1670,15 @@ fn parse_fn_sign_args(tokens: &[Tok]) -> Result<(Vec<(Node, Node)>, usize), Err>
     while idx < tokens.len() && tokens[idx].kind != Kind::FunctionArrow {
         // ident type , || ident,
         match &tokens[idx].kind {
+            // an identifier following a parameter is the name of its object type
+            Kind::Identifier if idx > 0 && tokens[idx - 1].kind == Kind::Identifier => {
+                for _ in 1..=(args.len() - arg_types.len()) {
+                    arg_types.push(Node::Identifier {
+                        value: tokens[idx].str.clone().unwrap(),
+                        position: tokens[idx].position.clone(),
+                    })
+                }
+            }
             Kind::Identifier => {
                 args.push(Node::Identifier {
                     value: tokens[idx].str.clone().unwrap(),
@@ -1087,6 +1693,8 @@ fn parse_fn_sign_args(tokens: &[Tok]) -> Result<(Vec<(Node, Node)>, usize), Err>
                             a = tokens[idx].position.string()
                         ),
                         reason: ErrorReason::Syntax,
+                        span: Some(Span::at(&tokens[idx].position)),
+                        trace: None,
                     });
                 }
                 for _ in 1..=(args.len() - arg_types.len()) {
@@ -1107,6 +1715,8 @@ fn parse_fn_sign_args(tokens: &[Tok]) -> Result<(Vec<(Node, Node)>, usize), Err>
                         b = tokens[idx].position.string()
                     ),
                     reason: ErrorReason::Syntax,
+                    span: Some(Span::at(&tokens[idx].position)),
+                    trace: None,
                 });
             }
         }
@@ -1118,19 +1728,23 @@ fn parse_fn_sign_args(tokens: &[Tok]) -> Result<(Vec<(Node, Node)>, usize), Err>
 
 fn guard_unexpected_input_end(tokens: &[Tok], idx: usize) -> Result<(), Err> {
     if idx >= tokens.len() {
-        if tokens.is_empty() {
+        if let Some(last) = tokens.last() {
             return Err(Err {
                 message: t!(
                     "errors.guard_unexpected_input_end_e1",
-                    a = tokens[tokens.len() - 1].kind.string()
+                    a = last.kind.string()
                 ),
                 reason: ErrorReason::Syntax,
+                span: Some(Span::at(&last.position)),
+                trace: None,
             });
         }
 
         return Err(Err {
             message: t!("errors.guard_unexpected_input_end_e2"),
             reason: ErrorReason::Syntax,
+            span: None,
+            trace: None,
         });
     }
 
@@ -1141,9 +1755,10 @@ fn guard_unexpected_input_end(tokens: &[Tok], idx: usize) -> Result<(), Err> {
 mod test {
     use super::parse_expression;
     use crate::{
-        lexer::{Kind, Position, Tok},
+        lexer::{tokenize, Kind, Position, Tok},
         parser::Node,
     };
+    use std::io::BufReader;
 
     // "Hello World example"
     #[test]
@@ -1356,4 +1971,193 @@ mod test {
             res
         );
     }
+
+    #[test]
+    fn anonymous_function_literal() {
+        // the body is the rest of the line, the indented block, or the block up to the
+        // closing parenthesis
+        for (source, body_len) in [
+            ("f is _: x number -> number x * 2", 1),
+            ("f is _: x number -> number\n    y is x\n    y * 2\nf 1", 2),
+            ("apply (_: x number -> number\n    y is x\n    y * 2) 1", 2),
+        ] {
+            let mut tokens = Vec::new();
+            tokenize(&mut BufReader::new(source.as_bytes()), &mut tokens, false)
+                .expect("the source is lexed");
+            let (res, _) = parse_expression(&tokens, false, 1).expect("the source is parsed");
+
+            let literal = match res {
+                Node::BinaryExpression { right_operand, .. } => *right_operand,
+                Node::FunctionCall { arguments, .. } => arguments[0].clone(),
+                _ => panic!("unexpected node {}", res.string()),
+            };
+            match literal {
+                Node::FunctionLiteral { sign, body, .. } => {
+                    assert!(matches!(*sign.0, Node::EmptyIdentifier { .. }), "{source}");
+                    assert_eq!(body.len(), body_len, "{source}");
+                }
+                _ => panic!("unexpected node {}", literal.string()),
+            }
+        }
+
+        // the body of an anonymous function must be indented
+        let mut tokens = Vec::new();
+        tokenize(
+            &mut BufReader::new("f is _: x number -> number\nf 1".as_bytes()),
+            &mut tokens,
+            false,
+        )
+        .expect("the source is lexed");
+        assert!(parse_expression(&tokens, false, 1).is_err());
+    }
+
+    #[test]
+    fn while_expr_and_range() {
+        let source = "while n < 3\n    n is n + 1\nfor i in 1..n + 1\n    println i";
+        let mut tokens = Vec::new();
+        tokenize(&mut BufReader::new(source.as_bytes()), &mut tokens, false)
+            .expect("the source is lexed");
+
+        let (res, consumed) = parse_expression(&tokens, false, 0).expect("the source is parsed");
+        match res {
+            Node::WhileExpr {
+                condition, body, ..
+            } => {
+                assert_eq!(condition.string(), "Binary '<' (n, 3)");
+                assert_eq!(body.map(|body| body.len()), Some(1));
+            }
+            _ => panic!("unexpected node {}", res.string()),
+        }
+
+        let (res, _) =
+            parse_expression(&tokens[consumed..], false, 0).expect("the source is parsed");
+        match res {
+            Node::ForExpr { iterable, .. } => match *iterable {
+                Node::BinaryExpression {
+                    operator: Kind::EllipsisOp,
+                    right_operand,
+                    ..
+                } => assert!(matches!(
+                    *right_operand,
+                    Node::BinaryExpression {
+                        operator: Kind::AddOp,
+                        ..
+                    }
+                )),
+                node => panic!("unexpected node {}", node.string()),
+            },
+            _ => panic!("unexpected node {}", res.string()),
+        }
+
+        // a range is an expression of its own, binding looser than arithmetic
+        let mut tokens = Vec::new();
+        tokenize(
+            &mut BufReader::new("r is 1..n + 1".as_bytes()),
+            &mut tokens,
+            false,
+        )
+        .expect("the source is lexed");
+        let (res, _) = parse_expression(&tokens, false, 0).expect("the source is parsed");
+        match res {
+            Node::BinaryExpression {
+                operator: Kind::AssignOp,
+                right_operand,
+                ..
+            } => assert!(matches!(
+                *right_operand,
+                Node::BinaryExpression {
+                    operator: Kind::EllipsisOp,
+                    ..
+                }
+            )),
+            _ => panic!("unexpected node {}", res.string()),
+        }
+    }
+
+    #[test]
+    fn if_expr_blocks() {
+        let source = "if a ?\n    b is 1\n    if b ? 2\n    ! 3\n! if c ? 4\n!\n    5\n6";
+        let mut tokens = Vec::new();
+        tokenize(&mut BufReader::new(source.as_bytes()), &mut tokens, false)
+            .expect("the source is lexed");
+
+        let (res, consumed) = parse_expression(&tokens, false, 0).expect("the source is parsed");
+        match res {
+            Node::IfExpr {
+                on_true: Some(on_true),
+                on_false: Some(on_false),
+                ..
+            } => {
+                // the nested `!` is indented deeper than the outer if, so is its own
+                assert_eq!(on_true.len(), 2);
+                assert!(matches!(
+                    &on_true[1],
+                    Node::IfExpr {
+                        on_false: Some(_),
+                        ..
+                    }
+                ));
+                match &on_false[..] {
+                    [Node::IfExpr {
+                        on_true: Some(on_true),
+                        on_false: Some(on_false),
+                        ..
+                    }] => {
+                        assert_eq!(on_true.len(), 1);
+                        assert_eq!(on_false.len(), 1);
+                    }
+                    stmts => panic!("unexpected clause of {} statements", stmts.len()),
+                }
+            }
+            _ => panic!("unexpected node {}", res.string()),
+        }
+        assert_eq!(tokens[consumed].position.line, 8);
+    }
+
+    #[test]
+    fn match_expr() {
+        let source = "size is match xs\n    [] ? 0\n    [first, ..rest] if first > 0 ? 1\n    Person { name, age a } ? a\n    _ ? 3\nsize";
+        let mut tokens = Vec::new();
+        tokenize(&mut BufReader::new(source.as_bytes()), &mut tokens, false)
+            .expect("the source is lexed");
+        let (res, consumed) = parse_expression(&tokens, false, 0).expect("the source is parsed");
+        assert_eq!(
+            tokens.len() - 1,
+            consumed,
+            "the arms end at the next statement"
+        );
+
+        let arms = match res {
+            Node::BinaryExpression { right_operand, .. } => match *right_operand {
+                Node::MatchExpr { arms, .. } => arms,
+                node => panic!("unexpected node {}", node.string()),
+            },
+            _ => panic!("unexpected node {}", res.string()),
+        };
+        let patterns = arms
+            .iter()
+            .map(|arm| (arm.pattern.string(), arm.guard.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            patterns,
+            [
+                ("[]".to_string(), false),
+                ("[first, ..rest]".to_string(), true),
+                ("Person { name name, age a }".to_string(), false),
+                ("_".to_string(), false),
+            ]
+        );
+
+        // the arms must be on the lines after the value
+        for source in [
+            "match xs [] ? 0",
+            "match xs\n    [1 ? 0",
+            "match xs\n    1 0",
+        ] {
+            let mut tokens = Vec::new();
+            tokenize(&mut BufReader::new(source.as_bytes()), &mut tokens, false)
+                .expect("the source is lexed");
+            assert!(parse_expression(&tokens, false, 0).is_err(), "{source}");
+        }
+    }
 }
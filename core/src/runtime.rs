@@ -1,16 +1,110 @@
 use super::{
+    bytecode::Chunk,
+    check::check,
+    embed::{HostFn, HostFunction},
     error::{Err, ErrorReason},
     eval::{value::Value, CallerCtx},
     lexer::tokenize,
-    log::log_debug,
-    parser::{parse, Node},
+    locale,
+    log::{log_debug, write_err, write_interactive},
+    parser::{parse, parse_recovering, Node},
+    stdlib::{self, Input},
+    vm::Vm,
 };
 use crate::lexer::Tok;
-use rust_i18n::t;
-use std::{collections::HashMap, fmt, fs, io::BufReader};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use unicode_segmentation::UnicodeSegmentation;
 
 pub const MAX_PRINT_LEN: usize = 120;
 
+thread_local! {
+    /// The module loader shared by the `mod` builtin for the current thread.
+    static LOADER: RefCell<ModuleLoader> = RefCell::new(ModuleLoader::default());
+
+    /// The input and outputs of the context evaluating on the current thread.
+    static IO: RefCell<Rc<Io>> = RefCell::new(Rc::new(Io::default()));
+}
+
+/// Io holds the input and the outputs of the programs of a context. Native functions
+/// are given the `Io` of the context running them to read and print with.
+pub struct Io {
+    pub(crate) input: RefCell<Input>,
+    pub(crate) stdout: RefCell<Box<dyn Write>>,
+    pub(crate) stderr: RefCell<Box<dyn Write>>,
+}
+
+impl Io {
+    /// The `Io` of the context evaluating on the current thread.
+    pub(crate) fn current() -> Rc<Io> {
+        IO.with(|io| Rc::clone(&io.borrow()))
+    }
+
+    /// Writes the text to the output, flushing it so that prompts show before input.
+    pub(crate) fn print(&self, text: &str) -> Result<(), Err> {
+        let mut stdout = self.stdout.borrow_mut();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Io {
+            input: RefCell::new(Input::Stdin),
+            stdout: RefCell::new(Box::new(io::stdout())),
+            stderr: RefCell::new(Box::new(io::stderr())),
+        }
+    }
+}
+
+impl fmt::Debug for Io {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Io {{ input: {:?} }}", self.input.borrow())
+    }
+}
+
+/// Captured is an output kept in memory, for embedders and tests to read what a
+/// program printed. Its clones share the output.
+#[derive(Debug, Clone, Default)]
+pub struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Captured {
+    /// The text written to the output so far.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+}
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// ModuleLoader keeps track of the Speak files being evaluated and caches the
+/// top-level bindings of every module that has been loaded.
+#[derive(Debug, Default)]
+struct ModuleLoader {
+    /// The chain of files currently being evaluated, the last is the innermost.
+    files: Vec<PathBuf>,
+    /// Loaded modules keyed by their canonical path, those of the evaluating Context.
+    cache: Modules,
+    /// Whether modules are evaluated by the tree walker, as the importing program is.
+    tree_walk: bool,
+}
+
 /// ValueTable is used anytime a map of names/labels to Speak Values is needed,
 /// and is notably used to represent stack frames/heaps and CompositeValue dictionaries.
 #[derive(Debug, Clone)]
@@ -21,18 +115,26 @@ impl VTable {
         self.0.get(name)
     }
 
-    fn set(&mut self, key: String, value: Value) {
+    pub(crate) fn set(&mut self, key: String, value: Value) {
         self.0.insert(key, value);
     }
 }
 
+/// Scope is a table of names shared by every stack frame it is part of, so that a
+/// name bound through one of them is seen through all the others.
+pub type Scope = Rc<RefCell<VTable>>;
+
+/// Modules maps the canonical paths of the modules a Context has loaded to their bindings.
+type Modules = Rc<RefCell<HashMap<PathBuf, VTable>>>;
+
 /// StackFrame represents the heap of variables local to a particular function call frame,
-/// and recursively references other parent StackFrames internally.
+/// and recursively references other parent StackFrames internally. Frames are shared,
+/// cloning a StackFrame is cheap and the clone sees the same variables.
 #[derive(Debug, Clone)]
 pub enum StackFrame {
     Frame {
-        frame: VTable,
-        parent_frame: Box<StackFrame>,
+        frame: Scope,
+        parent_frame: Rc<StackFrame>,
     },
     Nil,
 }
@@ -41,81 +143,81 @@ impl StackFrame {
     /// Creates a new stack frame with the provided value table and parent stack frame.
     pub fn new(value_table: VTable, parent: StackFrame) -> Self {
         Self::Frame {
-            frame: value_table,
-            parent_frame: Box::new(parent),
+            frame: Rc::new(RefCell::new(value_table)),
+            parent_frame: Rc::new(parent),
         }
     }
 
     /// Pushes a frame to the stack and sets it's current frame as the parent frame.
     pub fn push_frame(&mut self, frame: VTable) {
-        *self = Self::Frame {
-            frame,
-            parent_frame: Box::new(self.clone()),
-        };
+        let parent = std::mem::replace(self, StackFrame::Nil);
+        *self = Self::new(frame, parent);
     }
 
     /// Pops a child frame from the stack, setting it's parent frame as the current frame.
     pub fn pop_frame(&mut self) -> Result<(), Err> {
-        match self {
+        match std::mem::replace(self, StackFrame::Nil) {
             StackFrame::Frame { parent_frame, .. } => {
-                *self = parent_frame.as_ref().clone();
+                *self = Rc::unwrap_or_clone(parent_frame);
                 Ok(())
             }
             StackFrame::Nil => Err(Err {
                 message: t!("errors.pop_frame_e"),
                 reason: ErrorReason::Assert,
+                span: None,
+                trace: None,
             }),
         }
     }
 
-    /// Get a value from the current stack frame; or up the parent frames.
-    pub fn get(&self, name: &str) -> Option<&Value> {
+    /// The innermost scope binding the name, if any.
+    fn scope_of(&self, name: &str) -> Option<&Scope> {
         let mut frame = self;
         while let StackFrame::Frame {
             frame: item,
             parent_frame: next,
         } = frame
         {
-            match item.get(name) {
-                Some(val) => return Some(val),
-                None => {
-                    frame = next;
-                }
+            if item.borrow().get(name).is_some() {
+                return Some(item);
             }
+            frame = next;
         }
         None
     }
 
+    /// Get a value from the current stack frame; or up the parent frames.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.scope_of(name)
+            .and_then(|scope| scope.borrow().get(name).cloned())
+    }
+
+    /// Whether the name is bound in the current stack frame; or up the parent frames.
+    pub fn contains(&self, name: &str) -> bool {
+        self.scope_of(name).is_some()
+    }
+
     /// Sets a value to the provided stack frame.
     pub fn set(&mut self, name: String, val: Value) {
         if let StackFrame::Frame { frame: item, .. } = self {
-            item.set(name, val)
+            item.borrow_mut().set(name, val)
         }
     }
 
     /// Updates a value in the current frame; or up the parent frames.
     pub fn up(&mut self, name: String, val: &Value) -> Result<(), Err> {
-        let mut frame = self;
-        while let StackFrame::Frame {
-            frame: item,
-            parent_frame,
-        } = frame
-        {
-            match item.get(&name) {
-                Some(_) => {
-                    item.0.insert(name, val.clone());
-                    return Ok(());
-                }
-                None => {
-                    frame = parent_frame;
-                }
+        match self.scope_of(&name) {
+            Some(scope) => {
+                scope.borrow_mut().set(name, val.clone());
+                Ok(())
             }
+            None => Err(Err {
+                message: t!("errors.up_e", a = name),
+                reason: ErrorReason::Assert,
+                span: None,
+                trace: None,
+            }),
         }
-
-        Err(Err {
-            message: t!("errors.up_e", a = name),
-            reason: ErrorReason::Assert,
-        })
     }
 
     /// dumps the stack frame chain to return out.
@@ -126,10 +228,13 @@ impl StackFrame {
         } = self
         {
             let mut entries = Vec::new();
-            for (k, v) in &item.0 {
+            for (k, v) in &item.borrow().0 {
                 let mut v_str = v.string();
-                if v_str.len() > MAX_PRINT_LEN {
-                    v_str = format!("{}...", &v_str[..MAX_PRINT_LEN])
+                if v_str.chars().count() > MAX_PRINT_LEN {
+                    v_str = format!(
+                        "{}...",
+                        v_str.chars().take(MAX_PRINT_LEN).collect::<String>()
+                    )
                 }
                 entries.push(format!("{k} -> {v_str}"))
             }
@@ -152,6 +257,24 @@ pub struct Context {
     pub file: Option<String>,
     /// Frame represents the Context's global heap.
     pub frame: StackFrame,
+    /// Evaluate programs by walking their syntax tree, instead of compiling them to
+    /// bytecode for the VM. The two evaluate programs alike, this is kept to test so.
+    pub tree_walk: bool,
+    /// Seeds the random numbers of the next evaluation, which takes the seed. The random
+    /// numbers are seeded from the clock if no seed is given.
+    pub seed: Option<u64>,
+    /// Write errors and REPL values with ANSI colours.
+    pub colors: bool,
+    /// Check the types of programs before evaluating them, failing with every mismatch
+    /// found instead of running them.
+    pub type_check: bool,
+    /// The input and outputs of the programs of the context, stdin, stdout and stderr
+    /// unless set otherwise.
+    io: Rc<Io>,
+    /// The modules loaded by the programs of the context, evaluated once each.
+    modules: Modules,
+    /// The functions registered by the embedder, bound with the builtins.
+    hosts: Vec<HostFunction>,
 
     debug_lex: bool,
     debug_parse: bool,
@@ -163,12 +286,67 @@ impl Context {
         Context {
             file: None,
             frame: StackFrame::new(VTable(HashMap::new()), StackFrame::Nil),
+            tree_walk: false,
+            seed: None,
+            colors: true,
+            type_check: false,
+            io: Rc::new(Io::default()),
+            modules: Modules::default(),
+            hosts: Vec::new(),
             debug_lex: *verbose,
             debug_parse: *verbose,
             debug_dump: *verbose,
         }
     }
 
+    /// Reads the input of the programs of the context from the reader instead of stdin.
+    pub fn set_input(&mut self, reader: impl BufRead + 'static) {
+        *self.io.input.borrow_mut() = Input::Reader(Box::new(reader));
+    }
+
+    /// Writes the output of the programs of the context to `out` instead of stdout.
+    pub fn set_stdout(&mut self, out: impl Write + 'static) {
+        *self.io.stdout.borrow_mut() = Box::new(out);
+    }
+
+    /// Writes the errors of the context to `out` instead of stderr.
+    pub fn set_stderr(&mut self, out: impl Write + 'static) {
+        *self.io.stderr.borrow_mut() = Box::new(out);
+    }
+
+    /// Registers a Rust function to be called from the programs of the context, named
+    /// by the locale its programs are written in. `names` pairs each locale with the
+    /// function's name in it, the name in the default locale is used for other locales.
+    /// The arguments are converted with `FromValue` and the result with `IntoValue`,
+    /// and a call with arguments the function does not take raises an error.
+    ///
+    /// ```
+    /// # use core::runtime::Context;
+    /// let mut ctx = Context::new(&false);
+    /// ctx.register_fn(&[("en", "double"), ("sw", "maradufu")], |x: f64| x * 2.0);
+    /// ```
+    pub fn register_fn<Args>(
+        &mut self,
+        names: &[(&str, &str)],
+        func: impl HostFn<Args>,
+    ) -> &mut Self {
+        self.hosts.push(HostFunction::new(names, func));
+        self
+    }
+
+    /// Writes the output of an interactive session, like the values of a REPL, to the
+    /// stdout of the context.
+    pub fn log_interactive(&self, args: &str) {
+        let mut stdout = self.io.stdout.borrow_mut();
+        _ = write_interactive(&mut *stdout, args, self.colors);
+    }
+
+    /// Writes the error to the stderr of the context, with an excerpt of its source.
+    pub fn log_err(&self, err: &Err) {
+        let mut stderr = self.io.stderr.borrow_mut();
+        _ = write_err(&mut *stderr, err, self.colors);
+    }
+
     pub fn dump(&self) {
         if let Some(s) = self.frame.string() {
             log_debug(&format!("frame_dump:\n{}", s));
@@ -184,6 +362,24 @@ impl Context {
 
         // load runtime
         load_builtins(self)?;
+        LOADER.with(|loader| {
+            let mut loader = loader.borrow_mut();
+            loader.tree_walk = self.tree_walk;
+            loader.cache = Rc::clone(&self.modules);
+        });
+        if let Some(seed) = self.seed.take() {
+            stdlib::seed(seed);
+        }
+        IO.with(|io| *io.borrow_mut() = Rc::clone(&self.io));
+
+        if !self.tree_walk {
+            let chunk = Rc::new(Chunk::compile(&nodes));
+            last_val = Vm::new(&mut self.frame).run(chunk)?;
+            if dump_frame && len > 0 {
+                self.dump();
+            }
+            return Ok(last_val);
+        }
 
         for (i, node) in nodes.into_iter().enumerate() {
             let mut node = node;
@@ -199,6 +395,31 @@ impl Context {
         Ok(last_val)
     }
 
+    /// Loads a locale definition file, a YAML or TOML file with the same keys as the
+    /// interpreter's `locales/en.yml`. Returns the name of the locale, which is then
+    /// provided to exec the Speak programs written in it.
+    pub fn load_locale(&self, path: &str) -> Result<String, Err> {
+        locale::load_locale(path)
+    }
+
+    /// Lexes and parses a Speak program, recovering from syntax errors. Returns the
+    /// tokens, the syntax tree of the statements that parsed, and every error found. The
+    /// errors are not yet in a file, callers attach the file the source was read from.
+    pub fn parse_source(&self, speak: &str, source: &str) -> (Vec<Tok>, Vec<Node>, Vec<Err>) {
+        locale::set_locale(speak);
+
+        let mut tokens = Vec::new();
+        let mut buf = BufReader::new(source.as_bytes());
+        if let Err(err) = tokenize(&mut buf, &mut tokens, self.debug_lex) {
+            return (tokens, Vec::new(), vec![err]);
+        }
+
+        let mut nodes = Vec::new();
+        let errs = parse_recovering(&tokens, &mut nodes, self.debug_parse);
+
+        (tokens, nodes, errs)
+    }
+
     /// Runs a Speak program defined by the buffer.
     /// This is the main way to invoke Speak programs from Rust.
     pub fn exec(
@@ -206,48 +427,91 @@ impl Context {
         speak: &str,
         input: BufReader<&[u8]>,
     ) -> Result<(Value, Vec<Tok>, Vec<Node>), Err> {
-        rust_i18n::set_locale(speak);
-
-        let mut tokens = Vec::new();
-
-        let mut buf = input;
-        tokenize(&mut buf, &mut tokens, self.debug_lex)?;
-
-        let mut nodes = Vec::new();
+        let mut source = String::new();
+        let mut input = input;
+        input.read_to_string(&mut source)?;
 
-        parse(&tokens, &mut nodes, self.debug_parse)?;
-
-        let val = self.eval(nodes.clone(), self.debug_dump)?;
-
-        Ok((val, tokens, nodes))
+        self.exec_source(speak, &source)
+            .map_err(|errs| errs.into_iter().next().expect("errors are never empty"))
     }
 
-    /// Allows to Exec() a program file in a given context.
-    pub fn exec_path(&mut self, speak: &str, path: &str) -> Result<Value, Err> {
-        match fs::read(path) {
-            Ok(data) => {
+    /// Allows to Exec() a program file in a given context. Returns every syntax error
+    /// of the program, or the error the program failed with at runtime.
+    pub fn exec_path(&mut self, speak: &str, path: &str) -> Result<Value, Vec<Err>> {
+        match fs::read_to_string(path) {
+            Ok(source) => {
                 self.file = Some(path.to_string());
-                let (val, _, _) = self.exec(speak, BufReader::new(&data[..]))?;
+
+                // modules imported by this file are resolved relative to it
+                let file = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+                LOADER.with(|loader| loader.borrow_mut().files.push(file));
+                let res = self.exec_source(speak, &source);
+                LOADER.with(|loader| loader.borrow_mut().files.pop());
+
+                let (val, _, _) = res?;
                 Ok(val)
             }
-            Err(err) => Err(Err {
+            Err(err) => Err(vec![Err {
                 message: t!("errors.exec_path_e", a = err),
                 reason: ErrorReason::System,
-            }),
+                span: None,
+                trace: None,
+            }]),
         }
     }
+
+    /// Runs the Speak program in the source, if it has no syntax errors. The warnings of
+    /// the type checker are logged, and the program runs anyway.
+    fn exec_source(
+        &mut self,
+        speak: &str,
+        source: &str,
+    ) -> Result<(Value, Vec<Tok>, Vec<Node>), Vec<Err>> {
+        let file = self.file.clone();
+        let in_source = |err: Err| err.in_source(file.as_deref(), source);
+        let (tokens, nodes, errs) = self.parse_source(speak, source);
+        let mut errs = errs.into_iter().map(in_source).collect::<Vec<_>>();
+        if errs.is_empty() && self.type_check {
+            let (warnings, checked) = check(&nodes)
+                .into_iter()
+                .map(in_source)
+                .partition::<Vec<_>, _>(|err| err.reason == ErrorReason::Warning);
+            for warning in &warnings {
+                self.log_err(warning);
+            }
+            errs = checked;
+        }
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+
+        let val = self
+            .eval(nodes.clone(), self.debug_dump)
+            .map_err(|err| vec![in_source(err)])?;
+
+        Ok((val, tokens, nodes))
+    }
 }
 
-/// Native function are convenience functions that come with the interpreter;
-/// an example is the `println` function
+/// Native function are convenience functions that come with the interpreter, or are
+/// registered by its embedder; an example is the `println` function
 #[derive(Clone)]
-pub struct NativeFunction<F: Fn(&mut StackFrame, &[Value]) -> Result<Value, Err>>(
-    pub String,
-    pub F,
-);
-pub type NativeFn = NativeFunction<fn(&mut StackFrame, &[Value]) -> Result<Value, Err>>;
+pub struct NativeFunction(pub String, pub NativeFn);
+
+/// The implementation of a native function, called with the stack and the `Io` of its
+/// caller. It may capture the state of the host it is registered by.
+pub type NativeFn = Rc<dyn Fn(&mut StackFrame, &Io, &[Value]) -> Result<Value, Err>>;
 
-impl fmt::Debug for NativeFn {
+impl NativeFunction {
+    pub fn new(
+        name: String,
+        func: impl Fn(&mut StackFrame, &Io, &[Value]) -> Result<Value, Err> + 'static,
+    ) -> Self {
+        NativeFunction(name, Rc::new(func))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -261,32 +525,44 @@ impl fmt::Debug for NativeFn {
 
 /// This loads up the built-in functions which come with the interpreter.
 pub fn load_builtins(ctx: &mut Context) -> Result<(), Err> {
-    match &mut ctx.frame {
+    load_builtins_to(&mut ctx.frame)?;
+
+    for host in &ctx.hosts {
+        if let Some(func) = host.native() {
+            ctx.frame.set(func.0.clone(), Value::NativeFunction(func));
+        }
+    }
+    Ok(())
+}
+
+/// Loads the built-in functions to the provided stack frame.
+fn load_builtins_to(stack: &mut StackFrame) -> Result<(), Err> {
+    match stack {
         StackFrame::Frame { frame, .. } => {
+            let mut frame = frame.borrow_mut();
             let key = t!("builtins.print");
             frame.set(
                 key.clone(),
-                Value::NativeFunction(NativeFunction(key, |_, inputs| {
+                Value::NativeFunction(NativeFunction::new(key, |_, io, inputs| {
                     if inputs.is_empty() {
                         return Err(Err {
                             message: t!("errors.load_builtins_e1", a = t!("builtins.print")),
                             reason: ErrorReason::Runtime,
+                            span: None,
+                            trace: None,
                         });
                     }
 
-                    print!(
-                        "{}",
-                        inputs[0].string().split("{}").enumerate().fold(
-                            String::new(),
-                            |acc, (i, x)| {
-                                if i == inputs.len() - 1 {
-                                    acc + x
-                                } else {
-                                    acc + x + &inputs[i + 1].string()
-                                }
-                            },
-                        )
-                    );
+                    io.print(&inputs[0].string().split("{}").enumerate().fold(
+                        String::new(),
+                        |acc, (i, x)| {
+                            if i == inputs.len() - 1 {
+                                acc + x
+                            } else {
+                                acc + x + &inputs[i + 1].string()
+                            }
+                        },
+                    ))?;
 
                     Ok(Value::Empty)
                 })),
@@ -295,9 +571,9 @@ pub fn load_builtins(ctx: &mut Context) -> Result<(), Err> {
             let key = t!("builtins.println");
             frame.set(
                 key.clone(),
-                Value::NativeFunction(NativeFunction(key, |_, inputs| {
-                    println!(
-                        "{}",
+                Value::NativeFunction(NativeFunction::new(key, |_, io, inputs| {
+                    io.print(&format!(
+                        "{}\n",
                         inputs[0].string().split("{}").enumerate().fold(
                             String::new(),
                             |acc, (i, x)| {
@@ -308,7 +584,7 @@ pub fn load_builtins(ctx: &mut Context) -> Result<(), Err> {
                                 }
                             },
                         )
-                    );
+                    ))?;
 
                     Ok(Value::Empty)
                 })),
@@ -317,11 +593,13 @@ pub fn load_builtins(ctx: &mut Context) -> Result<(), Err> {
             let key = t!("builtins.sprint");
             frame.set(
                 key.clone(),
-                Value::NativeFunction(NativeFunction(key, |_, inputs| {
+                Value::NativeFunction(NativeFunction::new(key, |_, _, inputs| {
                     if inputs.is_empty() {
                         return Err(Err {
                             message: t!("errors.load_builtins_e1", a = t!("builtins.sprint")),
                             reason: ErrorReason::Runtime,
+                            span: None,
+                            trace: None,
                         });
                     }
 
@@ -343,20 +621,24 @@ pub fn load_builtins(ctx: &mut Context) -> Result<(), Err> {
             let key = t!("builtins.len");
             frame.set(
                 key.clone(),
-                Value::NativeFunction(NativeFunction(key, |_, inputs| {
+                Value::NativeFunction(NativeFunction::new(key, |_, _, inputs| {
                     if inputs.len() != 1 {
                         return Err(Err {
                             message: t!("errors.load_builtins_e2", a = t!("builtins.len")),
                             reason: ErrorReason::Runtime,
+                            span: None,
+                            trace: None,
                         });
                     }
 
                     match &inputs[0] {
-                        Value::String(val) => Ok(Value::Number(val.len() as f64)),
+                        Value::String(val) => Ok(Value::Number(val.graphemes(true).count() as f64)),
                         Value::Array(_, val) => Ok(Value::Number(val.len() as f64)),
                         _ => Err(Err {
                             message: t!("errors.load_builtins_e3", a = t!("builtins.len")),
                             reason: ErrorReason::Runtime,
+                            span: None,
+                            trace: None,
                         }),
                     }
                 })),
@@ -365,19 +647,21 @@ pub fn load_builtins(ctx: &mut Context) -> Result<(), Err> {
             let key = t!("builtins.mod");
             frame.set(
                 key.clone(),
-                Value::NativeFunction(NativeFunction(
+                Value::NativeFunction(NativeFunction::new(
                     key,
-                    |_stack: &mut StackFrame, inputs: &[Value]| -> Result<Value, Err> {
+                    |stack: &mut StackFrame, _: &Io, inputs: &[Value]| -> Result<Value, Err> {
                         for i in inputs {
                             match i {
-                                Value::String(_path) => {
-                                    // TODO: load data to stack
-                                    unimplemented!()
+                                Value::String(path) => {
+                                    let (name, vt) = load_module(path)?;
+                                    stack.set(name.clone(), Value::Module { name, vt });
                                 }
                                 _ => {
                                     return Err(Err {
                                         message: t!("errors.load_builtins_e4"),
                                         reason: ErrorReason::Runtime,
+                                        span: None,
+                                        trace: None,
                                     });
                                 }
                             }
@@ -388,12 +672,136 @@ pub fn load_builtins(ctx: &mut Context) -> Result<(), Err> {
                 )),
             );
 
+            stdlib::load(&mut frame);
             Ok(())
         }
 
         StackFrame::Nil => Err(Err {
             message: t!("errors.load_builtins_e5"),
             reason: ErrorReason::Assert,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// Resolves the module path relative to the file currently being evaluated,
+/// if any, defaulting to the `.spk` extension.
+fn resolve_module_path(path: &str) -> PathBuf {
+    let mut module_path = PathBuf::from(path);
+    if module_path.extension().is_none() {
+        module_path.set_extension("spk");
+    }
+
+    if module_path.is_relative() {
+        let dir = LOADER.with(|loader| {
+            loader
+                .borrow()
+                .files
+                .last()
+                .and_then(|file| file.parent().map(Path::to_path_buf))
+        });
+        if let Some(dir) = dir {
+            return dir.join(module_path);
+        }
+    }
+
+    module_path
+}
+
+/// Loads the Speak module at the provided path, returning the name it is bound to
+/// and its top-level bindings. Modules are evaluated once in an isolated stack frame
+/// and cached for subsequent imports.
+fn load_module(path: &str) -> Result<(String, VTable), Err> {
+    let module_path = resolve_module_path(path);
+    let file = fs::canonicalize(&module_path).map_err(|err| Err {
+        message: t!("errors.load_module_e1", a = module_path.display(), b = err),
+        reason: ErrorReason::System,
+        span: None,
+        trace: None,
+    })?;
+
+    let name = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // assert the module is not already being evaluated up the import chain
+    let cycle = LOADER.with(|loader| {
+        let loader = loader.borrow();
+        loader.files.iter().position(|f| f == &file).map(|i| {
+            loader.files[i..]
+                .iter()
+                .chain(std::iter::once(&file))
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        })
+    });
+    if let Some(cycle) = cycle {
+        return Err(Err {
+            message: t!("errors.load_module_e2", a = cycle),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        });
+    }
+
+    if let Some(vt) = LOADER.with(|loader| loader.borrow().cache.borrow().get(&file).cloned()) {
+        return Ok((name, vt));
+    }
+
+    let data = fs::read(&file).map_err(|err| Err {
+        message: t!("errors.load_module_e1", a = file.display(), b = err),
+        reason: ErrorReason::System,
+        span: None,
+        trace: None,
+    })?;
+
+    LOADER.with(|loader| loader.borrow_mut().files.push(file.clone()));
+    let vt = eval_module(&data);
+    LOADER.with(|loader| loader.borrow_mut().files.pop());
+    let vt = vt.map_err(|err| {
+        err.in_source(
+            Some(&module_path.display().to_string()),
+            &String::from_utf8_lossy(&data),
+        )
+    })?;
+
+    LOADER.with(|loader| loader.borrow().cache.borrow_mut().insert(file, vt.clone()));
+    Ok((name, vt))
+}
+
+/// Evaluates a module's source in a frame of its own, on top of the builtins.
+fn eval_module(data: &[u8]) -> Result<VTable, Err> {
+    let mut tokens = Vec::new();
+    tokenize(&mut BufReader::new(data), &mut tokens, false)?;
+
+    let mut nodes = Vec::new();
+    parse(&tokens, &mut nodes, false)?;
+
+    let mut builtins = StackFrame::new(VTable(HashMap::new()), StackFrame::Nil);
+    load_builtins_to(&mut builtins)?;
+
+    let mut stack = StackFrame::new(VTable(HashMap::new()), builtins);
+    match LOADER.with(|loader| loader.borrow().tree_walk) {
+        true => {
+            for mut node in nodes {
+                node.eval(&CallerCtx::Undefined, &mut stack, false)?;
+            }
+        }
+        false => {
+            Vm::new(&mut stack).run(Rc::new(Chunk::compile(&nodes)))?;
+        }
+    }
+
+    match stack {
+        StackFrame::Frame { frame, .. } => Ok(Rc::unwrap_or_clone(frame).into_inner()),
+        StackFrame::Nil => Err(Err {
+            message: t!("errors.load_builtins_e5"),
+            reason: ErrorReason::Assert,
+            span: None,
+            trace: None,
         }),
     }
 }
@@ -429,14 +837,88 @@ mod tests {
             .equals(Value::String("mutated value".to_string())));
 
         // test stackframe.get, in parent frame
-        let frame = StackFrame::Frame {
-            frame: VTable(HashMap::new()),
-            parent_frame: Box::new(frame),
-        };
+        let frame = StackFrame::new(VTable(HashMap::new()), frame);
         assert!(frame.get("a").is_some());
         assert!(frame.get("b").is_none());
     }
 
+    #[test]
+    fn stack_frames_are_shared() {
+        let mut global = StackFrame::new(VTable(HashMap::new()), StackFrame::Nil);
+        global.set("a".to_string(), Value::Number(1.0));
+
+        // pushing frames does not copy the frames below
+        let mut stack = global.clone();
+        for _ in 0..1000 {
+            stack.push_frame(VTable(HashMap::new()));
+        }
+        let mut bottom = &stack;
+        while let StackFrame::Frame { parent_frame, .. } = bottom {
+            if let StackFrame::Nil = parent_frame.as_ref() {
+                break;
+            }
+            bottom = parent_frame;
+        }
+        match (bottom, &global) {
+            (StackFrame::Frame { frame, .. }, StackFrame::Frame { frame: global, .. }) => {
+                assert!(Rc::ptr_eq(frame, global))
+            }
+            _ => panic!("the frames must be set"),
+        }
+
+        // and writes through either are seen by both
+        stack
+            .up("a".to_string(), &Value::Number(2.0))
+            .expect("a is bound in the global frame");
+        assert!(global
+            .get("a")
+            .expect("key must be present")
+            .equals(Value::Number(2.0)));
+        for _ in 0..1000 {
+            stack.pop_frame().expect("the frame was pushed");
+        }
+        assert!(stack.get("a").is_some());
+    }
+
+    #[test]
+    fn loop_writes_are_visible() {
+        let program = "total is 0\nfor x in [1, 2, 3]\n    total is total + x\nsum is total";
+        for tree_walk in [true, false] {
+            let mut ctx_test = Context::new(&false);
+            ctx_test.tree_walk = tree_walk;
+            if let Err(err) = ctx_test.exec("en", BufReader::new(program.as_bytes())) {
+                panic!("{:?}", err)
+            }
+            assert!(ctx_test
+                .frame
+                .get("sum")
+                .expect("sum is bound after the loop")
+                .equals(Value::Number(6.0)));
+            assert!(ctx_test.frame.get("x").is_none());
+        }
+    }
+
+    #[test]
+    fn output_sinks() {
+        let program = "println \"{} and {}\" 1 2\nprint \"no newline\"\nx is 1 + true";
+        for tree_walk in [true, false] {
+            let mut ctx_test = Context::new(&false);
+            ctx_test.tree_walk = tree_walk;
+            ctx_test.colors = false;
+            let (out, err) = (Captured::default(), Captured::default());
+            ctx_test.set_stdout(out.clone());
+            ctx_test.set_stderr(err.clone());
+
+            let res = ctx_test.exec("en", BufReader::new(program.as_bytes()));
+            assert_eq!(out.text(), "1 and 2\nno newline");
+
+            // errors are written without colours
+            ctx_test.log_err(&res.expect_err("a number cannot be added to a bool"));
+            assert!(err.text().starts_with("Runtime error: "), "{}", err.text());
+            assert!(!err.text().contains('\u{1b}'));
+        }
+    }
+
     #[test]
     fn hello_world_eval() {
         let mut ctx_test = Context::new(&true);
@@ -467,4 +949,125 @@ mod tests {
             Err(err) => panic!("{:?}", err),
         }
     }
+
+    #[test]
+    fn module_eval() {
+        let mut ctx_test = Context::new(&false);
+        let cwd = std::env::current_dir().expect("there must be a wd");
+
+        match ctx_test.exec_path(
+            "en",
+            cwd.join("samples/module.spk")
+                .to_str()
+                .expect("this path exists"),
+        ) {
+            Ok(val) => assert!(val.equals(Value::Number(144.0))),
+            Err(err) => panic!("{:?}", err),
+        }
+
+        // module bindings are not leaked to the importing frame
+        assert!(ctx_test.frame.get("square").is_none());
+        assert!(ctx_test.frame.get("geometry").is_some());
+    }
+
+    #[test]
+    fn modules_are_cached_per_context() {
+        let dir = std::env::temp_dir().join("speak_modules_cached_per_context");
+        fs::create_dir_all(&dir).expect("temp dir is writable");
+        let main = dir.join("main.spk");
+        let main = main.to_str().expect("this path exists");
+        fs::write(main, "mod \"answer.spk\"\nanswer::value\n").expect("temp dir is writable");
+
+        let exec = |value: &str| {
+            fs::write(dir.join("answer.spk"), format!("value is {}\n", value))
+                .expect("temp dir is writable");
+            Context::new(&false)
+                .exec_path("en", main)
+                .expect("the module exists")
+        };
+
+        // a new context on the same thread loads the module afresh
+        assert!(exec("1").equals(Value::Number(1.0)));
+        assert!(exec("2").equals(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn module_cyclic_import() {
+        let dir = std::env::temp_dir().join("speak_module_cyclic_import");
+        fs::create_dir_all(&dir).expect("temp dir is writable");
+        fs::write(dir.join("a.spk"), "mod \"b.spk\"\n").expect("temp dir is writable");
+        fs::write(dir.join("b.spk"), "mod \"a\"\n").expect("temp dir is writable");
+
+        let mut ctx_test = Context::new(&false);
+        let err = ctx_test
+            .exec_path("en", dir.join("a.spk").to_str().expect("this path exists"))
+            .expect_err("the import chain is cyclic")
+            .remove(0);
+
+        assert_eq!(err.reason, ErrorReason::Runtime);
+        assert!(err.message.starts_with("cyclic module import"));
+        assert!(err.message.contains("a.spk -> "));
+        assert!(err.message.contains("b.spk -> "));
+    }
+
+    #[test]
+    fn error_spans() {
+        let dir = std::env::temp_dir().join("speak_error_spans");
+        fs::create_dir_all(&dir).expect("temp dir is writable");
+        let path = dir.join("main.spk");
+        fs::write(&path, "x is 1\nprintln \"{}\" y\n").expect("temp dir is writable");
+
+        let mut ctx_test = Context::new(&false);
+        let file = path.to_str().expect("this path exists");
+        let err = ctx_test
+            .exec_path("en", file)
+            .expect_err("y is not defined")
+            .remove(0);
+        let span = err.span.expect("the error is located");
+        assert_eq!(span.file.as_deref(), Some(file));
+        assert_eq!(span.start.string(), "2:14");
+        assert_eq!(span.snippet.as_deref(), Some("println \"{}\" y"));
+
+        // lexer and parser errors are located too
+        let mut ctx_test = Context::new(&false);
+        for (program, position) in [("x is \"abc", "1:6"), ("x is (1 + 2", "1:11")] {
+            let err = ctx_test
+                .exec("en", BufReader::new(program.as_bytes()))
+                .expect_err("the program is invalid");
+            let span = err.span.expect("the error is located");
+            assert_eq!(span.start.string(), position, "{program}");
+            assert_eq!(span.snippet.as_deref(), Some(program));
+        }
+    }
+
+    #[test]
+    fn syntax_error_recovery() {
+        let dir = std::env::temp_dir().join("speak_syntax_error_recovery");
+        fs::create_dir_all(&dir).expect("temp dir is writable");
+        let path = dir.join("main.spk");
+        let program =
+            "x is (1 + 2\nf: n number -> number\n    n * (2\n    n\ny is )\nprintln \"{}\" x\n";
+        fs::write(&path, program).expect("temp dir is writable");
+
+        // every syntax error is reported and nothing runs
+        let mut ctx_test = Context::new(&false);
+        let errs = ctx_test
+            .exec_path("en", path.to_str().expect("this path exists"))
+            .expect_err("the program has syntax errors");
+        let lines = errs
+            .iter()
+            .map(|err| err.span.as_ref().expect("the error is located").start.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![1, 3, 5]);
+        assert!(errs.iter().all(|err| err.reason == ErrorReason::Syntax));
+
+        // the statements that parsed make up the partial syntax tree
+        let (_, nodes, errs) = ctx_test.parse_source("en", program);
+        assert_eq!(errs.len(), 3);
+        assert_eq!(nodes.len(), 2);
+        match &nodes[0] {
+            Node::FunctionLiteral { body, .. } => assert_eq!(body.len(), 1),
+            node => panic!("expected a function literal, got {}", node.string()),
+        }
+    }
 }
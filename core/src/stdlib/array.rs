@@ -0,0 +1,308 @@
+//! Functions of arrays. Arrays are values, so the functions changing an array return the
+//! changed array, which is assigned back to keep it: `xs is push xs 4`.
+
+use super::{arity, array_arg, bind, call, function_arg, number_arg, string_arg};
+use crate::{
+    error::{Err, ErrorReason},
+    eval::{r#type::Type, value::Value},
+    runtime::{Io, StackFrame, VTable},
+};
+use std::{cmp::Ordering, slice};
+
+pub(super) fn load(frame: &mut VTable) {
+    bind(
+        frame,
+        &[
+            ("builtins.map", map),
+            ("builtins.filter", filter),
+            ("builtins.reduce", reduce),
+            ("builtins.each", each),
+            ("builtins.sort", sort),
+            ("builtins.sort_by", sort_by),
+            ("builtins.find", find),
+            ("builtins.index_of", index_of),
+            ("builtins.contains", contains),
+            ("builtins.reverse", reverse),
+            ("builtins.join", join),
+            ("builtins.range", range),
+            ("builtins.push", push),
+            ("builtins.pop", pop),
+        ],
+    );
+}
+
+/// `map xs f`, the array of `f` called with each item of `xs`.
+fn map(stack: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.map";
+    arity(key, inputs, 2, 2)?;
+    let (_, vals) = array_arg(key, inputs, 0)?;
+    let func = function_arg(key, inputs, 1)?;
+
+    let mut mapped = Vec::with_capacity(vals.len());
+    for val in vals {
+        mapped.push(call(stack, func, &[val])?);
+    }
+    array(key, mapped)
+}
+
+/// `filter xs f`, the items of `xs` for which `f` holds.
+fn filter(stack: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.filter";
+    arity(key, inputs, 2, 2)?;
+    let (t, vals) = array_arg(key, inputs, 0)?;
+    let func = function_arg(key, inputs, 1)?;
+
+    let mut kept = Vec::new();
+    for val in vals {
+        if holds(key, call(stack, func, slice::from_ref(&val))?)? {
+            kept.push(val);
+        }
+    }
+    Ok(Value::Array(t, kept))
+}
+
+/// `reduce xs f init`, the value of calling `f` with the value so far, starting with
+/// `init`, and each item of `xs`.
+fn reduce(stack: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.reduce";
+    arity(key, inputs, 3, 3)?;
+    let (_, vals) = array_arg(key, inputs, 0)?;
+    let func = function_arg(key, inputs, 1)?;
+
+    let mut acc = inputs[2].clone();
+    for val in vals {
+        acc = call(stack, func, &[acc, val])?;
+    }
+    Ok(acc)
+}
+
+/// `each xs f`, calls `f` with each item of `xs`.
+fn each(stack: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.each";
+    arity(key, inputs, 2, 2)?;
+    let (_, vals) = array_arg(key, inputs, 0)?;
+    let func = function_arg(key, inputs, 1)?;
+
+    for val in vals {
+        call(stack, func, &[val])?;
+    }
+    Ok(Value::Empty)
+}
+
+/// `sort xs`, the numbers or strings of `xs` in ascending order.
+fn sort(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.sort";
+    arity(key, inputs, 1, 1)?;
+    let (t, mut vals) = array_arg(key, inputs, 0)?;
+
+    sortable(key, &t)?;
+    vals.sort_by(compare);
+    Ok(Value::Array(t, vals))
+}
+
+/// `sort_by xs f`, the items of `xs` in the ascending order of the numbers or strings
+/// `f` returns for them. Items of equal order keep their order.
+fn sort_by(stack: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.sort_by";
+    arity(key, inputs, 2, 2)?;
+    let (t, vals) = array_arg(key, inputs, 0)?;
+    let func = function_arg(key, inputs, 1)?;
+
+    let mut keys = Vec::with_capacity(vals.len());
+    for val in &vals {
+        keys.push(call(stack, func, slice::from_ref(val))?);
+    }
+    if let Value::Array(t, _) = array(key, keys.clone())? {
+        sortable(key, &t)?;
+    }
+
+    let mut keyed = keys.into_iter().zip(vals).collect::<Vec<_>>();
+    keyed.sort_by(|(a, _), (b, _)| compare(a, b));
+    Ok(Value::Array(
+        t,
+        keyed.into_iter().map(|(_, val)| val).collect(),
+    ))
+}
+
+/// `find xs f`, the first item of `xs` for which `f` holds, or `()`.
+pub(super) fn find(stack: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.find";
+    arity(key, inputs, 2, 2)?;
+    let (_, vals) = array_arg(key, inputs, 0)?;
+    let func = function_arg(key, inputs, 1)?;
+
+    for val in vals {
+        if holds(key, call(stack, func, slice::from_ref(&val))?)? {
+            return Ok(val);
+        }
+    }
+    Ok(Value::Empty)
+}
+
+/// `index_of xs x`, the index of the first item of `xs` equal to `x`, or `()`.
+fn index_of(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.index_of";
+    arity(key, inputs, 2, 2)?;
+    let (t, vals) = array_arg(key, inputs, 0)?;
+
+    item(key, &t, &inputs[1])?;
+    Ok(vals
+        .iter()
+        .position(|val| val.equals(inputs[1].clone()))
+        .map_or(Value::Empty, |i| Value::Number(i as f64)))
+}
+
+/// `contains xs x`, whether an item of `xs` is equal to `x`.
+fn contains(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.contains";
+    arity(key, inputs, 2, 2)?;
+    let (t, vals) = array_arg(key, inputs, 0)?;
+
+    item(key, &t, &inputs[1])?;
+    Ok(Value::Bool(
+        vals.iter().any(|val| val.equals(inputs[1].clone())),
+    ))
+}
+
+/// `reverse xs`, the items of `xs` in reverse order.
+fn reverse(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.reverse";
+    arity(key, inputs, 1, 1)?;
+    let (t, mut vals) = array_arg(key, inputs, 0)?;
+
+    vals.reverse();
+    Ok(Value::Array(t, vals))
+}
+
+/// `join xs sep`, the string of the items of `xs` separated by `sep`.
+fn join(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.join";
+    arity(key, inputs, 2, 2)?;
+    let (_, vals) = array_arg(key, inputs, 0)?;
+    let sep = string_arg(key, inputs, 1)?;
+
+    Ok(Value::String(
+        vals.iter()
+            .map(Value::string)
+            .collect::<Vec<_>>()
+            .join(&sep),
+    ))
+}
+
+/// `range end`, `range start end` or `range start end step`, the numbers from `start`,
+/// or 0, up to but not including `end`, `step`, or 1, apart.
+fn range(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.range";
+    arity(key, inputs, 1, 3)?;
+    let mut bounds = Vec::with_capacity(inputs.len());
+    for i in 0..inputs.len() {
+        bounds.push(number_arg(key, inputs, i)?);
+    }
+    let (start, end, step) = match bounds[..] {
+        [end] => (0.0, end, 1.0),
+        [start, end] => (start, end, 1.0),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!("the arity is checked"),
+    };
+
+    if step == 0.0 || !step.is_finite() {
+        return Err(Err {
+            message: t!("errors.range_e", a = t!(key), b = step),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        });
+    }
+
+    let mut numbers = Vec::new();
+    let mut n = start;
+    while (step > 0.0 && n < end) || (step < 0.0 && n > end) {
+        numbers.push(Value::Number(n));
+        n = start + step * numbers.len() as f64;
+    }
+    Ok(Value::Array(Type::Number, numbers))
+}
+
+/// `push xs x`, `xs` with `x` added at its end.
+fn push(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.push";
+    arity(key, inputs, 2, 2)?;
+    let (t, mut vals) = array_arg(key, inputs, 0)?;
+
+    item(key, &t, &inputs[1])?;
+    vals.push(inputs[1].clone());
+    Ok(Value::Array(inputs[1].value_type(), vals))
+}
+
+/// `pop xs`, `xs` without its last item.
+fn pop(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.pop";
+    arity(key, inputs, 1, 1)?;
+    let (t, mut vals) = array_arg(key, inputs, 0)?;
+
+    vals.pop();
+    Ok(Value::Array(t, vals))
+}
+
+/// The array of the values, which must be of one type.
+fn array(key: &str, vals: Vec<Value>) -> Result<Value, Err> {
+    let t = vals.first().map_or(Type::Empty, Value::value_type);
+    for val in &vals {
+        item(key, &t, val)?;
+    }
+    Ok(Value::Array(t, vals))
+}
+
+/// Asserts that the value can be an item of an array of the type. Any value can be an
+/// item of an empty array.
+fn item(key: &str, t: &Type, val: &Value) -> Result<(), Err> {
+    if *t == Type::Empty || val.value_type() == *t {
+        return Ok(());
+    }
+
+    Err(Err {
+        message: t!(
+            "errors.item_e",
+            a = t!(key),
+            b = t.string(),
+            c = val.value_type().string()
+        ),
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    })
+}
+
+/// Whether the value returned by the function given to the native function holds.
+fn holds(key: &str, val: Value) -> Result<bool, Err> {
+    match val {
+        Value::Bool(b) => Ok(b),
+        val => Err(Err {
+            message: t!("errors.holds_e", a = t!(key), b = val.value_type().string()),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// Asserts that arrays of items of the type can be sorted, numbers and strings.
+fn sortable(key: &str, t: &Type) -> Result<(), Err> {
+    match t {
+        Type::Number | Type::String | Type::Empty => Ok(()),
+        t => Err(Err {
+            message: t!("errors.sortable_e", a = t!(key), b = t.string()),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
@@ -0,0 +1,66 @@
+//! Errors handled by Speak programs. `try` runs a function and hands a runtime error it
+//! fails with to a handler, as an object of the `Error` type, while syntax errors and
+//! errors of the interpreter itself still end the program. `raise` fails with an error
+//! of the program's own.
+
+use super::{arity, bind, call, function_arg, string_arg};
+use crate::{
+    error::{Err, ErrorReason},
+    eval::{r#type::Type, value::Value},
+    runtime::{Io, StackFrame, VTable},
+};
+use std::collections::HashMap;
+
+pub(super) fn load(frame: &mut VTable) {
+    bind(frame, &[("builtins.try", r#try), ("builtins.raise", raise)]);
+}
+
+/// `try f handle`, the value of `f ()`, or if it fails with a runtime error, the value of
+/// `handle err` with the error as an object of its reason, message and position.
+fn r#try(stack: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.try";
+    arity(key, inputs, 2, 2)?;
+    let func = function_arg(key, inputs, 0)?;
+    let handle = function_arg(key, inputs, 1)?;
+
+    match call(stack, func, &[Value::Empty]) {
+        Err(err) if err.reason == ErrorReason::Runtime => call(stack, handle, &[error_value(&err)]),
+        res => res,
+    }
+}
+
+/// `raise message`, fails with a runtime error of the message.
+fn raise(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.raise";
+    arity(key, inputs, 1, 1)?;
+
+    Err(Err {
+        message: string_arg(key, inputs, 0)?,
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    })
+}
+
+/// The error as an `Error` object. Its position is `line:column`, or empty if the error
+/// is not located.
+fn error_value(err: &Err) -> Value {
+    let position = err
+        .span
+        .as_ref()
+        .map(|span| span.start.string())
+        .unwrap_or_default();
+
+    let body = [
+        ("error.reason", err.reason.string()),
+        ("error.message", err.message.clone()),
+        ("error.position", position),
+    ]
+    .into_iter()
+    .map(|(key, s)| (t!(key), (Type::String, Value::String(s))))
+    .collect::<HashMap<_, _>>();
+    Value::Object {
+        name: t!("error.name"),
+        body,
+    }
+}
@@ -0,0 +1,104 @@
+//! Reading the input of a program, a line at a time. Lines are read from the input of
+//! the `Context` running the program, stdin unless another reader is given to it, and
+//! prompts are written to its output.
+
+use super::{argument_e, arity, bind, string_arg};
+use crate::{
+    error::{Err, ErrorReason},
+    eval::value::Value,
+    runtime::{Io, StackFrame, VTable},
+};
+use std::{
+    fmt,
+    io::{self, BufRead},
+};
+
+/// Where a program's input is read from.
+pub(crate) enum Input {
+    /// Stdin, read through its shared buffer so that the lines of the REPL and those of
+    /// the program it runs are read in turn.
+    Stdin,
+    Reader(Box<dyn BufRead>),
+}
+
+impl Input {
+    pub(crate) fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        match self {
+            Input::Stdin => io::stdin().read_line(buf),
+            Input::Reader(reader) => reader.read_line(buf),
+        }
+    }
+}
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Stdin => write!(f, "Stdin"),
+            Input::Reader(_) => write!(f, "Reader"),
+        }
+    }
+}
+
+pub(super) fn load(frame: &mut VTable) {
+    bind(
+        frame,
+        &[
+            ("builtins.input", input),
+            ("builtins.read_line", read_line),
+            ("builtins.read_number", read_number),
+        ],
+    );
+}
+
+/// `input` or `input prompt`, the next line of input without its line ending, or an empty
+/// string at the end of the input.
+fn input(_: &mut StackFrame, io: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let line = prompted_line("builtins.input", io, inputs)?;
+    Ok(Value::String(line.unwrap_or_default()))
+}
+
+/// `read_line` or `read_line prompt`, the next line of input without its line ending, or
+/// `()` at the end of the input, to stop reading at.
+fn read_line(_: &mut StackFrame, io: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let line = prompted_line("builtins.read_line", io, inputs)?;
+    Ok(line.map_or(Value::Empty, Value::String))
+}
+
+/// `read_number` or `read_number prompt`, the number written on the next line of input.
+fn read_number(_: &mut StackFrame, io: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.read_number";
+    let line = prompted_line(key, io, inputs)?.unwrap_or_default();
+
+    match line.trim().replace('_', "").parse::<f64>() {
+        Ok(n) => Ok(Value::Number(n)),
+        Err(_) => Err(Err {
+            message: t!("errors.read_number_e", a = t!(key), b = line),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// Writes the prompt, if given, and reads the next line of input, `None` at its end.
+fn prompted_line(key: &str, io: &Io, inputs: &[Value]) -> Result<Option<String>, Err> {
+    match inputs {
+        // called without a prompt, as `input ()`
+        [] | [Value::Empty] => {}
+        [Value::String(_)] => {
+            io.print(&string_arg(key, inputs, 0)?)?;
+        }
+        [val] => return Err(argument_e(key, 0, &t!("types.string"), val)),
+        _ => arity(key, inputs, 0, 1)?,
+    }
+
+    let mut line = String::new();
+    let read = io.input.borrow_mut().read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+    Ok(Some(line))
+}
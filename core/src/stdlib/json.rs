@@ -0,0 +1,74 @@
+//! JSON, written and read as plain JSON documents: arrays as JSON arrays, objects as JSON
+//! objects of their fields and `()` as null. Reading takes the serialized form of values
+//! as well, so the values written by a Rust program with `serde_json` read back as they
+//! were.
+
+use super::{arity, bind, string_arg};
+use crate::{
+    error::{Err, ErrorReason},
+    eval::value::Value,
+    runtime::{Io, StackFrame, VTable},
+};
+use serde_json::{Map, Number, Value as Json};
+
+pub(super) fn load(frame: &mut VTable) {
+    bind(
+        frame,
+        &[
+            ("builtins.json_parse", json_parse),
+            ("builtins.json_stringify", json_stringify),
+        ],
+    );
+}
+
+/// `json_parse s`, the value of the JSON document `s`. Arrays must hold items of one type.
+fn json_parse(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.json_parse";
+    arity(key, inputs, 1, 1)?;
+    let s = string_arg(key, inputs, 0)?;
+
+    serde_json::from_str(&s).map_err(|e| Err {
+        message: t!("errors.json_parse_e", a = t!(key), b = e),
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    })
+}
+
+/// `json_stringify val`, the JSON document of `val`, with the fields of objects in the
+/// order of their names.
+fn json_stringify(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.json_stringify";
+    arity(key, inputs, 1, 1)?;
+
+    Ok(Value::String(to_json(&inputs[0])?.to_string()))
+}
+
+fn to_json(val: &Value) -> Result<Json, Err> {
+    let json = match val {
+        // whole numbers are written without a fraction, as they are printed
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < 2f64.powi(53) => Json::from(*n as i64),
+        Value::Number(n) => Number::from_f64(*n).map_or(Json::Null, Json::Number),
+        Value::Bool(b) => Json::Bool(*b),
+        Value::String(s) => Json::String(s.clone()),
+        Value::Empty => Json::Null,
+        Value::Array(_, vals) => Json::Array(vals.iter().map(to_json).collect::<Result<_, _>>()?),
+        Value::Object { body, .. } => {
+            let mut map = Map::new();
+            for (field, (_, val)) in body {
+                map.insert(field.clone(), to_json(val)?);
+            }
+            Json::Object(map)
+        }
+        Value::Assignment(val) => to_json(val)?,
+        val => {
+            return Err(Err {
+                message: t!("errors.serialize_e", a = val.value_type().string()),
+                reason: ErrorReason::Runtime,
+                span: None,
+                trace: None,
+            })
+        }
+    };
+    Ok(json)
+}
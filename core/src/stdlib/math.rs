@@ -0,0 +1,199 @@
+//! The math module, bound with the builtins as `math` and its members accessed as
+//! `math::sqrt 2`. Numbers are `f64`, and a function given numbers it is not defined for
+//! raises an error instead of returning NaN.
+
+use super::{arity, bind, integer_arg, number_arg};
+use crate::{
+    error::{Err, ErrorReason},
+    eval::value::Value,
+    runtime::{Io, StackFrame, VTable},
+};
+use std::{collections::HashMap, f64::consts};
+
+pub(super) fn load(frame: &mut VTable) {
+    let mut vt = VTable(HashMap::new());
+    bind(
+        &mut vt,
+        &[
+            ("math.sqrt", sqrt),
+            ("math.pow", pow),
+            ("math.exp", exp),
+            ("math.log", log),
+            ("math.floor", floor),
+            ("math.ceil", ceil),
+            ("math.round", round),
+            ("math.abs", abs),
+            ("math.min", min),
+            ("math.max", max),
+            ("math.sin", sin),
+            ("math.cos", cos),
+            ("math.tan", tan),
+            ("math.asin", asin),
+            ("math.acos", acos),
+            ("math.atan", atan),
+            ("math.gcd", gcd),
+            ("math.is_prime", is_prime),
+            ("math.int_div", int_div),
+        ],
+    );
+    vt.set(t!("math.pi"), Value::Number(consts::PI));
+    vt.set(t!("math.e"), Value::Number(consts::E));
+
+    let name = t!("builtins.math");
+    frame.set(name.clone(), Value::Module { name, vt });
+}
+
+/// `sqrt x`, the square root of `x`, which must not be negative.
+fn sqrt(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let x = unary("math.sqrt", inputs)?;
+    defined("math.sqrt", x >= 0.0, x)?;
+    Ok(Value::Number(x.sqrt()))
+}
+
+/// `pow x y`, `x` to the power of `y`. A negative `x` has only whole powers.
+fn pow(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "math.pow";
+    arity(key, inputs, 2, 2)?;
+    let x = number_arg(key, inputs, 0)?;
+    let y = number_arg(key, inputs, 1)?;
+
+    defined(key, x >= 0.0 || y.fract() == 0.0, x)?;
+    Ok(Value::Number(x.powf(y)))
+}
+
+/// `exp x`, e to the power of `x`.
+fn exp(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.exp", inputs)?.exp()))
+}
+
+/// `log x`, the natural logarithm of `x`, which must be positive.
+fn log(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let x = unary("math.log", inputs)?;
+    defined("math.log", x > 0.0, x)?;
+    Ok(Value::Number(x.ln()))
+}
+
+/// `floor x`, the greatest whole number not above `x`.
+fn floor(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.floor", inputs)?.floor()))
+}
+
+/// `ceil x`, the least whole number not below `x`.
+fn ceil(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.ceil", inputs)?.ceil()))
+}
+
+/// `round x`, the whole number nearest to `x`, halves rounded away from zero.
+fn round(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.round", inputs)?.round()))
+}
+
+/// `abs x`, the absolute value of `x`.
+fn abs(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.abs", inputs)?.abs()))
+}
+
+/// `min x y`, the lesser of `x` and `y`.
+fn min(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "math.min";
+    arity(key, inputs, 2, 2)?;
+    Ok(Value::Number(
+        number_arg(key, inputs, 0)?.min(number_arg(key, inputs, 1)?),
+    ))
+}
+
+/// `max x y`, the greater of `x` and `y`.
+fn max(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "math.max";
+    arity(key, inputs, 2, 2)?;
+    Ok(Value::Number(
+        number_arg(key, inputs, 0)?.max(number_arg(key, inputs, 1)?),
+    ))
+}
+
+/// `sin x`, the sine of `x` radians.
+fn sin(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.sin", inputs)?.sin()))
+}
+
+/// `cos x`, the cosine of `x` radians.
+fn cos(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.cos", inputs)?.cos()))
+}
+
+/// `tan x`, the tangent of `x` radians.
+fn tan(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.tan", inputs)?.tan()))
+}
+
+/// `asin x`, the angle in radians whose sine is `x`, which must be within -1 and 1.
+fn asin(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let x = unary("math.asin", inputs)?;
+    defined("math.asin", (-1.0..=1.0).contains(&x), x)?;
+    Ok(Value::Number(x.asin()))
+}
+
+/// `acos x`, the angle in radians whose cosine is `x`, which must be within -1 and 1.
+fn acos(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let x = unary("math.acos", inputs)?;
+    defined("math.acos", (-1.0..=1.0).contains(&x), x)?;
+    Ok(Value::Number(x.acos()))
+}
+
+/// `atan x`, the angle in radians whose tangent is `x`.
+fn atan(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    Ok(Value::Number(unary("math.atan", inputs)?.atan()))
+}
+
+/// `gcd a b`, the greatest common divisor of the whole numbers `a` and `b`.
+fn gcd(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "math.gcd";
+    arity(key, inputs, 2, 2)?;
+    let (mut a, mut b) = (integer_arg(key, inputs, 0)?, integer_arg(key, inputs, 1)?);
+
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    Ok(Value::Number(a.abs() as f64))
+}
+
+/// `is_prime n`, whether the whole number `n` is prime.
+fn is_prime(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "math.is_prime";
+    arity(key, inputs, 1, 1)?;
+    let n = integer_arg(key, inputs, 0)?;
+
+    let prime = n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| n % d != 0);
+    Ok(Value::Bool(prime))
+}
+
+/// `int_div a b`, the whole times the whole number `b` goes into `a`, rounded toward
+/// zero. `b` must not be 0.
+fn int_div(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "math.int_div";
+    arity(key, inputs, 2, 2)?;
+    let a = integer_arg(key, inputs, 0)?;
+    let b = integer_arg(key, inputs, 1)?;
+
+    defined(key, b != 0, b as f64)?;
+    Ok(Value::Number((a / b) as f64))
+}
+
+/// The one number argument of the function.
+fn unary(key: &str, inputs: &[Value]) -> Result<f64, Err> {
+    arity(key, inputs, 1, 1)?;
+    number_arg(key, inputs, 0)
+}
+
+/// Asserts that the function is defined for the argument `x`.
+fn defined(key: &str, holds: bool, x: f64) -> Result<(), Err> {
+    match holds {
+        true => Ok(()),
+        false => Err(Err {
+            message: t!("errors.defined_e", a = t!(key), b = x),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        }),
+    }
+}
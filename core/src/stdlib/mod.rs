@@ -0,0 +1,443 @@
+//! The standard library of Speak, native functions loaded with the builtins. Their names
+//! and errors are localized like the keywords of the language.
+
+use super::{
+    error::{Err, ErrorReason},
+    eval::{eval_speak_function, r#type::Type, value::Value, CallerCtx},
+    runtime::{Io, NativeFunction, StackFrame, VTable},
+};
+
+mod array;
+mod errors;
+mod input;
+mod json;
+mod math;
+mod random;
+mod string;
+
+pub(crate) use input::Input;
+pub(crate) use random::seed;
+
+/// Loads the functions of the standard library to the frame.
+pub(crate) fn load(frame: &mut VTable) {
+    array::load(frame);
+    string::load(frame);
+    math::load(frame);
+    random::load(frame);
+    input::load(frame);
+    json::load(frame);
+    errors::load(frame);
+}
+
+/// A native function of the standard library.
+type Native = fn(&mut StackFrame, &Io, &[Value]) -> Result<Value, Err>;
+
+/// Binds the native functions to the frame, each by the name of its builtin key in the
+/// active locale.
+fn bind(frame: &mut VTable, natives: &[(&str, Native)]) {
+    for (key, func) in natives {
+        let name = t!(key);
+        frame.set(
+            name.clone(),
+            Value::NativeFunction(NativeFunction::new(name, *func)),
+        );
+    }
+}
+
+/// Asserts that the native function named by the builtin key got between `min` and `max`
+/// arguments.
+fn arity(key: &str, inputs: &[Value], min: usize, max: usize) -> Result<(), Err> {
+    if (min..=max).contains(&inputs.len()) {
+        return Ok(());
+    }
+
+    let message = match min == max {
+        true => t!("errors.arity_e1", a = t!(key), b = min, c = inputs.len()),
+        false => t!(
+            "errors.arity_e2",
+            a = t!(key),
+            b = min,
+            c = max,
+            d = inputs.len()
+        ),
+    };
+    Err(Err {
+        message,
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    })
+}
+
+/// The error of the argument at `i` not being of the type expected by the native function.
+fn argument_e(key: &str, i: usize, want: &str, got: &Value) -> Err {
+    Err {
+        message: t!(
+            "errors.argument_e",
+            a = t!(key),
+            b = want,
+            c = i + 1,
+            d = got.value_type().string()
+        ),
+        reason: ErrorReason::Runtime,
+        span: None,
+        trace: None,
+    }
+}
+
+/// The array argument at `i`, with the type of its items.
+fn array_arg(key: &str, inputs: &[Value], i: usize) -> Result<(Type, Vec<Value>), Err> {
+    match &inputs[i] {
+        Value::Array(t, vals) => Ok((t.clone(), vals.clone())),
+        val => Err(argument_e(key, i, &t!("types.array"), val)),
+    }
+}
+
+/// The function argument at `i`, which is called back by the native function.
+fn function_arg<'a>(key: &str, inputs: &'a [Value], i: usize) -> Result<&'a Value, Err> {
+    match &inputs[i] {
+        val @ (Value::Function(_) | Value::NativeFunction(_)) => Ok(val),
+        val => Err(argument_e(key, i, &t!("types.function"), val)),
+    }
+}
+
+/// The number argument at `i`.
+fn number_arg(key: &str, inputs: &[Value], i: usize) -> Result<f64, Err> {
+    match &inputs[i] {
+        Value::Number(n) => Ok(*n),
+        val => Err(argument_e(key, i, &t!("types.number"), val)),
+    }
+}
+
+/// The argument at `i` as an index or a count, a whole number that is not negative.
+fn index_arg(key: &str, inputs: &[Value], i: usize) -> Result<usize, Err> {
+    match number_arg(key, inputs, i)? {
+        n if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        n => Err(Err {
+            message: t!("errors.index_arg_e", a = t!(key), b = i + 1, c = n),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// The argument at `i` as a whole number.
+fn integer_arg(key: &str, inputs: &[Value], i: usize) -> Result<i64, Err> {
+    match number_arg(key, inputs, i)? {
+        n if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => Ok(n as i64),
+        n => Err(Err {
+            message: t!("errors.integer_arg_e", a = t!(key), b = i + 1, c = n),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        }),
+    }
+}
+
+/// The string argument at `i`.
+fn string_arg(key: &str, inputs: &[Value], i: usize) -> Result<String, Err> {
+    match &inputs[i] {
+        Value::String(s) => Ok(s.clone()),
+        val => Err(argument_e(key, i, &t!("types.string"), val)),
+    }
+}
+
+/// Calls back into a Speak function, or a native function, with the arguments.
+fn call(stack: &mut StackFrame, func: &Value, args: &[Value]) -> Result<Value, Err> {
+    match eval_speak_function(&CallerCtx::Undefined, stack, func, false, args)? {
+        Value::Assignment(val) => Ok(*val),
+        val => Ok(val),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::Context;
+    use std::io::BufReader;
+
+    /// The value of the program in both the tree walker and the VM, which must agree.
+    fn run(program: &str) -> Result<String, String> {
+        let outcomes = [true, false].map(|tree_walk| {
+            let mut ctx = Context::new(&false);
+            ctx.tree_walk = tree_walk;
+            ctx.exec("en", BufReader::new(program.as_bytes()))
+                .map(|(val, ..)| val.string())
+                .map_err(|err| err.message)
+        });
+        assert_eq!(outcomes[0], outcomes[1], "{program}");
+        outcomes[0].clone()
+    }
+
+    #[test]
+    fn higher_order_functions() {
+        for (program, want) in [
+            ("xs is [1, 2, 3]\nmap xs (_: x number -> number x * 2)", "array ([]number): [Number(2.0), Number(4.0), Number(6.0)]"),
+            ("xs is [1, 2, 3, 4]\nfilter xs (_: x number -> bool x % 2 = 0)", "array ([]number): [Number(2.0), Number(4.0)]"),
+            ("xs is [1, 2, 3]\nreduce xs (_: a number, x number -> number a + x) 10", "16"),
+            ("xs is [3, 1, 2]\nsort xs", "array ([]number): [Number(1.0), Number(2.0), Number(3.0)]"),
+            ("xs is [\"bb\", \"a\", \"cc\"]\nsort_by xs (_: s string -> number len s)", "array ([]string): [String(\"a\"), String(\"bb\"), String(\"cc\")]"),
+            ("xs is [1, 2, 3]\nfind xs (_: x number -> bool x > 1)", "2"),
+            ("xs is [1, 2, 3]\nfind xs (_: x number -> bool x > 5)", ""),
+            ("xs is [1, 2, 3]\nindex_of xs 3", "2"),
+            ("xs is [1, 2, 3]\ncontains xs 4", "false"),
+            ("xs is [1, 2, 3]\nreverse xs", "array ([]number): [Number(3.0), Number(2.0), Number(1.0)]"),
+            ("xs is [1, 2, 3]\njoin xs \"-\"", "1-2-3"),
+            ("step is 0 - 2\nrange 5 0 step", "array ([]number): [Number(5.0), Number(3.0), Number(1.0)]"),
+            ("xs is []\nxs is push xs 1\npush xs 2", "array ([]number): [Number(1.0), Number(2.0)]"),
+            ("xs is [1, 2]\npop xs", "array ([]number): [Number(1.0)]"),
+            // each calls back for its effects
+            (
+                "total is 0\nxs is [1, 2, 3]\neach xs (_: x number -> number\n    total is total + x\n)\ntotal",
+                "6",
+            ),
+        ] {
+            assert_eq!(run(program), Ok(want.to_string()), "{program}");
+        }
+    }
+
+    #[test]
+    fn string_functions() {
+        for (program, want) in [
+            (
+                "split \"a,b\" \",\"",
+                "array ([]string): [String(\"a\"), String(\"b\")]",
+            ),
+            ("trim \"  a \"", "a"),
+            ("upper \"straße\"", "STRASSE"),
+            ("lower \"ÀB\"", "àb"),
+            ("replace \"a-b-c\" \"-\" \"+\"", "a+b+c"),
+            ("starts_with \"habari\" \"hab\"", "true"),
+            ("ends_with \"habari\" \"hab\"", "false"),
+            ("find \"naïve café\" \"café\"", "6"),
+            ("find \"abc\" \"d\"", ""),
+            ("xs is [1, 2]\nfind xs (_: x number -> bool x = 2)", "2"),
+            // a flag is one character of two code points
+            (
+                "chars \"🇰🇪!\"",
+                "array ([]string): [String(\"🇰🇪\"), String(\"!\")]",
+            ),
+            ("char_at \"e\u{301}x\" 1", "x"),
+            ("substring \"mañana\" 1 4", "aña"),
+            ("repeat \"ab\" 3", "ababab"),
+            ("to_number \" 1_000.5\"", "1000.5"),
+            ("len \"ñ\"", "1"),
+        ] {
+            assert_eq!(run(program), Ok(want.to_string()), "{program}");
+        }
+
+        // to_number's error does not shadow the one of indices that are not numbers
+        assert_eq!(
+            run("to_number \"x\""),
+            Err("to_number cannot read a number from \"x\"".to_string())
+        );
+        assert_eq!(
+            run("xs is [1, 2]\ni is \"a\"\nxs[i]"),
+            Err("expected number, provided node is (i) at [3:4]".to_string())
+        );
+    }
+
+    #[test]
+    fn math_module() {
+        for (program, want) in [
+            ("math::sqrt 2.25", Ok("1.5")),
+            ("math::pow 2 10", Ok("1024")),
+            ("math::floor 2.5", Ok("2")),
+            ("math::ceil 2.5", Ok("3")),
+            ("math::round 2.5", Ok("3")),
+            ("math::abs (0 - 3)", Ok("3")),
+            ("math::min 3 4", Ok("3")),
+            ("math::max 3 4", Ok("4")),
+            ("math::cos 0", Ok("1")),
+            ("math::round (math::sin math::pi)", Ok("0")),
+            ("math::log math::e", Ok("1")),
+            ("math::gcd 12 18", Ok("6")),
+            ("math::is_prime 97", Ok("true")),
+            ("math::is_prime 1", Ok("false")),
+            ("math::int_div 7 2", Ok("3")),
+            // domain errors
+            ("math::sqrt (0 - 1)", Err("sqrt is not defined for -1")),
+            ("math::log 0", Err("log is not defined for 0")),
+            ("math::asin 2", Err("asin is not defined for 2")),
+            ("math::pow (0 - 8) 0.5", Err("pow is not defined for -8")),
+            ("math::int_div 1 0", Err("int_div is not defined for 0")),
+            (
+                "math::gcd 1.5 2",
+                Err("gcd expects a whole number as argument 1, but got 1.5"),
+            ),
+        ] {
+            let want = want.map(str::to_string).map_err(str::to_string);
+            assert_eq!(run(program), want, "{program}");
+        }
+    }
+
+    #[test]
+    fn json() {
+        for (program, want) in [
+            ("json_stringify (json_parse \"[1, 2.5]\")", Ok("[1,2.5]")),
+            ("xs is json_parse \"[[1], [2, 3]]\"\nxs[1]", Ok("array ([]number): [Number(2.0), Number(3.0)]")),
+            ("json_parse \"null\"", Ok("")),
+            (
+                "n is \"Osoro\"\np is Person { name n }\nq is json_parse (json_stringify p)\nq.name",
+                Ok("Osoro"),
+            ),
+            ("xs is [true, false]\njson_stringify xs", Ok("[true,false]")),
+            ("json_parse \"[1, true]\"", Err("json_parse could not parse the JSON: an array of number cannot hold an item of type bool")),
+            ("json_parse \"[1,\"", Err("json_parse could not parse the JSON: EOF while parsing a value at line 1 column 3")),
+            ("json_stringify len", Err("values of type function cannot be serialized")),
+        ] {
+            let want = want.map(str::to_string).map_err(str::to_string);
+            assert_eq!(run(program), want, "{program}");
+        }
+    }
+
+    #[test]
+    fn try_and_raise() {
+        let handler = "describe: err Error -> string\n    sprint \"{} at {}: {}\" (err.reason) (err.position) (err.message)\n";
+        for (program, want) in [
+            ("f: -> number\n    1\ntry f describe", Ok("1")),
+            (
+                "f: -> number\n    raise \"bad input\"\ntry f describe",
+                Ok("Runtime error at 4:5: bad input"),
+            ),
+            // errors of natives and of the interpreter are caught alike
+            (
+                "f: -> number\n    math::sqrt 0 - 1\ntry f describe",
+                Ok("Runtime error at 4:5: sqrt is not defined for -1"),
+            ),
+            (
+                "f: -> number\n    n is 1\n    n + \"x\"\ntry f describe",
+                Ok("Runtime error at 5:7: cannot perform operation, '+', on (n) and (x), at [5:7]"),
+            ),
+            (
+                "f: -> number\n    raise \"bad input\"\nf ()",
+                Err("bad input"),
+            ),
+            // only runtime errors are caught
+            (
+                "f: -> number\n    missing\ntry f describe",
+                Err("missing is not defined [4:5]"),
+            ),
+        ] {
+            let want = want.map(str::to_string).map_err(str::to_string);
+            assert_eq!(run(&format!("{handler}{program}")), want, "{program}");
+        }
+    }
+
+    #[test]
+    fn seeded_random() {
+        let program = "xs is [1, 2, 3, 4, 5]\nsprint \"{} {} {} {}\" (random ()) (random_int 1 6) (shuffle xs) (choice xs)";
+        let outcomes = [1, 1, 2].map(|seed| {
+            let mut ctx = Context::new(&false);
+            ctx.seed = Some(seed);
+            ctx.exec("en", BufReader::new(program.as_bytes()))
+                .map(|(val, ..)| val.string())
+                .map_err(|err| err.message)
+        });
+        assert_eq!(outcomes[0], outcomes[1]);
+        assert_ne!(outcomes[0], outcomes[2]);
+
+        // the numbers of a seed do not change across versions and platforms
+        let mut ctx = Context::new(&false);
+        ctx.seed = Some(42);
+        let res = ctx.exec("en", BufReader::new("random_int 1 100".as_bytes()));
+        assert_eq!(
+            res.map(|(val, ..)| val.string()).ok(),
+            Some("14".to_string())
+        );
+
+        for (program, want) in [
+            ("random_int 3 3", Ok("3")),
+            ("xs is []\nchoice xs", Ok("")),
+            (
+                "random_int 2 1",
+                Err("random_int cannot choose a number from 2 up to 1"),
+            ),
+        ] {
+            let want = want.map(str::to_string).map_err(str::to_string);
+            assert_eq!(run(program), want, "{program}");
+        }
+    }
+
+    #[test]
+    fn reading_input() {
+        for (program, input, want) in [
+            ("input ()", "Juma\nAmina\n", Ok("Juma")),
+            (
+                "a is input ()\nb is input ()\na + b",
+                "Juma\r\nAmina",
+                Ok("JumaAmina"),
+            ),
+            ("input ()", "", Ok("")),
+            // read_line tells the end of the input from an empty line
+            (
+                "line is read_line ()\nif line = () ? \"end\" ! line",
+                "",
+                Ok("end"),
+            ),
+            (
+                "line is read_line ()\nif line = () ? \"end\" ! line",
+                "\n",
+                Ok(""),
+            ),
+            ("read_number \"> \"", "  42.5\n", Ok("42.5")),
+            (
+                "read_number ()",
+                "four\n",
+                Err("read_number expected a number, but read \"four\""),
+            ),
+            (
+                "input 1",
+                "",
+                Err("argument 1 of input must be of type string, but got (number)"),
+            ),
+        ] {
+            let outcomes = [true, false].map(|tree_walk| {
+                let mut ctx = Context::new(&false);
+                ctx.tree_walk = tree_walk;
+                ctx.set_input(input.as_bytes());
+                ctx.exec("en", BufReader::new(program.as_bytes()))
+                    .map(|(val, ..)| val.string())
+                    .map_err(|err| err.message)
+            });
+            assert_eq!(outcomes[0], outcomes[1], "{program}");
+            let want = want.map(str::to_string).map_err(str::to_string);
+            assert_eq!(outcomes[0], want, "{program}");
+        }
+    }
+
+    #[test]
+    fn argument_errors() {
+        for (program, want) in [
+            ("xs is [1]\nmap xs", "map takes 2 arguments, but got 1"),
+            ("range 1 2 3 4", "range takes 1 to 3 arguments, but got 4"),
+            (
+                "reverse 1",
+                "argument 1 of reverse must be of type array, but got (number)",
+            ),
+            (
+                "xs is [1]\npush xs \"a\"",
+                "push expects an item of type number, but got (string)",
+            ),
+            (
+                "xs is [1]\nfilter xs (_: x number -> number x)",
+                "filter expects its function to return a bool, but it returned (number)",
+            ),
+            (
+                "xs is [true]\nsort xs",
+                "sort can only sort numbers and strings, not (bool)",
+            ),
+            ("range 0 1 0", "range cannot count in steps of 0"),
+            (
+                "repeat \"a\" 1.5",
+                "repeat expects a whole number that is not negative as argument 2, but got 1.5",
+            ),
+            (
+                "to_number \"one\"",
+                "to_number cannot read a number from \"one\"",
+            ),
+        ] {
+            assert_eq!(run(program), Err(want.to_string()), "{program}");
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Random numbers. They are drawn from a SplitMix64 generator written here rather than
+//! taken from a crate, so that a program run with a seed gives the same numbers on every
+//! run and every platform. Without a seed the generator is seeded from the clock.
+
+use super::{arity, array_arg, bind, integer_arg};
+use crate::{
+    error::{Err, ErrorReason},
+    eval::value::Value,
+    runtime::{Io, StackFrame, VTable},
+};
+use std::{
+    cell::Cell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(clock_seed());
+}
+
+pub(super) fn load(frame: &mut VTable) {
+    bind(
+        frame,
+        &[
+            ("builtins.random", random),
+            ("builtins.random_int", random_int),
+            ("builtins.shuffle", shuffle),
+            ("builtins.choice", choice),
+        ],
+    );
+}
+
+/// Seeds the random numbers of the thread, the same seed gives the same numbers.
+pub(crate) fn seed(seed: u64) {
+    STATE.with(|state| state.set(seed));
+}
+
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+/// The next number of the SplitMix64 sequence.
+fn next() -> u64 {
+    STATE.with(|state| {
+        let s = state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state.set(s);
+        let mut z = s;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    })
+}
+
+/// A whole number from 0 up to but not including `n`.
+fn below(n: u64) -> u64 {
+    // numbers past the last whole multiple of n are redrawn, so none is favoured
+    let zone = u64::MAX - u64::MAX % n;
+    loop {
+        let x = next();
+        if x < zone {
+            return x % n;
+        }
+    }
+}
+
+/// `random ()`, a number from 0 up to but not including 1.
+fn random(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.random";
+    // called as `random ()`, with the empty value
+    if !matches!(inputs, [] | [Value::Empty]) {
+        arity(key, inputs, 0, 0)?;
+    }
+
+    Ok(Value::Number((next() >> 11) as f64 / (1u64 << 53) as f64))
+}
+
+/// `random_int lo hi`, a whole number from `lo` up to and including `hi`.
+fn random_int(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.random_int";
+    arity(key, inputs, 2, 2)?;
+    let lo = integer_arg(key, inputs, 0)?;
+    let hi = integer_arg(key, inputs, 1)?;
+
+    if lo > hi {
+        return Err(Err {
+            message: t!("errors.random_int_e", a = t!(key), b = lo, c = hi),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        });
+    }
+    let n = hi.abs_diff(lo) + 1;
+    Ok(Value::Number(lo.wrapping_add(below(n) as i64) as f64))
+}
+
+/// `shuffle xs`, the items of `xs` in a random order.
+fn shuffle(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.shuffle";
+    arity(key, inputs, 1, 1)?;
+    let (t, mut vals) = array_arg(key, inputs, 0)?;
+
+    // Fisher-Yates, each order is as likely
+    for i in (1..vals.len()).rev() {
+        vals.swap(i, below(i as u64 + 1) as usize);
+    }
+    Ok(Value::Array(t, vals))
+}
+
+/// `choice xs`, an item of `xs` chosen at random, or `()` if `xs` is empty.
+fn choice(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.choice";
+    arity(key, inputs, 1, 1)?;
+    let (_, mut vals) = array_arg(key, inputs, 0)?;
+
+    match vals.is_empty() {
+        true => Ok(Value::Empty),
+        false => Ok(vals.swap_remove(below(vals.len() as u64) as usize)),
+    }
+}
@@ -0,0 +1,191 @@
+//! Functions of strings. They count in characters, the grapheme clusters a reader sees as
+//! one character, so that text in any script is indexed and cut where it reads.
+
+use super::{arity, bind, index_arg, string_arg};
+use crate::{
+    error::{Err, ErrorReason},
+    eval::{characters, r#type::Type, value::Value},
+    runtime::{Io, StackFrame, VTable},
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+pub(super) fn load(frame: &mut VTable) {
+    bind(
+        frame,
+        &[
+            ("builtins.split", split),
+            ("builtins.trim", trim),
+            ("builtins.upper", upper),
+            ("builtins.lower", lower),
+            ("builtins.replace", replace),
+            ("builtins.starts_with", starts_with),
+            ("builtins.ends_with", ends_with),
+            ("builtins.find", find),
+            ("builtins.chars", chars),
+            ("builtins.char_at", char_at),
+            ("builtins.substring", substring),
+            ("builtins.repeat", repeat),
+            ("builtins.to_number", to_number),
+        ],
+    );
+}
+
+/// `split s sep`, the parts of `s` between each `sep`, or the characters of `s` if `sep`
+/// is empty.
+fn split(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.split";
+    arity(key, inputs, 2, 2)?;
+    let s = string_arg(key, inputs, 0)?;
+    let sep = string_arg(key, inputs, 1)?;
+
+    let parts = match sep.is_empty() {
+        true => characters(&s),
+        false => s
+            .split(sep.as_str())
+            .map(|part| Value::String(part.to_string()))
+            .collect(),
+    };
+    Ok(Value::Array(Type::String, parts))
+}
+
+/// `trim s`, `s` without the whitespace at its start and end.
+fn trim(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.trim";
+    arity(key, inputs, 1, 1)?;
+    let s = string_arg(key, inputs, 0)?;
+
+    Ok(Value::String(s.trim().to_string()))
+}
+
+/// `upper s`, `s` in upper case.
+fn upper(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.upper";
+    arity(key, inputs, 1, 1)?;
+    let s = string_arg(key, inputs, 0)?;
+
+    Ok(Value::String(s.to_uppercase()))
+}
+
+/// `lower s`, `s` in lower case.
+fn lower(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.lower";
+    arity(key, inputs, 1, 1)?;
+    let s = string_arg(key, inputs, 0)?;
+
+    Ok(Value::String(s.to_lowercase()))
+}
+
+/// `replace s from to`, `s` with each `from` in it replaced by `to`.
+fn replace(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.replace";
+    arity(key, inputs, 3, 3)?;
+    let s = string_arg(key, inputs, 0)?;
+    let from = string_arg(key, inputs, 1)?;
+    let to = string_arg(key, inputs, 2)?;
+
+    Ok(Value::String(s.replace(&from, &to)))
+}
+
+/// `starts_with s prefix`, whether `s` starts with `prefix`.
+fn starts_with(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.starts_with";
+    arity(key, inputs, 2, 2)?;
+    let s = string_arg(key, inputs, 0)?;
+    let prefix = string_arg(key, inputs, 1)?;
+
+    Ok(Value::Bool(s.starts_with(&prefix)))
+}
+
+/// `ends_with s suffix`, whether `s` ends with `suffix`.
+fn ends_with(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.ends_with";
+    arity(key, inputs, 2, 2)?;
+    let s = string_arg(key, inputs, 0)?;
+    let suffix = string_arg(key, inputs, 1)?;
+
+    Ok(Value::Bool(s.ends_with(&suffix)))
+}
+
+/// `find s sub`, the index of the character `sub` is first found at in `s`, or `()`.
+/// The name is shared with the `find` of arrays, which is called for an array.
+fn find(stack: &mut StackFrame, io: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.find";
+    if let Some(Value::Array(..)) = inputs.first() {
+        return super::array::find(stack, io, inputs);
+    }
+    arity(key, inputs, 2, 2)?;
+    let s = string_arg(key, inputs, 0)?;
+    let sub = string_arg(key, inputs, 1)?;
+
+    // only matches starting at a character count, not within one
+    Ok(s.grapheme_indices(true)
+        .map(|(at, _)| at)
+        .chain([s.len()])
+        .position(|at| s[at..].starts_with(&sub))
+        .map_or(Value::Empty, |i| Value::Number(i as f64)))
+}
+
+/// `chars s`, the characters of `s`.
+fn chars(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.chars";
+    arity(key, inputs, 1, 1)?;
+    let s = string_arg(key, inputs, 0)?;
+
+    Ok(Value::Array(Type::String, characters(&s)))
+}
+
+/// `char_at s i`, the character of `s` at `i`, or `()` if `s` is shorter.
+fn char_at(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.char_at";
+    arity(key, inputs, 2, 2)?;
+    let s = string_arg(key, inputs, 0)?;
+    let i = index_arg(key, inputs, 1)?;
+
+    Ok(s.graphemes(true)
+        .nth(i)
+        .map_or(Value::Empty, |c| Value::String(c.to_string())))
+}
+
+/// `substring s start end`, the characters of `s` from `start` up to but not including
+/// `end`, as far as `s` goes.
+fn substring(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.substring";
+    arity(key, inputs, 3, 3)?;
+    let s = string_arg(key, inputs, 0)?;
+    let start = index_arg(key, inputs, 1)?;
+    let end = index_arg(key, inputs, 2)?;
+
+    Ok(Value::String(
+        s.graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect(),
+    ))
+}
+
+/// `repeat s n`, `s` repeated `n` times.
+fn repeat(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.repeat";
+    arity(key, inputs, 2, 2)?;
+    let s = string_arg(key, inputs, 0)?;
+    let n = index_arg(key, inputs, 1)?;
+
+    Ok(Value::String(s.repeat(n)))
+}
+
+/// `to_number s`, the number written in `s`.
+fn to_number(_: &mut StackFrame, _: &Io, inputs: &[Value]) -> Result<Value, Err> {
+    let key = "builtins.to_number";
+    arity(key, inputs, 1, 1)?;
+    let s = string_arg(key, inputs, 0)?;
+
+    match s.trim().replace('_', "").parse::<f64>() {
+        Ok(n) => Ok(Value::Number(n)),
+        Err(_) => Err(Err {
+            message: t!("errors.to_number_arg_e", a = t!(key), b = s),
+            reason: ErrorReason::Runtime,
+            span: None,
+            trace: None,
+        }),
+    }
+}
@@ -0,0 +1,222 @@
+//! Translation of Speak programs between locales. A program written in one variant of
+//! Speak is rewritten to another by replacing its keywords, type names, literals and
+//! builtin names, leaving user identifiers, comments, string literals and the layout
+//! of the program unchanged. The `Error` type of caught errors is translated too, and
+//! so are its field names where they follow a `.`.
+
+use super::{
+    error::{Err, ErrorReason, Span},
+    lexer::{tokenize, Kind},
+    locale,
+};
+use std::{collections::HashMap, io::BufReader};
+use unicode_normalization::UnicodeNormalization;
+
+/// The fields of the objects caught errors are passed to handlers as.
+const ERROR_FIELDS: [&str; 3] = ["error.reason", "error.message", "error.position"];
+
+/// Translates the Speak program in `source` from the `from` locale to the `to` locale.
+/// If a user identifier of the program is a keyword or builtin name in the target
+/// locale the program is not translated, and an error is returned for each identifier.
+pub fn translate_source(source: &str, from: &str, to: &str) -> Result<String, Vec<Err>> {
+    for speak in [from, to] {
+        if !locale::is_available(speak) {
+            return Err(vec![Err {
+                message: t!("errors.translate_source_e1", a = speak),
+                reason: ErrorReason::System,
+                span: None,
+                trace: None,
+            }]);
+        }
+    }
+
+    // the program is lexed in the locale it is written in
+    let active = locale::locale();
+    locale::set_locale(from);
+    let mut tokens = Vec::new();
+    let res = tokenize(&mut BufReader::new(source.as_bytes()), &mut tokens, false);
+    locale::set_locale(&active);
+    res.map_err(|err| vec![err])?;
+
+    // types written as names in signatures are translated with the keywords
+    let mut keywords = locale::keyword_keys();
+    keywords.extend(["types.range", "error.name"]);
+    let words = |keys: &[&'static str], speak: &str| {
+        keys.iter()
+            .map(|key| (t!(key, locale = speak), *key))
+            .collect::<HashMap<_, _>>()
+    };
+    let (from_words, to_words) = (words(&keywords, from), words(&keywords, to));
+    let from_fields = words(&ERROR_FIELDS, from);
+
+    let mut lines = source
+        .split_inclusive('\n')
+        .map(|line| line.chars().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    // (line, start column, length, replacement) of the words to replace
+    let mut edits = Vec::new();
+    let mut collisions = Vec::new();
+    for (i, tok) in tokens.iter().enumerate() {
+        if !matches!(
+            tok.kind,
+            Kind::Identifier
+                | Kind::If
+                | Kind::For
+                | Kind::In
+                | Kind::Match
+                | Kind::While
+                | Kind::ContinueLiteral
+                | Kind::BreakLiteral
+                | Kind::TrueLiteral
+                | Kind::FalseLiteral
+                | Kind::AssignOp
+                | Kind::TypeName(_)
+        ) {
+            continue;
+        }
+
+        let line = &lines[tok.position.line - 1];
+        let mut start = tok.position.column - 1;
+
+        // array types are translated by their element type, `[]number`
+        while line.get(start) == Some(&'[') && line.get(start + 1) == Some(&']') {
+            start += 2;
+        }
+
+        let len = line[start..].iter().take_while(|c| is_word_char(c)).count();
+        let word = line[start..start + len].iter().collect::<String>();
+        let word = word.nfc().collect::<String>();
+
+        // the names after a `.` are field names, not keywords
+        let field = i > 0 && matches!(tokens[i - 1].kind, Kind::AccessorOp);
+        let known = match field {
+            true => &from_fields,
+            false => &from_words,
+        };
+        match known.get(&word) {
+            Some(key) => edits.push((tok.position.line - 1, start, len, t!(key, locale = to))),
+            None => {
+                if let (Kind::Identifier, Some(key)) = (&tok.kind, to_words.get(&word)) {
+                    collisions.push(Err {
+                        message: t!(
+                            "errors.translate_source_e2",
+                            a = word,
+                            b = tok.position.string(),
+                            c = key,
+                            d = to
+                        ),
+                        reason: ErrorReason::Syntax,
+                        span: Some(Span::at(&tok.position)),
+                        trace: None,
+                    });
+                }
+            }
+        }
+    }
+
+    if !collisions.is_empty() {
+        return Err(collisions);
+    }
+
+    // replace from the end of each line so the columns of earlier words hold
+    edits.sort_by_key(|edit| std::cmp::Reverse((edit.0, edit.1)));
+    for (line, start, len, word) in edits {
+        lines[line].splice(start..start + len, word.chars());
+    }
+
+    Ok(lines.into_iter().flatten().collect())
+}
+
+/// Whether the char can be part of a keyword or an identifier, as delimited by the lexer.
+fn is_word_char(c: &char) -> bool {
+    !c.is_whitespace() && !"\":,.!?=(){}[]~-+*/%&|><".contains(*c)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::Context;
+    use std::{env, fs};
+
+    #[test]
+    fn translate_samples() {
+        let cwd = env::current_dir().expect("there must be a wd");
+
+        // the swahili samples translate to english and back unchanged
+        for sample in ["fizzbuzz_swa", "fib_swa", "collatz_swa", "habari_dunia"] {
+            let source = fs::read_to_string(cwd.join(format!("samples/{sample}.spk")))
+                .expect("the sample exists");
+
+            let en = translate_source(&source, "sw", "en").expect("translates to english");
+            let sw = translate_source(&en, "en", "sw").expect("translates to swahili");
+            assert_eq!(source, sw, "{sample} round trip");
+        }
+
+        let source =
+            fs::read_to_string(cwd.join("samples/fizzbuzz.spk")).expect("the sample exists");
+        let sw = translate_source(&source, "en", "sw").expect("translates to swahili");
+        assert_eq!(
+            sw,
+            r#"// speak fizzbuzz implementation
+fizzbuzz: n nambari -> mlolongo
+    kama n % 15 = 0 ? "FizzBuzz"
+    kama n % 3 = 0 ? "Fizz"
+    kama n % 5 = 0 ? "Buzz"
+    mlolongo_andika n
+
+// call with 45
+andika "fizzbuzz result for {} is {}" 45 (fizzbuzz 45)"#
+        );
+
+        // array types and values
+        let sw = translate_source("xs is []\nf: x []number -> bool\n    true", "en", "sw")
+            .expect("translates to swahili");
+        assert_eq!(sw, "xs ni []\nf: x []nambari -> bool\n    kweli");
+        let sw = translate_source("f: r interval -> number\n    0", "en", "sw")
+            .expect("translates to swahili");
+        assert_eq!(sw, "f: r kipindi -> nambari\n    0");
+    }
+
+    #[test]
+    fn translate_caught_errors() {
+        let source = "describe: err Error -> string
+    sprint \"{} at {}\" (err.message) (err.position)
+
+f: -> number
+    raise \"bad input\"
+
+try f describe
+";
+        let sw = translate_source(source, "en", "sw").expect("translates to swahili");
+        assert!(sw.starts_with("describe: err Hitilafu -> mlolongo\n"));
+        assert!(sw.contains("(err.ujumbe) (err.mahali)"));
+        assert_eq!(
+            translate_source(&sw, "sw", "en").expect("translates to english"),
+            source
+        );
+
+        // the translation handles the error as the program does
+        for (program, speak) in [(source, "en"), (sw.as_str(), "sw")] {
+            let (val, ..) = Context::new(&false)
+                .exec(speak, BufReader::new(program.as_bytes()))
+                .expect("the error is caught");
+            assert_eq!(val.string(), "bad input at 5:5", "{speak}");
+        }
+    }
+
+    #[test]
+    fn translate_collisions() {
+        let errs = translate_source("kama is 1\nprintln kama\nkwa is 2", "en", "sw")
+            .expect_err("identifiers collide with swahili keywords");
+
+        assert_eq!(errs.len(), 3);
+        assert!(errs[0].message.contains("kama"));
+        assert!(errs[0].message.contains("1:1"));
+        assert!(errs[2].message.contains("kwa"));
+        assert!(errs[2].message.contains("literals.for"));
+
+        let errs = translate_source("x is 1", "en", "xx").expect_err("the locale is unknown");
+        assert_eq!(errs[0].reason, ErrorReason::System);
+    }
+}
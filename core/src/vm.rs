@@ -0,0 +1,895 @@
+//! The stack VM running Speak programs compiled to bytecode. Values are computed on a
+//! value stack and calls run on a stack of call frames, so deep recursion does not grow
+//! the interpreter's own stack. The locals of functions are bound in slots of their call
+//! frames, other names in the same stack frames the tree walker binds them in, and
+//! errors are made by the same functions, so that both evaluate programs alike.
+
+use super::{
+    bytecode::{Chunk, Op, Proto},
+    error::Err,
+    eval::{
+        array_target, array_value, assign, assign_e, binary_value, bind_arguments, condition_value,
+        field_target, field_value, guard_value, identifier_value, index_value, indexable,
+        loop_items, match_pattern, member_value, negate_identifier_e, no_return_e, not_callable_e,
+        outside_loop_e, returns, set_field, set_index, slice_value, to_index,
+        value::{Function, Value},
+        LoopItems,
+    },
+    lexer::Position,
+    parser::{MatchArm, Node},
+    runtime::{Io, StackFrame, VTable},
+};
+use std::{collections::HashMap, rc::Rc};
+
+/// CallFrame is a call to a Speak function, or the program, being run.
+struct CallFrame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    /// The height of the value stack when the call was made.
+    base: usize,
+    /// The stack frames of the caller, restored when the call returns. The call runs on
+    /// the stack frames the function was defined in. None for the program.
+    caller: Option<StackFrame>,
+    /// The function called, none for the program.
+    proto: Option<Rc<Proto>>,
+    /// The position of the call, where errors in the function are located.
+    at: Option<Position>,
+    /// The number of calls that took over the call frame by tail calls.
+    elided: usize,
+    /// The values of the locals of the function, by slot, none for those not bound.
+    slots: Vec<Option<Value>>,
+}
+
+/// Loop is a for or while loop being run.
+struct Loop {
+    /// The loop variable and the items left, none for a while loop.
+    items: Option<(String, LoopItems)>,
+    /// The stack frames before the loop, which are restored when it ends as the loop
+    /// body runs in a frame of its own.
+    saved: StackFrame,
+    in_loop: bool,
+    /// The value of the loop, set by a statement of the body with a value.
+    value: Value,
+}
+
+pub struct Vm<'a> {
+    stack: &'a mut StackFrame,
+    values: Vec<Value>,
+    calls: Vec<CallFrame>,
+    loops: Vec<Loop>,
+    /// Whether continue and break are in a loop, as the tree walker's `CallerCtx`.
+    in_loop: bool,
+    saved_in_loop: Vec<bool>,
+}
+
+impl<'a> Vm<'a> {
+    /// Creates a VM binding names in the provided stack frames.
+    pub fn new(stack: &'a mut StackFrame) -> Self {
+        Vm {
+            stack,
+            values: Vec::new(),
+            calls: Vec::new(),
+            loops: Vec::new(),
+            in_loop: false,
+            saved_in_loop: Vec::new(),
+        }
+    }
+
+    /// Runs the compiled program, returning its value. If the program fails, the stack
+    /// frames are restored to those of the program's top level.
+    pub fn run(&mut self, chunk: Rc<Chunk>) -> Result<Value, Err> {
+        self.calls.push(CallFrame {
+            chunk,
+            ip: 0,
+            base: 0,
+            caller: None,
+            proto: None,
+            at: None,
+            elided: 0,
+            slots: Vec::new(),
+        });
+
+        let top = self.stack.clone();
+        let res = self.dispatch();
+        if res.is_err() {
+            *self.stack = top;
+        }
+
+        // errors in functions are located at the innermost call, and traced through the
+        // calls running
+        let res = res.map_err(|err| {
+            self.calls
+                .iter()
+                .rev()
+                .fold(err, |err, call| match (&call.proto, &call.at) {
+                    (Some(proto), Some(at)) => err
+                        .or_at(at)
+                        .called(proto.name.clone(), call.elided)
+                        .called_at(at),
+                    _ => err,
+                })
+        });
+
+        self.values.clear();
+        self.calls.clear();
+        self.loops.clear();
+        self.in_loop = false;
+        self.saved_in_loop.clear();
+        res
+    }
+
+    fn pop(&mut self) -> Value {
+        self.values.pop().expect("the operand was pushed")
+    }
+
+    fn dispatch(&mut self) -> Result<Value, Err> {
+        let mut chunk = Rc::clone(&self.calls.last().expect("a call is running").chunk);
+        let mut ip = 0;
+
+        loop {
+            let op = chunk.ops[ip];
+            ip += 1;
+
+            match op {
+                Op::Const(idx) => self.values.push(chunk.consts[idx as usize].clone()),
+                Op::Load(node) => {
+                    let val = identifier_value(&chunk.nodes[node as usize], self.stack)?;
+                    self.values.push(val);
+                }
+                Op::LoadLocal { slot, node } => {
+                    let call = self.calls.last().expect("a call is running");
+                    let val = match &call.slots[slot as usize] {
+                        Some(val) => val.clone(),
+                        None => identifier_value(&chunk.nodes[node as usize], self.stack)?,
+                    };
+                    self.values.push(val);
+                }
+                Op::Member(node) => {
+                    let val = member_value(&chunk.nodes[node as usize], self.stack)?;
+                    self.values.push(val);
+                }
+                Op::Fail(idx) => return Err(chunk.errs[idx as usize].clone()),
+                Op::NegateIdent(node) => {
+                    if let Node::UnaryExpression {
+                        operand, position, ..
+                    } = &chunk.nodes[node as usize]
+                    {
+                        return Err(negate_identifier_e(operand, position, self.stack));
+                    }
+                }
+                Op::Array(node) => {
+                    let node = &chunk.nodes[node as usize];
+                    if let Node::ArrayLiteral { value, .. } = node {
+                        let items = self.values.split_off(self.values.len() - value.len());
+                        self.values.push(array_value(node, items)?);
+                    }
+                }
+                Op::Object(node) => {
+                    if let Node::ObjectLiteral { name, value, .. } = &chunk.nodes[node as usize] {
+                        let fields = self.values.split_off(self.values.len() - value.len());
+                        let body = value
+                            .iter()
+                            .zip(fields)
+                            .map(|((field_name, _), val)| {
+                                (field_name.clone(), (val.value_type(), val))
+                            })
+                            .collect();
+                        self.values.push(Value::Object {
+                            name: name.clone(),
+                            body,
+                        });
+                    }
+                }
+                Op::Binary(node) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let val = binary_value(&chunk.nodes[node as usize], left, right)?;
+                    self.values.push(val);
+                }
+                Op::Field(node) => {
+                    if let Node::BinaryExpression {
+                        left_operand,
+                        right_operand,
+                        ..
+                    } = &chunk.nodes[node as usize]
+                    {
+                        let object = self.pop();
+                        let val = field_value(object, &right_operand.string(), left_operand)?;
+                        self.values.push(val);
+                    }
+                }
+                Op::Assign(node) => {
+                    if let Node::Identifier { value, .. } = &chunk.nodes[node as usize] {
+                        let val = self.pop();
+                        assign(self.stack, value, val.clone());
+                        self.values.push(Value::Assignment(Box::new(val)));
+                    }
+                }
+                Op::AssignLocal { slot, node, top } => {
+                    if let Node::Identifier { value, .. } = &chunk.nodes[node as usize] {
+                        let val = self.pop();
+                        let call = self.calls.last_mut().expect("a call is running");
+                        let slot = &mut call.slots[slot as usize];
+                        match slot.is_some() || top && !self.stack.contains(value) {
+                            true => *slot = Some(val.clone()),
+                            false => assign(self.stack, value, val.clone()),
+                        }
+                        self.values.push(Value::Assignment(Box::new(val)));
+                    }
+                }
+                Op::FieldTarget(node) => {
+                    if let Node::BinaryExpression {
+                        right_operand,
+                        position,
+                        ..
+                    } = &chunk.nodes[node as usize]
+                    {
+                        let object = self.pop();
+                        let (name, body) = field_target(object, &right_operand.string(), position)?;
+                        self.values.push(Value::Object { name, body });
+                    }
+                }
+                Op::SetField(node) => {
+                    if let Node::BinaryExpression {
+                        left_operand,
+                        right_operand,
+                        ..
+                    } = &chunk.nodes[node as usize]
+                    {
+                        let val = self.pop();
+                        if let Value::Object { name, body } = self.pop() {
+                            let object = (name, body);
+                            let val = set_field(
+                                self.stack,
+                                left_operand,
+                                object,
+                                right_operand.string(),
+                                val,
+                            )?;
+                            self.values.push(val);
+                        }
+                    }
+                }
+                Op::AssignError(node) => {
+                    let val = self.pop();
+                    return Err(assign_e(&val, &chunk.nodes[node as usize]));
+                }
+                Op::IndexTarget(node) => {
+                    let val = self.pop();
+                    self.values
+                        .push(indexable(val, &chunk.nodes[node as usize])?);
+                }
+                Op::ArrayTarget(node) => {
+                    let val = self.pop();
+                    let (t, vals) = array_target(val, &chunk.nodes[node as usize])?;
+                    self.values.push(Value::Array(t, vals));
+                }
+                Op::ToIndex(node) => {
+                    let val = self.pop();
+                    let idx = to_index(val, &chunk.nodes[node as usize])?;
+                    self.values.push(Value::Number(idx as f64));
+                }
+                Op::Index => {
+                    let idx = self.pop_index();
+                    let val = self.pop();
+                    self.values.push(index_value(val, idx));
+                }
+                Op::Slice { start, end } => {
+                    let start = start.then(|| self.pop_index());
+                    let end = end.then(|| self.pop_index());
+                    let val = self.pop();
+                    self.values.push(slice_value(val, start, end)?);
+                }
+                Op::SetIndex(node) => {
+                    let val = self.pop();
+                    let idx = self.pop_index();
+                    if let Value::Array(t, vals) = self.pop() {
+                        let val = set_index(
+                            self.stack,
+                            &chunk.nodes[node as usize],
+                            (t, vals),
+                            idx,
+                            val,
+                        )?;
+                        self.values.push(val);
+                    }
+                }
+                Op::Define(idx) | Op::Closure(idx) => {
+                    let proto = &chunk.protos[idx as usize];
+                    let func = Value::Function(Function {
+                        defn: Rc::clone(&proto.defn),
+                        code: Some(Rc::clone(proto)),
+                        env: self.stack.clone(),
+                    });
+                    match op {
+                        Op::Define(_) => {
+                            self.stack.set(proto.name.clone(), func);
+                            self.values.push(Value::Empty);
+                        }
+                        _ => self.values.push(func),
+                    }
+                }
+                Op::Call { argc, node } => {
+                    let fn_value = self.pop();
+                    let args = self.values.split_off(self.values.len() - argc as usize);
+                    let position = chunk.nodes[node as usize].position();
+                    if let Some(call) = self.call(fn_value, &args, position)? {
+                        self.calls.last_mut().expect("a call is running").ip = ip;
+                        chunk = Rc::clone(&call.chunk);
+                        ip = 0;
+                        self.calls.push(call);
+                    }
+                }
+                Op::TailCall { argc, node } => {
+                    let fn_value = self.pop();
+                    let args = self.values.split_off(self.values.len() - argc as usize);
+                    let position = chunk.nodes[node as usize].position();
+
+                    let caller = self.calls.last().and_then(|call| call.proto.as_ref());
+                    let callee = match (&fn_value, caller) {
+                        (Value::Function(func), Some(caller)) => {
+                            Some(proto(func)).filter(|callee| callee.ret == caller.ret)
+                        }
+                        _ => None,
+                    };
+
+                    match (callee, &fn_value) {
+                        // the callee's value is the caller's, so the callee takes over the
+                        // caller's call frame, running on its own stack frames in place of
+                        // the caller's which are not needed again
+                        (Some(callee), Value::Function(func)) => {
+                            let mut vt = bind_arguments(func, &fn_value, &args)
+                                .map_err(|err| err.or_at(position))?;
+                            let slots = slots(&callee, &mut vt);
+                            *self.stack = func.env.clone();
+                            self.stack.push_frame(vt);
+
+                            let call = self.calls.last_mut().expect("a call is running");
+                            call.at = Some(position.clone());
+                            call.elided += 1;
+                            call.slots = slots;
+                            chunk = Rc::clone(&callee.chunk);
+                            call.chunk = Rc::clone(&callee.chunk);
+                            call.proto = Some(callee);
+                            ip = 0;
+                            self.values.truncate(call.base);
+                        }
+                        _ => {
+                            if let Some(call) = self.call(fn_value, &args, position)? {
+                                self.calls.last_mut().expect("a call is running").ip = ip;
+                                chunk = Rc::clone(&call.chunk);
+                                ip = 0;
+                                self.calls.push(call);
+                            }
+                        }
+                    }
+                }
+                Op::OutOfLoop => {
+                    self.saved_in_loop.push(self.in_loop);
+                    self.in_loop = false;
+                }
+                Op::RestoreLoop => {
+                    self.in_loop = self.saved_in_loop.pop().expect("the loop was saved");
+                }
+                Op::Jump(target) => ip = target as usize,
+                Op::JumpUnless { target, node } => {
+                    let val = self.pop();
+                    if !condition_value(val, &chunk.nodes[node as usize])? {
+                        ip = target as usize;
+                    }
+                }
+                Op::BlockCheck { end } => match self.values.last() {
+                    Some(Value::ContinueCalled | Value::BreakCalled) => ip = end as usize,
+                    _ => {
+                        self.pop();
+                    }
+                },
+                Op::ForBegin(_) | Op::WhileBegin => {
+                    let items = match op {
+                        Op::ForBegin(node) => {
+                            let val = self.pop();
+                            Some(loop_items(&chunk.nodes[node as usize], val)?)
+                        }
+                        _ => None,
+                    };
+                    self.loops.push(Loop {
+                        items,
+                        saved: self.stack.clone(),
+                        in_loop: self.in_loop,
+                        value: Value::Empty,
+                    });
+                    self.stack.push_frame(VTable(HashMap::new()));
+                    self.in_loop = true;
+                }
+                Op::ForNext { exit } => {
+                    let running = self.loops.last_mut().expect("a loop is running");
+                    let (var, items) = running.items.as_mut().expect("a for loop is running");
+                    match items.next() {
+                        Some(item) => self.stack.set(var.clone(), item),
+                        None => ip = exit as usize,
+                    }
+                }
+                Op::LoopCheck { next, exit } => match self.pop() {
+                    // if the loop returns an non empty value continue current loop
+                    Value::Empty | Value::Assignment(..) => {}
+                    // if contine is called continue outer loop
+                    Value::ContinueCalled => ip = next as usize,
+                    // if break is called break from outer loop
+                    Value::BreakCalled => ip = exit as usize,
+                    // else return value
+                    val => {
+                        self.loops.last_mut().expect("a loop is running").value = val;
+                        ip = exit as usize;
+                    }
+                },
+                Op::ForEnd => {
+                    let ended = self.loops.pop().expect("a loop is running");
+                    *self.stack = ended.saved;
+                    self.in_loop = ended.in_loop;
+                    self.values.push(ended.value);
+                }
+                Op::MatchArm { node, arm, next } => {
+                    let val = self.values.last().expect("the value matched was pushed");
+                    let mut bindings = HashMap::new();
+                    match match_pattern(
+                        &arms(&chunk, node)[arm as usize].pattern,
+                        val,
+                        &mut bindings,
+                    ) {
+                        true => self.stack.push_frame(VTable(bindings)),
+                        false => ip = next as usize,
+                    }
+                }
+                Op::Guard { node, arm, next } => {
+                    let val = self.pop();
+                    if !guard_value(val, &arms(&chunk, node)[arm as usize])? {
+                        self.stack.pop_frame()?;
+                        ip = next as usize;
+                    }
+                }
+                Op::EndArm => {
+                    let val = self.pop();
+                    self.stack.pop_frame()?;
+                    self.pop();
+                    self.values.push(val);
+                }
+                Op::Continue(node) | Op::Break(node) => {
+                    if !self.in_loop {
+                        return Err(outside_loop_e(&chunk.nodes[node as usize]));
+                    }
+                    self.values.push(match op {
+                        Op::Continue(_) => Value::ContinueCalled,
+                        _ => Value::BreakCalled,
+                    });
+                }
+                Op::Return { last } => {
+                    let val = self.pop();
+
+                    // if there's a next evaluation, assignment does not count
+                    if let (Value::Assignment(_), false) = (&val, last) {
+                        continue;
+                    }
+
+                    let call = self.calls.last().expect("a call is running");
+                    let ret = &call.proto.as_ref().expect("a function is running").ret;
+                    if returns(&val, ret) {
+                        let call = self.calls.pop().expect("a call is running");
+                        *self.stack = call.caller.expect("a function is running");
+                        self.values.truncate(call.base);
+                        self.values.push(val);
+
+                        let caller = self.calls.last().expect("the caller is running");
+                        chunk = Rc::clone(&caller.chunk);
+                        ip = caller.ip;
+                    }
+                }
+                Op::NoReturn => {
+                    let call = self.calls.last().expect("a call is running");
+                    let proto = call.proto.as_ref().expect("a function is running");
+                    return Err(no_return_e(&proto.ret));
+                }
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Halt => return Ok(self.pop()),
+            }
+        }
+    }
+
+    fn pop_index(&mut self) -> usize {
+        match self.pop() {
+            Value::Number(idx) => idx as usize,
+            _ => unreachable!("indices are converted by ToIndex"),
+        }
+    }
+
+    /// Calls the function with the arguments. Native functions are run and their value
+    /// pushed, while the call of a Speak function is returned to be run, with its
+    /// arguments bound.
+    fn call(
+        &mut self,
+        fn_value: Value,
+        args: &[Value],
+        position: &Position,
+    ) -> Result<Option<CallFrame>, Err> {
+        let res = match &fn_value {
+            Value::Function(func) => bind_arguments(func, &fn_value, args).map(|mut vt| {
+                let proto = proto(func);
+                let slots = slots(&proto, &mut vt);
+                let caller = std::mem::replace(self.stack, func.env.clone());
+                self.stack.push_frame(vt);
+                Some(CallFrame {
+                    chunk: Rc::clone(&proto.chunk),
+                    ip: 0,
+                    base: self.values.len(),
+                    caller: Some(caller),
+                    proto: Some(proto),
+                    at: Some(position.clone()),
+                    elided: 0,
+                    slots,
+                })
+            }),
+
+            // stack is used in the mod function only to load
+            Value::NativeFunction(func) => func.1(self.stack, &Io::current(), args).map(|val| {
+                self.values.push(val);
+                None
+            }),
+
+            _ => Err(not_callable_e(&fn_value)),
+        };
+
+        // errors of native functions are located at the call, as are the calls of the
+        // trace made by them
+        res.map_err(|err| err.or_at(position).called_at(position))
+    }
+}
+
+/// The arms of the match expression node of the chunk.
+fn arms(chunk: &Chunk, node: u32) -> &[MatchArm] {
+    match &chunk.nodes[node as usize] {
+        Node::MatchExpr { arms, .. } => arms,
+        _ => unreachable!("match instructions are compiled from match expressions"),
+    }
+}
+
+/// Moves the arguments bound to locals of the function out of its frame, into slots.
+fn slots(proto: &Proto, vt: &mut VTable) -> Vec<Option<Value>> {
+    proto
+        .chunk
+        .locals
+        .iter()
+        .map(|name| vt.0.remove(name))
+        .collect()
+}
+
+/// The compiled function, compiling the functions defined by the tree walker on call.
+fn proto(func: &Function) -> Rc<Proto> {
+    match &func.code {
+        Some(proto) => Rc::clone(proto),
+        None => Rc::new(Proto::compile(Rc::clone(&func.defn))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::Err,
+        eval::r#type::Type,
+        runtime::{Captured, Context},
+    };
+    use std::{env, fs, io::BufReader, thread};
+
+    /// The value of the program, or its error, and the names it binds at its top level.
+    fn outcome(res: Result<String, Err>, ctx: &Context) -> (Result<String, String>, Vec<String>) {
+        let res = res.map_err(|err| {
+            let at = err.span.as_ref().map(|span| span.start.string());
+            format!("{:?}: {} at {:?}", err.reason, err.message, at)
+        });
+
+        // objects are left out, the order of their fields is not stable
+        let mut bindings = Vec::new();
+        if let crate::runtime::StackFrame::Frame { frame, .. } = &ctx.frame {
+            for (name, val) in &frame.borrow().0 {
+                if !matches!(val.value_type(), Type::Object(_)) {
+                    bindings.push(format!("{name} -> {}", val.string()));
+                }
+            }
+        }
+        bindings.sort();
+        (res, bindings)
+    }
+
+    fn run(program: &str, tree_walk: bool) -> (Result<String, String>, Vec<String>) {
+        let program = program.to_string();
+        on_big_stack(move || {
+            let mut ctx = Context::new(&false);
+            ctx.tree_walk = tree_walk;
+            let res = ctx
+                .exec("en", BufReader::new(program.as_bytes()))
+                .map(|(val, ..)| val.string());
+            outcome(res, &ctx)
+        })
+    }
+
+    // the tree walker recurses deeper than the test thread's stack allows
+    fn on_big_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+        thread::Builder::new()
+            .stack_size(1 << 28)
+            .spawn(f)
+            .expect("the thread starts")
+            .join()
+            .expect("the program runs")
+    }
+
+    #[test]
+    fn differential_samples() {
+        let cwd = env::current_dir().expect("there must be a wd");
+        for entry in fs::read_dir(cwd.join("samples")).expect("the samples exist") {
+            let path = entry.expect("the sample exists").path();
+            let name = path.display().to_string();
+            let speak = if name.contains("_swa") || name.contains("habari") {
+                "sw"
+            } else {
+                "en"
+            };
+
+            // the samples print the same output in both
+            let outcomes = [true, false].map(|tree_walk| {
+                let (speak, name) = (speak.to_string(), name.clone());
+                on_big_stack(move || {
+                    let mut ctx = Context::new(&false);
+                    ctx.tree_walk = tree_walk;
+                    let out = Captured::default();
+                    ctx.set_stdout(out.clone());
+                    let res = ctx
+                        .exec_path(&speak, &name)
+                        .map(|val| val.string())
+                        .map_err(|mut errs| errs.remove(0));
+                    (outcome(res, &ctx), out.text())
+                })
+            });
+            assert_eq!(outcomes[0], outcomes[1], "{name}");
+        }
+    }
+
+    #[test]
+    fn differential_programs() {
+        for (program, want) in [
+            // writes in the body of a loop are visible after it, its own names are not
+            (
+                "total is 0\nfor x in [1, 2, 3, 4]\n    if x = 3 ? continue\n    total is total + x\n    y is x\ntotal",
+                Ok("7"),
+            ),
+            ("found is 0\nfor x in [5, 6, 7]\n    if x = 6 ? break\n    found is x\nfound", Ok("5")),
+            ("xs is [0, 0]\nfor i in [0, 1]\n    xs[i] is i + 1\nxs[1]", Ok("2")),
+            ("for x in [1]\n    y is x\ny", Err(())),
+            ("for x in [1]\n    y is x\nx", Err(())),
+            // a statement of the loop body with a value is the loop's value
+            (
+                "first: xs []number -> number\n    for x in xs\n        if x > 2 ? x\n    0\nxs is [1, 2, 3, 4]\nfirst xs",
+                Ok("3"),
+            ),
+            // strings are iterated and indexed by character
+            ("for c in \"ñb\"\n    c", Ok("ñ")),
+            ("s is \"héllo\"\ns[1]", Ok("é")),
+            ("s is \"héllo\"\ns[1..3]", Ok("él")),
+            ("if \"\" ? 1 ! 2", Ok("1")),
+            ("~true", Ok("false")),
+            // functions see the bindings where they are defined, not those of their callers
+            (
+                "inner: n number -> number\n    n + k\nouter: n number -> number\n    k is 5\n    inner n\nouter 1",
+                Err(()),
+            ),
+            (
+                "k is 1\ninner: n number -> number\n    n + k\nouter: n number -> number\n    k is 5\n    inner n\nouter 1",
+                Ok("6"),
+            ),
+            (
+                "make: n number -> function\n    _: x number -> number x * n\ntriple is make 3\nn is 10\ntriple 2",
+                Ok("6"),
+            ),
+            (
+                "compose: f function, g function -> function\n    _: x number -> number f (g x)\ninc is _: x number -> number x + 1\ntwice is compose inc inc\ntwice 1",
+                Ok("3"),
+            ),
+            (
+                "each: xs []number, f function -> number\n    for x in xs\n        f x\n    0\ntotal is 0\nxs is [1, 2, 3]\neach xs (_: x number -> number\n    y is x\n    total is total + y)\ntotal",
+                Ok("6"),
+            ),
+            ("f is _: x number -> number x\nf \"a\"", Err(())),
+            (
+                "count: n number, acc number -> number\n    if n = 0 ? acc\n    count (n - 1) (acc + 1)\ncount 50 0",
+                Ok("50"),
+            ),
+            (
+                "memo is [0, 1]\nfib: n number -> number\n    if memo[n] = () ? memo[n] is (fib n - 1) + (fib n - 2)\n    memo[n]\nfib 15",
+                Ok("610"),
+            ),
+            ("xs is [1, 2, 3, 4]\nxs[1] is 9\nys is xs[1..3]\nlen ys", Ok("2")),
+            ("xs is [1, 2]\nxs[5]", Ok("")),
+            // while loops and ranges run like for loops, ranges counting up to their end
+            (
+                "n is 0\nwhile n < 5\n    n is n + 1\n    if n = 2 ? continue\n    if n = 4 ? break\nn",
+                Ok("4"),
+            ),
+            ("while false\n    y is 1\ny", Err(())),
+            (
+                "total is 0\nfor i in 1..5\n    total is total + i\ntotal",
+                Ok("10"),
+            ),
+            ("for i in 0..1000000000\n    if i = 3 ? i", Ok("3")),
+            ("lo is 2\nfor i in lo..lo - 1\n    i", Ok("")),
+            // ranges are values, passed around like any other
+            (
+                "sum: r interval -> number\n    total is 0\n    for i in r\n        total is total + i\n    total\nr is 1..5\nsum r",
+                Ok("10"),
+            ),
+            ("n is 2\nr is 0..n + 1\nr", Ok("0..3")),
+            ("r is 1..3\nr = 1..3", Ok("true")),
+            ("xs is [1, 2, 3, 4]\nys is xs[1..3]\nlen ys", Ok("2")),
+            ("age is 1\np is Person {\n    age,\n}\np.age is 2\np.age", Ok("2")),
+            ("_ is 3", Ok("")),
+            // the first arm whose pattern matches and whose guard holds is taken, its
+            // names bound in a frame of the arm
+            (
+                "xs is [5, 6, 7]\nmatch xs\n    [] ? 0\n    [first, ..rest] if first > 5 ? 1\n    [first, ..rest] ? first + (len rest)",
+                Ok("7"),
+            ),
+            (
+                "name is \"Ada\"\np is Person { name name }\nmatch p\n    Point { name } ? 1\n    Person { name n } ? n",
+                Ok("Ada"),
+            ),
+            ("x is match 2\n    1 ? \"one\"\nx", Ok("")),
+            ("match 2\n    n ? y is n\ny", Err(())),
+            (
+                "sum: xs []number -> number\n    match xs\n        [] ? 0\n        [x, ..rest] ? x + (sum rest)\nxs is [1, 2, 3]\nsum xs",
+                Ok("6"),
+            ),
+            (
+                "total is 0\nfor x in [1, 2, 3, 4]\n    match x\n        2 ? continue\n        4 ? break\n        _ ? total is total + x\ntotal",
+                Ok("4"),
+            ),
+            // the value of an if expression is that of the last statement of the block
+            // taken, its alternatives chained by `! if`
+            (
+                "grade: n number -> string\n    if n > 89 ?\n        s is \"A\"\n        s\n    ! if n > 79 ? \"B\"\n    !\n        s is \"C\"\n        s\ngrade 85",
+                Ok("B"),
+            ),
+            ("x is 1\nif x > 1 ?\n    x is 2\n! if x > 0 ?\n    x is 3\nx", Ok("3")),
+            (
+                "sign: n number -> number\n    if n < 5 ?\n        if n < 2 ? 0\n        ! 1\n    ! 2\nsign 3",
+                Ok("1"),
+            ),
+            (
+                "total is 0\nfor x in [1, 2, 3]\n    if x = 2 ?\n        total is total + 10\n        continue\n        total is total + 100\n    total is total + x\ntotal",
+                Ok("14"),
+            ),
+            (
+                "count: n number, acc number -> number\n    if n = 0 ? acc\n    ! if n > 0 ?\n        m is n - 1\n        count m (acc + 1)\ncount 50 0",
+                Ok("50"),
+            ),
+            // the locals of functions, bound in slots by the VM, are bound as in frames
+            ("count is 0\ninc: -> number\n    count is count + 1\n    count\ninc ()\ninc ()\ncount", Ok("2")),
+            ("f: n number -> number\n    t is n * 2\n    t\nf 3\nt", Err(())),
+            (
+                "f: n number -> number\n    i is 0\n    while i < n\n        i is i + 1\n        k is i\n    i\nf 3",
+                Ok("3"),
+            ),
+            ("f: -> number\n    for x in [1]\n        k is x\n    k\nf ()", Err(())),
+            (
+                "f: x number -> number\n    t is 0\n    for x in [5, 6]\n        t is t + x\n    t + x\nf 1",
+                Ok("12"),
+            ),
+            ("y is 7\nf: x number, y number -> number\n    x + y\nf 1", Ok("8")),
+            ("f: xs []number -> number\n    xs[0] is 5\n    xs[0]\nys is [1, 2]\nf ys", Ok("5")),
+            ("f: n number -> number\n    match 5\n        n ? n\nf 1", Ok("5")),
+            // runtime errors handed to a handler are located
+            (
+                "h: err Error -> string\n    err.position\ntry (_: -> number 1 / 0) h",
+                Ok("3:23"),
+            ),
+            // errors
+            ("f: n number -> number\n    n / 0\nx is 1\nf x", Err(())),
+            ("f: n number -> number\n    n\nf \"x\"", Err(())),
+            ("f: n number -> string\n    n\nf 1", Err(())),
+            ("break", Err(())),
+            ("x is 1\n~x", Err(())),
+            ("[1, \"a\"]", Err(())),
+            ("x is 1\nx[0]", Err(())),
+            ("xs is [1]\nxs[\"a\"]", Err(())),
+            ("p is P {\n    a 1,\n}\np.b", Err(())),
+            ("f: n number -> number\n    g n\nf 1", Err(())),
+            ("if 1 ? 2", Err(())),
+            ("for x in 1\n    x", Err(())),
+            ("for x in 1..\"a\"\n    x", Err(())),
+            ("r is \"a\"..2", Err(())),
+            ("while 1\n    2", Err(())),
+            ("if true ?\n2", Err(())),
+            ("x is 1\nx 2", Err(())),
+            ("match 1\n    n if n ? 2", Err(())),
+        ] {
+            let tree_walk = run(program, true);
+            let vm = run(program, false);
+            assert_eq!(tree_walk, vm, "{program}");
+            match want {
+                Ok(want) => assert_eq!(vm.0.as_deref(), Ok(want), "{program}"),
+                Err(()) => assert!(vm.0.is_err(), "{program}"),
+            }
+        }
+    }
+
+    #[test]
+    fn deep_calls() {
+        // tail calls take over their caller's frame, other calls do not grow the
+        // interpreter's own stack
+        for (program, want) in [
+            (
+                "count: n number, acc number -> number\n    if n = 0 ? acc\n    count (n - 1) (acc + 1)\ncount 100000 0",
+                "100000",
+            ),
+            (
+                "sum: n number -> number\n    if n = 0 ? 0\n    n + (sum n - 1)\nsum 5000",
+                "12502500",
+            ),
+            (
+                "count: n number -> number\n    if n > 0 ?\n        m is n - 1\n        count m\n    ! n\ncount 100000",
+                "0",
+            ),
+        ] {
+            assert_eq!(run(program, false).0.as_deref(), Ok(want), "{program}");
+        }
+
+        // the tree walker merges tail calls too
+        let program = "count: n number, acc number -> number\n    if n = 0 ? acc\n    count (n - 1) (acc + 1)\ncount 20000 0";
+        assert_eq!(run(program, true).0.as_deref(), Ok("20000"));
+    }
+
+    #[test]
+    fn errors_restore_frames() {
+        let mut ctx = Context::new(&false);
+        let program =
+            "f: n number -> number\n    for x in [1]\n        y is n / 0\n    n\nz is 1\nf 2";
+        let err = ctx
+            .exec("en", BufReader::new(program.as_bytes()))
+            .expect_err("divides by zero");
+        assert_eq!(
+            err.span.expect("the error is located").start.string(),
+            "3:18"
+        );
+
+        // the arguments of the call are not left bound
+        assert!(ctx.frame.get("z").is_some());
+        assert!(ctx.frame.get("n").is_none());
+        assert!(ctx.frame.get("y").is_none());
+    }
+
+    #[test]
+    fn traces() {
+        let program = "inner: n number -> number\n    n + \"x\"\nouter: n number -> number\n    (inner n) + 1\ncount: n number -> number\n    if n = 0 ? outer n\n    count n - 1\ncount 2";
+        for tree_walk in [true, false] {
+            let mut ctx = Context::new(&false);
+            ctx.tree_walk = tree_walk;
+            let err = ctx
+                .exec("en", BufReader::new(program.as_bytes()))
+                .expect_err("adds a string to a number");
+            let trace = err.trace.expect("the error is traced");
+            let trace: Vec<_> = trace
+                .frames
+                .iter()
+                .map(|frame| {
+                    let at = frame.position.as_ref().map(|position| position.string());
+                    (frame.function.as_str(), at, frame.elided)
+                })
+                .collect();
+
+            // both merge the calls of count by tail calls into the last
+            let want = vec![
+                ("inner", Some("4:6".to_string()), 0),
+                ("outer", Some("6:16".to_string()), 0),
+                ("count", Some("7:5".to_string()), 2),
+            ];
+            assert_eq!(trace, want, "tree walk {tree_walk}");
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! A Language Server Protocol server for Speak files, speaking JSON-RPC over stdio.
+//! It publishes the syntax errors of open documents, shows function signatures on
+//! hover, goes to the definitions of names and completes keywords in the active locale.
+
+use core::{
+    error::{Err, ErrorReason},
+    lexer::{Kind, Position, Tok},
+    locale,
+    parser::{Node, Pattern},
+    runtime::Context,
+};
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Request as RequestTrait},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf,
+    PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use std::{collections::HashMap, fmt::Display};
+
+/// Runs the language server over stdin and stdout until the client shuts it down.
+pub fn run_stdio(speak: &str) -> Result<(), Err> {
+    let (connection, io_threads) = Connection::stdio();
+    run(&connection, speak)?;
+
+    // the writer thread finishes once the connection is dropped
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Serves the client on the other end of the connection, reading the documents in the
+/// `speak` locale.
+pub fn run(connection: &Connection, speak: &str) -> Result<(), Err> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        ..Default::default()
+    };
+    let capabilities = serde_json::to_value(capabilities).map_err(system_err)?;
+    connection.initialize(capabilities).map_err(system_err)?;
+
+    let mut server = Server {
+        speak: speak.to_string(),
+        ctx: Context::new(&false),
+        documents: HashMap::new(),
+    };
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req).map_err(system_err)? {
+                    return Ok(());
+                }
+                let resp = server.handle_request(req);
+                connection
+                    .sender
+                    .send(Message::Response(resp))
+                    .map_err(system_err)?;
+            }
+            Message::Notification(not) => {
+                if let Some(diagnostics) = server.handle_notification(not) {
+                    connection
+                        .sender
+                        .send(Message::Notification(diagnostics))
+                        .map_err(system_err)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Document is an open Speak file, analysed on every change.
+struct Document {
+    text: String,
+    tokens: Vec<Tok>,
+    nodes: Vec<Node>,
+    errs: Vec<Err>,
+}
+
+struct Server {
+    speak: String,
+    ctx: Context,
+    documents: HashMap<Url, Document>,
+}
+
+/// Definition is a name bound by an assignment, a function literal or a loop, with the
+/// signature of the function it names.
+struct Definition {
+    name: String,
+    position: Position,
+    signature: Option<String>,
+}
+
+impl Server {
+    fn handle_request(&mut self, req: Request) -> Response {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            HoverRequest::METHOD => params::<HoverParams>(req)
+                .map(|params| serde_json::to_value(self.hover(params)).unwrap_or_default()),
+            GotoDefinition::METHOD => params::<GotoDefinitionParams>(req)
+                .map(|params| serde_json::to_value(self.definition(params)).unwrap_or_default()),
+            Completion::METHOD => params::<CompletionParams>(req)
+                .map(|_| serde_json::to_value(self.completion()).unwrap_or_default()),
+            _ => {
+                return Response::new_err(
+                    id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    req.method,
+                )
+            }
+        };
+
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(err) => {
+                Response::new_err(id, lsp_server::ErrorCode::InvalidParams as i32, err.message)
+            }
+        }
+    }
+
+    /// Updates the open documents, returning the diagnostics of the changed document.
+    fn handle_notification(&mut self, not: Notification) -> Option<Notification> {
+        let (uri, text) = match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification_params::<DidOpenTextDocumentParams>(not)?;
+                (params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut params = notification_params::<DidChangeTextDocumentParams>(not)?;
+                (params.text_document.uri, params.content_changes.pop()?.text)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification_params::<DidCloseTextDocumentParams>(not)?;
+                self.documents.remove(&params.text_document.uri);
+                return None;
+            }
+            _ => return None,
+        };
+
+        let (tokens, nodes, errs) = self.ctx.parse_source(&self.speak, &text);
+        let doc = Document {
+            text,
+            tokens,
+            nodes,
+            errs,
+        };
+
+        let diagnostics = doc
+            .errs
+            .iter()
+            .map(|err| {
+                let range = match &err.span {
+                    Some(span) => Range::new(
+                        lsp_position(&doc.text, &span.start),
+                        lsp_position(
+                            &doc.text,
+                            &Position {
+                                line: span.end.line,
+                                column: span.end.column + 1,
+                            },
+                        ),
+                    ),
+                    None => Range::default(),
+                };
+                Diagnostic {
+                    range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("speak".to_string()),
+                    message: err.message.clone(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        self.documents.insert(uri.clone(), doc);
+        Some(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            PublishDiagnosticsParams {
+                uri,
+                diagnostics,
+                version: None,
+            },
+        ))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let at = params.text_document_position_params;
+        let doc = self.documents.get(&at.text_document.uri)?;
+        let (tok, name) = identifier_at(doc, at.position)?;
+
+        locale::set_locale(&self.speak);
+        let defn = find_definition(&doc.nodes, name, &tok.position)?;
+        let signature = defn.signature?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```speak\n{signature}\n```"),
+            }),
+            range: Some(name_range(&doc.text, &tok.position, name)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let at = params.text_document_position_params;
+        let doc = self.documents.get(&at.text_document.uri)?;
+        let (tok, name) = identifier_at(doc, at.position)?;
+
+        locale::set_locale(&self.speak);
+        let defn = find_definition(&doc.nodes, name, &tok.position)?;
+        Some(GotoDefinitionResponse::Scalar(Location {
+            uri: at.text_document.uri,
+            range: name_range(&doc.text, &defn.position, &defn.name),
+        }))
+    }
+
+    /// Completes the keywords, type names and builtin functions of the active locale.
+    fn completion(&self) -> CompletionResponse {
+        CompletionResponse::Array(
+            locale::keyword_keys()
+                .into_iter()
+                .map(|key| CompletionItem {
+                    // members of the math module are completed with the module name
+                    label: match key.starts_with("math.") {
+                        true => format!(
+                            "{}::{}",
+                            locale::translate(&self.speak, "builtins.math"),
+                            locale::translate(&self.speak, key)
+                        ),
+                        false => locale::translate(&self.speak, key),
+                    },
+                    kind: Some(
+                        match key.starts_with("builtins.") || key.starts_with("math.") {
+                            true => CompletionItemKind::FUNCTION,
+                            false => CompletionItemKind::KEYWORD,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Returns the identifier token under the cursor, with its name.
+fn identifier_at(doc: &Document, at: lsp_types::Position) -> Option<(&Tok, &str)> {
+    let position = speak_position(&doc.text, at);
+    doc.tokens
+        .iter()
+        .find_map(|tok| match (&tok.kind, &tok.str) {
+            (Kind::Identifier, Some(name))
+                if tok.position.line == position.line
+                    && tok.position.column <= position.column
+                    && position.column < tok.position.column + name.chars().count() =>
+            {
+                Some((tok, name.as_str()))
+            }
+            _ => None,
+        })
+}
+
+/// Finds the definition of the name used at the position: the last one before the use,
+/// or else the first one in the document, as functions may be called before they are
+/// defined.
+fn find_definition(nodes: &[Node], name: &str, at: &Position) -> Option<Definition> {
+    let mut defns = Vec::new();
+    for node in nodes {
+        definitions(node, &mut defns);
+    }
+
+    let before =
+        |defn: &Definition| (defn.position.line, defn.position.column) <= (at.line, at.column);
+    let defns = defns
+        .into_iter()
+        .filter(|defn| defn.name == name)
+        .collect::<Vec<_>>();
+    let idx = defns.iter().rposition(before).unwrap_or(0);
+    defns.into_iter().nth(idx)
+}
+
+/// Collects the definitions in the node and its children, in source order.
+fn definitions(node: &Node, defns: &mut Vec<Definition>) {
+    match node {
+        Node::FunctionLiteral { sign, body, .. } => {
+            let (name, args, ret_type) = sign;
+            let args = args
+                .iter()
+                .map(|(arg, arg_type)| format!("{} {}", arg.string(), arg_type.string()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            // anonymous functions define their parameters only
+            if !matches!(name.as_ref(), Node::EmptyIdentifier { .. }) {
+                defns.push(Definition {
+                    name: name.string(),
+                    position: name.position().clone(),
+                    signature: Some(format!(
+                        "{}: {} -> {}",
+                        name.string(),
+                        args,
+                        ret_type.string()
+                    )),
+                });
+            }
+
+            for (arg, arg_type) in &sign.1 {
+                defns.push(Definition {
+                    name: arg.string(),
+                    position: arg.position().clone(),
+                    signature: Some(format!("{} {}", arg.string(), arg_type.string())),
+                });
+            }
+            body.iter().for_each(|stmt| definitions(stmt, defns));
+        }
+        Node::BinaryExpression {
+            operator,
+            left_operand,
+            right_operand,
+            ..
+        } => {
+            if let (Kind::AssignOp, Node::Identifier { value, position }) =
+                (operator, left_operand.as_ref())
+            {
+                defns.push(Definition {
+                    name: value.clone(),
+                    position: position.clone(),
+                    signature: None,
+                });
+            } else {
+                definitions(left_operand, defns);
+            }
+            definitions(right_operand, defns);
+        }
+        Node::ForExpr {
+            variable,
+            iterable,
+            body,
+            ..
+        } => {
+            if let Node::Identifier { value, position } = variable.as_ref() {
+                defns.push(Definition {
+                    name: value.clone(),
+                    position: position.clone(),
+                    signature: None,
+                });
+            }
+            definitions(iterable, defns);
+            body.iter()
+                .flatten()
+                .for_each(|stmt| definitions(stmt, defns));
+        }
+        Node::WhileExpr {
+            condition, body, ..
+        } => {
+            definitions(condition, defns);
+            body.iter()
+                .flatten()
+                .for_each(|stmt| definitions(stmt, defns));
+        }
+        Node::IfExpr {
+            condition,
+            on_true,
+            on_false,
+            ..
+        } => {
+            definitions(condition, defns);
+            on_true
+                .iter()
+                .chain(on_false)
+                .flatten()
+                .for_each(|stmt| definitions(stmt, defns));
+        }
+        Node::MatchExpr { value, arms, .. } => {
+            definitions(value, defns);
+            for arm in arms {
+                pattern_definitions(&arm.pattern, defns);
+                arm.guard
+                    .iter()
+                    .chain([&arm.body])
+                    .for_each(|node| definitions(node, defns));
+            }
+        }
+        Node::FunctionCall {
+            function,
+            arguments,
+            ..
+        } => {
+            definitions(function, defns);
+            arguments.iter().for_each(|arg| definitions(arg, defns));
+        }
+        Node::ArrayLiteral { value, .. } => value.iter().for_each(|n| definitions(n, defns)),
+        Node::ObjectLiteral { value, .. } => value.iter().for_each(|(_, n)| definitions(n, defns)),
+        Node::UnaryExpression { operand, .. } => definitions(operand, defns),
+        _ => {}
+    }
+}
+
+/// Collects the names bound by the pattern of a match arm, in source order.
+fn pattern_definitions(pattern: &Pattern, defns: &mut Vec<Definition>) {
+    match pattern {
+        Pattern::Binding { name, position } => defns.push(Definition {
+            name: name.clone(),
+            position: position.clone(),
+            signature: None,
+        }),
+        Pattern::Array { items, rest, .. } => items
+            .iter()
+            .chain(rest.as_deref())
+            .for_each(|item| pattern_definitions(item, defns)),
+        Pattern::Object { fields, .. } => fields
+            .iter()
+            .for_each(|(_, field)| pattern_definitions(field, defns)),
+        Pattern::Wildcard { .. } | Pattern::Literal(_) => {}
+    }
+}
+
+/// Converts a position of the lexer, in chars, to an LSP position in UTF-16 code units.
+fn lsp_position(text: &str, position: &Position) -> lsp_types::Position {
+    let line = text
+        .lines()
+        .nth(position.line.saturating_sub(1))
+        .unwrap_or("");
+    let character = line
+        .chars()
+        .take(position.column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum::<usize>();
+    lsp_types::Position::new(position.line.saturating_sub(1) as u32, character as u32)
+}
+
+/// Converts an LSP position, in UTF-16 code units, to a position of the lexer in chars.
+fn speak_position(text: &str, position: lsp_types::Position) -> Position {
+    let line = text.lines().nth(position.line as usize).unwrap_or("");
+    let (mut units, mut column) = (0, 1);
+    for c in line.chars() {
+        units += c.len_utf16();
+        if units > position.character as usize {
+            break;
+        }
+        column += 1;
+    }
+    Position {
+        line: position.line as usize + 1,
+        column,
+    }
+}
+
+fn name_range(text: &str, position: &Position, name: &str) -> Range {
+    let end = Position {
+        line: position.line,
+        column: position.column + name.chars().count(),
+    };
+    Range::new(lsp_position(text, position), lsp_position(text, &end))
+}
+
+fn params<P: serde::de::DeserializeOwned>(req: Request) -> Result<P, Err> {
+    serde_json::from_value(req.params).map_err(system_err)
+}
+
+fn notification_params<P: serde::de::DeserializeOwned>(not: Notification) -> Option<P> {
+    serde_json::from_value(not.params).ok()
+}
+
+fn system_err(err: impl Display) -> Err {
+    Err {
+        reason: ErrorReason::System,
+        message: err.to_string(),
+        span: None,
+        trace: None,
+    }
+}
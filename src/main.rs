@@ -1,11 +1,14 @@
+mod lsp;
+
 use clap::{Parser, Subcommand};
 use core::{
-    log::{log_interactive, log_safe_err},
-    runtime::Context,
+    check::check_source, error::ErrorReason, format::format_source, runtime::Context,
+    translate::translate_source,
 };
 use std::{
-    env,
+    env, fs,
     io::{self, BufReader},
+    process,
 };
 
 static SPEAK: &str = "SPEAK";
@@ -21,33 +24,200 @@ struct SpeakCLI {
     /// Log all interpreter debug information.
     #[clap(short, long)]
     verbose: bool,
+
+    /// Load the keywords and messages from a locale definition file, in YAML or TOML.
+    #[clap(short, long)]
+    locale: Option<String>,
+
+    /// Evaluate with the tree-walking interpreter instead of the bytecode VM, for differential testing.
+    #[clap(long)]
+    tree_walk: bool,
+
+    /// Seed the random numbers, so that every run gives the same ones.
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// Write errors and values without ANSI colours.
+    #[clap(long)]
+    no_color: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Runs the `Speak` file provided.
-    Run { file_path: String },
+    Run {
+        file_path: String,
+        /// Check the types of the file first, and run it only if they agree.
+        #[clap(long)]
+        check: bool,
+    },
+    /// Checks the types of the `Speak` files provided, without running them.
+    Check {
+        #[clap(required = true)]
+        file_paths: Vec<String>,
+    },
     ///  Initializes an interactive repl session to start typing Speak expressions.
     Repl,
+    /// Formats the `Speak` files provided, in place.
+    Fmt {
+        /// Check that the files are formatted instead, failing if any is not.
+        #[clap(long)]
+        check: bool,
+        #[clap(required = true)]
+        file_paths: Vec<String>,
+    },
+    /// Starts a language server for `Speak` files, over stdio.
+    Lsp,
+    /// Translates the `Speak` file provided from one language to another.
+    Translate {
+        /// The language the file is written in.
+        #[clap(long)]
+        from: String,
+        /// The language to translate the file to.
+        #[clap(long)]
+        to: String,
+        file_path: String,
+        /// Write the translation to this file instead of stdout.
+        #[clap(short, long)]
+        output: Option<String>,
+    },
 }
 
 fn main() {
     let speak_cli = SpeakCLI::parse();
     let mut ctx = Context::new(&speak_cli.verbose);
+    ctx.tree_walk = speak_cli.tree_walk;
+    ctx.seed = speak_cli.seed;
+    ctx.colors = !speak_cli.no_color;
 
     let mut speak = "en".to_string();
     if let Ok(speak_) = env::var(SPEAK) {
         speak = speak_;
     }
 
+    if let Some(locale_path) = &speak_cli.locale {
+        match ctx.load_locale(locale_path) {
+            Ok(locale) => speak = locale,
+            Err(err) => {
+                ctx.log_err(&err);
+                return;
+            }
+        }
+    }
+
     match speak_cli.command {
-        Commands::Run { file_path } => match ctx.exec_path(&speak, &file_path) {
-            Ok(val) => log_interactive(&format!("{}\n", val.string())),
-            Err(err) => log_safe_err(&err.reason, &err.message),
-        },
+        Commands::Run { file_path, check } => {
+            ctx.type_check = check;
+            match ctx.exec_path(&speak, &file_path) {
+                Ok(val) => ctx.log_interactive(&format!("{}\n", val.string())),
+                Err(errs) => {
+                    for err in errs {
+                        ctx.log_err(&err);
+                    }
+                    process::exit(1);
+                }
+            }
+        }
+        Commands::Check { file_paths } => {
+            let mut failed = false;
+            for file_path in file_paths {
+                let source = match fs::read_to_string(&file_path) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        ctx.log_err(&err.into());
+                        process::exit(1);
+                    }
+                };
+
+                if let Err(errs) = check_source(&source, &speak) {
+                    // warnings are reported, but do not fail the check
+                    failed |= errs.iter().any(|err| err.reason != ErrorReason::Warning);
+                    for err in errs {
+                        ctx.log_err(&err.in_source(Some(&file_path), &source));
+                    }
+                }
+            }
+
+            if failed {
+                process::exit(1);
+            }
+        }
+        Commands::Translate {
+            from,
+            to,
+            file_path,
+            output,
+        } => {
+            let source = match fs::read_to_string(&file_path) {
+                Ok(source) => source,
+                Err(err) => {
+                    ctx.log_err(&err.into());
+                    process::exit(1);
+                }
+            };
+
+            match translate_source(&source, &from, &to) {
+                Ok(translation) => match output {
+                    Some(output) => {
+                        if let Err(err) = fs::write(output, translation) {
+                            ctx.log_err(&err.into());
+                            process::exit(1);
+                        }
+                    }
+                    None => print!("{translation}"),
+                },
+                Err(errs) => {
+                    for err in errs {
+                        ctx.log_err(&err.in_source(Some(&file_path), &source));
+                    }
+                    process::exit(1);
+                }
+            }
+        }
+        Commands::Fmt { check, file_paths } => {
+            let mut unformatted = false;
+            for file_path in file_paths {
+                let source = match fs::read_to_string(&file_path) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        ctx.log_err(&err.into());
+                        process::exit(1);
+                    }
+                };
+
+                match format_source(&source, &speak) {
+                    Ok(formatted) if formatted == source => {}
+                    Ok(_) if check => {
+                        unformatted = true;
+                        eprintln!("{file_path}");
+                    }
+                    Ok(formatted) => {
+                        if let Err(err) = fs::write(&file_path, formatted) {
+                            ctx.log_err(&err.into());
+                            process::exit(1);
+                        }
+                    }
+                    Err(errs) => {
+                        for err in errs {
+                            ctx.log_err(&err.in_source(Some(&file_path), &source));
+                        }
+                        process::exit(1);
+                    }
+                }
+            }
+
+            if unformatted {
+                process::exit(1);
+            }
+        }
+        Commands::Lsp => {
+            if let Err(err) = lsp::run_stdio(&speak) {
+                ctx.log_err(&err);
+            }
+        }
         Commands::Repl => loop {
             let mut input = String::new();
-            log_interactive("\n> ");
+            ctx.log_interactive("\n> ");
 
             match io::stdin().read_line(&mut input) {
                 Ok(_) => {
@@ -58,15 +228,15 @@ fn main() {
 
                     match ctx.exec(&speak, BufReader::new(input.as_bytes())) {
                         Ok((val, _, _)) => {
-                            log_interactive(&val.string());
+                            ctx.log_interactive(&val.string());
                         }
                         Err(err) => {
-                            log_safe_err(&err.reason, &err.message);
+                            ctx.log_err(&err);
                         }
                     }
                 }
                 Err(err) => {
-                    log_safe_err(&core::error::ErrorReason::System, &err.to_string());
+                    ctx.log_err(&err.into());
                 }
             }
         },
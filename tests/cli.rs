@@ -0,0 +1,62 @@
+//! Runs the `speak` subcommands on programs written to a temporary directory.
+
+use std::{env, fs, path::PathBuf, process::Command};
+
+/// Writes the program to a file of its own, returning its path.
+fn program(name: &str, source: &str) -> PathBuf {
+    let dir = env::temp_dir().join("speak_cli");
+    fs::create_dir_all(&dir).expect("temp dir is writable");
+    let path = dir.join(name);
+    fs::write(&path, source).expect("temp dir is writable");
+    path
+}
+
+/// Runs `speak` with the arguments, returning whether it succeeded and its stderr.
+fn speak(args: &[&str]) -> (bool, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_speak"))
+        .arg("--no-color")
+        .args(args)
+        .env_remove("SPEAK")
+        .output()
+        .expect("speak runs");
+    (
+        out.status.success(),
+        String::from_utf8_lossy(&out.stderr).to_string(),
+    )
+}
+
+#[test]
+fn check_locates_errors_in_the_file() {
+    let path = program("check_syntax_error.spk", "x is 1 + * 2\n");
+    let path = path.to_str().expect("the path is valid");
+
+    let (ok, stderr) = speak(&["check", path]);
+    assert!(!ok);
+    assert!(stderr.contains(&format!("--> {path}:1:10")), "{stderr}");
+}
+
+#[test]
+fn run_check_fails_on_type_errors() {
+    let path = program(
+        "run_check_type_error.spk",
+        "add: a number, b number -> number\n    a + b\nadd 1 \"two\"\n",
+    );
+    let path = path.to_str().expect("the path is valid");
+
+    let (ok, stderr) = speak(&["run", "--check", path]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("argument 2 of add must be of type (number), but got (string)"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn fmt_locates_errors_in_the_file() {
+    let path = program("fmt_syntax_error.spk", "x is 1 + * 2\n");
+    let path = path.to_str().expect("the path is valid");
+
+    let (ok, stderr) = speak(&["fmt", "--check", path]);
+    assert!(!ok);
+    assert!(stderr.contains(&format!("--> {path}:1:10")), "{stderr}");
+}
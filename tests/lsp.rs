@@ -0,0 +1,110 @@
+//! Drives `speak lsp` over stdio with a scripted client.
+
+use lsp_server::{Message, Notification, Request, RequestId};
+use serde_json::{json, Value};
+use std::{
+    io::BufReader,
+    process::{Command, Stdio},
+};
+
+#[test]
+fn lsp_session() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_speak"))
+        .arg("lsp")
+        .env_remove("SPEAK")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the server starts");
+    let mut stdin = server.stdin.take().expect("stdin is piped");
+    let mut stdout = BufReader::new(server.stdout.take().expect("stdout is piped"));
+
+    let mut send = |msg: Message| msg.write(&mut stdin).expect("the server reads stdin");
+    let mut recv = || {
+        Message::read(&mut stdout)
+            .expect("the server writes stdout")
+            .expect("the server is running")
+    };
+    let request = |id: i32, method: &str, params: Value| {
+        Message::Request(Request::new(
+            RequestId::from(id),
+            method.to_string(),
+            params,
+        ))
+    };
+    let notification = |method: &str, params: Value| {
+        Message::Notification(Notification::new(method.to_string(), params))
+    };
+    let result = |msg: Message| match msg {
+        Message::Response(resp) => resp.result.expect("the request succeeds"),
+        msg => panic!("expected a response, got {:?}", msg),
+    };
+
+    send(request(1, "initialize", json!({ "capabilities": {} })));
+    let capabilities = result(recv());
+    assert_eq!(capabilities["capabilities"]["hoverProvider"], json!(true));
+    send(notification("initialized", json!({})));
+
+    // diagnostics are published for every syntax error
+    let uri = "file:///tmp/main.spk";
+    let text = "double: n number -> number\n    n * 2\n\nx is (1 + 2\ny is double 21\nz is )\n";
+    send(notification(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": uri, "languageId": "speak", "version": 1, "text": text } }),
+    ));
+    match recv() {
+        Message::Notification(not) => {
+            assert_eq!(not.method, "textDocument/publishDiagnostics");
+            let diagnostics = not.params["diagnostics"].as_array().expect("diagnostics");
+            assert_eq!(diagnostics.len(), 2);
+            assert_eq!(
+                diagnostics[0]["range"]["start"],
+                json!({ "line": 3, "character": 10 })
+            );
+            assert_eq!(
+                diagnostics[1]["range"]["start"],
+                json!({ "line": 5, "character": 5 })
+            );
+        }
+        msg => panic!("expected diagnostics, got {:?}", msg),
+    }
+
+    // hover on a call shows the function's signature
+    let at = json!({ "textDocument": { "uri": uri }, "position": { "line": 4, "character": 7 } });
+    send(request(2, "textDocument/hover", at.clone()));
+    let hover = result(recv());
+    assert_eq!(
+        hover["contents"]["value"],
+        json!("```speak\ndouble: n number -> number\n```")
+    );
+
+    // the definition of the function is its name in the function literal
+    send(request(3, "textDocument/definition", at));
+    let defn = result(recv());
+    assert_eq!(defn["uri"], json!(uri));
+    assert_eq!(
+        defn["range"],
+        json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 6 } })
+    );
+
+    // keywords are completed in the active locale
+    send(request(
+        4,
+        "textDocument/completion",
+        json!({ "textDocument": { "uri": uri }, "position": { "line": 6, "character": 0 } }),
+    ));
+    let completion = result(recv());
+    let labels = completion
+        .as_array()
+        .expect("completion items")
+        .iter()
+        .map(|item| item["label"].as_str().expect("a label"))
+        .collect::<Vec<_>>();
+    assert!(labels.contains(&"if"));
+    assert!(labels.contains(&"println"));
+
+    send(request(5, "shutdown", Value::Null));
+    assert!(matches!(recv(), Message::Response(resp) if resp.error.is_none()));
+    send(notification("exit", Value::Null));
+    assert!(server.wait().expect("the server exits").success());
+}